@@ -0,0 +1,56 @@
+use std::io::{stdout, IsTerminal};
+use std::sync::atomic::{AtomicU32, Ordering};
+
+use windows::Win32::System::Console::{GetConsoleMode, GetConsoleOutputCP, GetStdHandle, SetConsoleMode, SetConsoleOutputCP, CONSOLE_MODE, ENABLE_VIRTUAL_TERMINAL_PROCESSING, STD_OUTPUT_HANDLE};
+
+/// `CP_UTF8` of `Win32_Globalization`
+const CP_UTF8: u32 = 65001;
+
+/// code page of the console before `set_output_utf8`, 0 if not changed
+static ORIGINAL_OUTPUT_CP: AtomicU32 = AtomicU32::new(0);
+
+extern "C" {
+    fn atexit(f: extern "C" fn()) -> i32;
+}
+
+/// the code page is a property of the console, it stays after exit if not restored
+extern "C" fn restore_output_cp() {
+    let cp = ORIGINAL_OUTPUT_CP.swap(0, Ordering::Relaxed);
+    if cp != 0 {
+        let _ = unsafe { SetConsoleOutputCP(cp) };
+    }
+}
+
+/// UTF-8 code page for the console until exit, also by `std::process::exit`,
+/// redirected output to a file or a pipe is raw UTF-8 bytes and not touched
+pub fn set_output_utf8() {
+    if !stdout().is_terminal() {
+        return;
+    }
+    unsafe {
+        let cp = GetConsoleOutputCP();
+        if cp == 0 || cp == CP_UTF8 || SetConsoleOutputCP(CP_UTF8).is_err() {
+            return;
+        }
+        ORIGINAL_OUTPUT_CP.store(cp, Ordering::Relaxed);
+        atexit(restore_output_cp);
+    }
+}
+
+/// ANSI escape codes interpreted by the console, `false` if stdout is not a console or an old one
+pub fn enable_virtual_terminal() -> bool {
+    if !stdout().is_terminal() {
+        return false;
+    }
+    unsafe {
+        let Ok(handle) = GetStdHandle(STD_OUTPUT_HANDLE) else {
+            return false;
+        };
+        let mut mode = CONSOLE_MODE::default();
+        if GetConsoleMode(handle, &mut mode).is_err() {
+            return false;
+        }
+        mode.contains(ENABLE_VIRTUAL_TERMINAL_PROCESSING)
+            || SetConsoleMode(handle, mode | ENABLE_VIRTUAL_TERMINAL_PROCESSING).is_ok()
+    }
+}
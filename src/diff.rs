@@ -0,0 +1,195 @@
+use std::io::Write;
+
+use crate::escape_utils::{escape_bytes_octal, escape_json_str};
+use crate::posix::StModeType;
+use crate::wsl_file::{FmtOptions, WslFileAttributes};
+
+/// a field of `WslFileAttributes` differs, `None` means not set
+#[derive(Debug, PartialEq, Eq)]
+pub struct FieldChange {
+    pub field: &'static str,
+    pub a: Option<String>,
+    pub b: Option<String>,
+}
+
+/// an extended attribute is added(`a` is `None`), removed(`b` is `None`) or changed
+#[derive(Debug, PartialEq, Eq)]
+pub struct AttrChange {
+    pub name: String,
+    pub a: Option<Vec<u8>>,
+    pub b: Option<Vec<u8>>,
+}
+
+/// differences of attributes of two files, both the text and json output are made from it
+#[derive(Debug, Default)]
+pub struct AttrsDiff {
+    pub fields: Vec<FieldChange>,
+    pub attrs: Vec<AttrChange>,
+}
+
+impl AttrsDiff {
+    /// `None` is a file without wslfs nor lxfs metadata, fs type itself is not compared
+    pub fn new(a: Option<&dyn WslFileAttributes>, b: Option<&dyn WslFileAttributes>) -> Self {
+        let mut diff = AttrsDiff::default();
+
+        let mut field = |field: &'static str, get: &dyn Fn(&dyn WslFileAttributes) -> Option<String>| {
+            let a = a.and_then(|a| get(a));
+            let b = b.and_then(|b| get(b));
+            if a != b {
+                diff.fields.push(FieldChange { field, a, b });
+            }
+        };
+        field("uid", &|x| x.get_uid().map(|v| v.to_string()));
+        field("gid", &|x| x.get_gid().map(|v| v.to_string()));
+        field("mode", &|x| x.get_mode().map(|v| format!("{:06o}", v)));
+        // lxfs always has st_rdev, it means nothing for other types of file
+        let is_dev = |x: &dyn WslFileAttributes| x.get_mode().is_some_and(|m| matches!(m.file_type(), StModeType::CHR | StModeType::BLK));
+        field("dev_major", &|x| x.get_dev_major().filter(|_| is_dev(x)).map(|v| v.to_string()));
+        field("dev_minor", &|x| x.get_dev_minor().filter(|_| is_dev(x)).map(|v| v.to_string()));
+
+        let attrs_a = a.map_or(vec![], |a| a.list_attrs());
+        let attrs_b = b.map_or(vec![], |b| b.list_attrs());
+        for (name, value_a) in &attrs_a {
+            let value_b = attrs_b.iter().find(|(n, _)| n == name).map(|(_, v)| *v);
+            if value_b != Some(*value_a) {
+                diff.attrs.push(AttrChange { name: name.clone(), a: Some(value_a.to_vec()), b: value_b.map(<[u8]>::to_vec) });
+            }
+        }
+        for (name, value_b) in &attrs_b {
+            if !attrs_a.iter().any(|(n, _)| n == name) {
+                diff.attrs.push(AttrChange { name: name.clone(), a: None, b: Some(value_b.to_vec()) });
+            }
+        }
+        diff
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.fields.is_empty() && self.attrs.is_empty()
+    }
+
+    pub fn fmt(&self, f: &mut dyn Write, opts: &FmtOptions) -> std::io::Result<()> {
+        //uid:                        1000 --> 0
+        //attr user.xdg.origin.url:   "http://a.url" --> none
+        fn value_display(v: &Option<Vec<u8>>) -> String {
+            let mut out = String::new();
+            if let Some(v) = v {
+                out.push('"');
+                escape_bytes_octal(v, &mut out, true).unwrap();
+                out.push('"');
+            } else {
+                out.push_str("none");
+            }
+            out
+        }
+
+        for c in &self.fields {
+            f.write_fmt(format_args!("{}{} --> {}\n", opts.label(&format!("{}:", c.field)),
+                c.a.as_deref().unwrap_or("none"), c.b.as_deref().unwrap_or("none")))?;
+        }
+        for c in &self.attrs {
+            f.write_fmt(format_args!("{}{} --> {}\n", opts.label(&format!("attr {}:", c.name)), value_display(&c.a), value_display(&c.b)))?;
+        }
+        Ok(())
+    }
+
+    /// `{"changes":[{"field","a","b"}],"attrs":{"added":[{"name","value"}],"removed":[{"name","value"}],"changed":[{"name","a","b"}]}}`,
+    /// attribute values are escaped as `view`
+    pub fn to_json(&self) -> String {
+        fn json_str(s: Option<&str>, out: &mut String) {
+            if let Some(s) = s {
+                out.push('"');
+                escape_json_str(s, &mut *out).unwrap();
+                out.push('"');
+            } else {
+                out.push_str("null");
+            }
+        }
+        fn json_value(v: &[u8], out: &mut String) {
+            let mut escaped = String::new();
+            escape_bytes_octal(v, &mut escaped, true).unwrap();
+            json_str(Some(&escaped), out);
+        }
+
+        let mut out = String::from("{\"changes\":[");
+        for (i, c) in self.fields.iter().enumerate() {
+            if i > 0 {
+                out.push(',');
+            }
+            out += &format!("{{\"field\":\"{}\",\"a\":", c.field);
+            json_str(c.a.as_deref(), &mut out);
+            out += ",\"b\":";
+            json_str(c.b.as_deref(), &mut out);
+            out.push('}');
+        }
+        out += "],\"attrs\":{";
+
+        let sections: [(&str, fn(&AttrChange) -> bool); 3] = [
+            ("added", |c| c.a.is_none()),
+            ("removed", |c| c.b.is_none()),
+            ("changed", |c| c.a.is_some() && c.b.is_some()),
+        ];
+        for (i, (section, filter)) in sections.iter().enumerate() {
+            if i > 0 {
+                out.push(',');
+            }
+            out += &format!("\"{}\":[", section);
+            for (j, c) in self.attrs.iter().filter(|c| filter(c)).enumerate() {
+                if j > 0 {
+                    out.push(',');
+                }
+                out += "{\"name\":";
+                json_str(Some(&c.name), &mut out);
+                match (&c.a, &c.b) {
+                    (Some(a), Some(b)) => {
+                        out += ",\"a\":";
+                        json_value(a, &mut out);
+                        out += ",\"b\":";
+                        json_value(b, &mut out);
+                    },
+                    (Some(v), None) | (None, Some(v)) => {
+                        out += ",\"value\":";
+                        json_value(v, &mut out);
+                    },
+                    (None, None) => {},
+                }
+                out.push('}');
+            }
+            out.push(']');
+        }
+        out += "}}";
+        out
+    }
+}
+
+#[test]
+fn test_attrs_diff() {
+    use crate::lxfs::LxfsParsed;
+    use crate::posix::Mode;
+    use crate::wslfs::WslfsParsed;
+
+    let mut wslfs = WslfsParsed::default();
+    wslfs.set_uid(1000);
+    wslfs.set_gid(1000);
+    wslfs.set_mode(Mode(0o100644));
+    wslfs.set_attr("user.same", b"v");
+    wslfs.set_attr("user.removed", b"old");
+    wslfs.set_attr("user.changed", b"a\"");
+
+    let mut lxfs = LxfsParsed::from(&wslfs);
+    assert!(AttrsDiff::new(Some(&wslfs as &dyn WslFileAttributes), Some(&lxfs as &dyn WslFileAttributes)).is_empty());
+
+    lxfs.set_uid(0);
+    lxfs.rm_attr("user.removed");
+    lxfs.set_attr("user.changed", b"b");
+    lxfs.set_attr("user.added", b"new");
+    let diff = AttrsDiff::new(Some(&wslfs as &dyn WslFileAttributes), Some(&lxfs as &dyn WslFileAttributes));
+    assert_eq!(diff.fields, vec![FieldChange { field: "uid", a: Some("1000".to_owned()), b: Some("0".to_owned()) }]);
+    assert_eq!(diff.attrs.len(), 3);
+    assert_eq!(diff.to_json(), concat!(
+        r#"{"changes":[{"field":"uid","a":"1000","b":"0"}],"#,
+        r#""attrs":{"added":[{"name":"user.added","value":"new"}],"removed":[{"name":"user.removed","value":"old"}],"#,
+        r#""changed":[{"name":"user.changed","a":"a\\\"","b":"b"}]}}"#,
+    ));
+
+    assert_eq!(AttrsDiff::new(None, Some(&lxfs as &dyn WslFileAttributes)).fields.len(), 3);
+}
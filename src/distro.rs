@@ -1,9 +1,10 @@
 use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
 
 use clap::ValueEnum;
 use windows_registry::{Key, CURRENT_USER};
 
-use crate::posix::{load_groups, load_users, Group, User};
+use crate::posix::{load_groups, load_groups_from, load_users, load_users_from, Group, User};
 use crate::path_utils::{is_path_prefix_disk, normalize_path, try_get_abs_path_prefix, try_get_distro_from_unc_path};
 
 #[derive(Clone, Copy, ValueEnum, Debug)]
@@ -30,6 +31,12 @@ pub struct Distro {
     pub name: String,
     pub base_path: PathBuf,
     pub fs_type: Option<FsType>, // None means WSL2
+    /// `Flags` in registry, `None` for the override
+    pub flags: Option<u32>,
+    /// name of the key under Lxss, a GUID, to open it again without searching all distros, `None` for the override
+    pub reg_key: Option<String>,
+    /// the directory of "/" given by `--rootfs`, `None` is `BASE_PATH\rootfs`
+    pub rootfs: Option<PathBuf>,
 
     pub source: DistroSource,
 
@@ -37,6 +44,124 @@ pub struct Distro {
     pub groups: Option<Vec<Group>>,
 }
 
+/// a distro not in registry, from `--distro-root-override`, for tests against a fixture tree or a copied distro
+#[derive(Debug, Clone)]
+pub struct DistroOverride {
+    pub name: String,
+    /// directory contains `rootfs`
+    pub base_path: PathBuf,
+    pub fs_type: FsType,
+}
+
+static DISTRO_OVERRIDE: OnceLock<DistroOverride> = OnceLock::new();
+
+/// "NAME=BASE_PATH,FS_TYPE"
+pub fn parse_distro_override(s: &str) -> Result<DistroOverride, String> {
+    let (name, rest) = s.split_once('=').ok_or("expect NAME=BASE_PATH,FS_TYPE")?;
+    let (base_path, fs_type) = rest.rsplit_once(',').ok_or("expect NAME=BASE_PATH,FS_TYPE")?;
+    if name.is_empty() || base_path.is_empty() {
+        return Err("NAME and BASE_PATH must not be empty".to_owned());
+    }
+    let fs_type = FsType::from_str(fs_type, true)?;
+    Ok(DistroOverride { name: name.to_owned(), base_path: PathBuf::from(base_path), fs_type })
+}
+
+/// the distro is found by name and by path before distros in registry, set once at start
+pub fn set_override(distro_override: DistroOverride) {
+    let _ = DISTRO_OVERRIDE.set(distro_override);
+}
+
+/// the override set at start, passed to the `*_with` loaders
+pub fn get_override() -> Option<&'static DistroOverride> {
+    DISTRO_OVERRIDE.get()
+}
+
+fn try_load_override(o: Option<&DistroOverride>, name: Option<&str>, path: Option<&Path>) -> Option<Distro> {
+    let o = o?;
+    let matched = name.is_some_and(|n| n.eq_ignore_ascii_case(&o.name))
+        || path.is_some_and(|p| normalize_path(&o.base_path).is_ok_and(|base_path| p.starts_with(base_path)));
+    if !matched {
+        return None;
+    }
+    let rootfs = rootfs_path(&o.base_path);
+    Some(Distro {
+        name: o.name.clone(),
+        base_path: o.base_path.clone(),
+        fs_type: Some(o.fs_type),
+        flags: None,
+        reg_key: None,
+        rootfs: None,
+        source: DistroSource::Unknown,
+        users: load_users(&rootfs),
+        groups: load_groups(&rootfs),
+    })
+}
+
+/// a distro tree not in registry from `--rootfs`, like a copied or backed-up distro, for the offline workflow
+#[derive(Debug, Clone)]
+pub struct OfflineDistro {
+    /// the directory of "/"
+    pub rootfs: PathBuf,
+    pub fs_type: FsType,
+    /// instead of `rootfs/etc/passwd`
+    pub passwd: Option<PathBuf>,
+    /// instead of `rootfs/etc/group`
+    pub group: Option<PathBuf>,
+}
+
+/// name of the `--rootfs` distro
+pub const OFFLINE_DISTRO_NAME: &str = "offline";
+
+static OFFLINE_DISTRO: OnceLock<OfflineDistro> = OnceLock::new();
+
+impl OfflineDistro {
+    fn passwd_path(&self) -> PathBuf {
+        self.passwd.clone().unwrap_or_else(|| self.rootfs.join("etc").join("passwd"))
+    }
+
+    fn group_path(&self) -> PathBuf {
+        self.group.clone().unwrap_or_else(|| self.rootfs.join("etc").join("group"))
+    }
+
+    /// warnings for a rootfs which does not look like one, nothing is refused
+    pub fn check(&self) -> Vec<String> {
+        let mut warnings = vec![];
+        if !self.rootfs.is_dir() {
+            warnings.push(format!("rootfs: {} is not a directory", self.rootfs.display()));
+        }
+        if !self.passwd_path().is_file() {
+            warnings.push(format!("no passwd: {}, user names cannot be resolved, use --passwd <FILE> or numeric uids", self.passwd_path().display()));
+        }
+        if !self.group_path().is_file() {
+            warnings.push(format!("no group: {}, group names cannot be resolved, use --group <FILE> or numeric gids", self.group_path().display()));
+        }
+        warnings
+    }
+
+    fn to_distro(&self) -> Distro {
+        Distro {
+            name: OFFLINE_DISTRO_NAME.to_owned(),
+            base_path: self.rootfs.parent().map_or_else(|| self.rootfs.clone(), Path::to_path_buf),
+            fs_type: Some(self.fs_type),
+            flags: None,
+            reg_key: None,
+            rootfs: Some(self.rootfs.clone()),
+            source: DistroSource::Unknown,
+            users: load_users_from(&self.passwd_path()),
+            groups: load_groups_from(&self.group_path()),
+        }
+    }
+}
+
+/// used for all paths without `--distro`, set once at start
+pub fn set_offline(offline: OfflineDistro) {
+    let _ = OFFLINE_DISTRO.set(offline);
+}
+
+pub fn try_load_offline() -> Option<Distro> {
+    OFFLINE_DISTRO.get().map(OfflineDistro::to_distro)
+}
+
 const REG_LXSS: &'static str = r"SOFTWARE\Microsoft\Windows\CurrentVersion\Lxss";
 
 #[allow(non_upper_case_globals)]
@@ -51,21 +176,52 @@ const Flags: &str = "Flags";
 #[allow(non_upper_case_globals)]
 const Version: &str = "Version";
 
+/// bits of `Flags`, `WSL_DISTRIBUTION_FLAGS` and the WSL2 bit
+const DISTRO_FLAGS: &[(u32, &str)] = &[
+    (0x01, "interop enabled"),
+    (0x02, "appendWindowsPath"),
+    (0x04, "drvfs mounting"),
+    (DISTRO_FLAG_WSL2, "WSL2"),
+];
+const DISTRO_FLAG_WSL2: u32 = 0x08;
+
+/// like "0x0000000f interop enabled | appendWindowsPath | drvfs mounting | WSL2"
+pub fn distro_flags_display(flags: u32) -> String {
+    if flags == 0 {
+        return "0".to_owned();
+    }
+    let mut names: Vec<String> = vec![];
+    let mut unknown = flags;
+    for (bit, name) in DISTRO_FLAGS {
+        if flags & bit != 0 {
+            names.push(name.to_string());
+            unknown &= !bit;
+        }
+    }
+    if unknown != 0 {
+        names.push(format!("UNKNOWN({:#010x})", unknown));
+    }
+    format!("{:#010x} {}", flags, names.join(" | "))
+}
+
 pub fn try_load_from_reg_default() -> Option<Distro> {
     let lxss = CURRENT_USER.open(REG_LXSS).ok()?;
     let default_distro_guid = lxss.get_string(DefaultDistribution).ok()?;
-    let mut d = try_load_from_reg_key(lxss.open(default_distro_guid).ok()?)?;
+    let distro_key = lxss.open(&default_distro_guid).ok()?;
+    let mut d = try_load_from_reg_key((default_distro_guid, distro_key))?;
     d.source = DistroSource::Default;
     return Some(d);
 }
 
-pub fn try_load_reg<S: AsRef<str>>(name: S) -> Option<Key> {
+/// the key name and the key of the distro
+pub fn try_load_reg<S: AsRef<str>>(name: S) -> Option<(String, Key)> {
     let lxss = CURRENT_USER.open(REG_LXSS).ok()?;
     lxss.keys().ok()?
-    .filter_map(|k| lxss.open(k).ok())
-    .filter(|k| {
+    .filter_map(|k| lxss.open(&k).ok().map(|key| (k, key)))
+    .filter(|(_, k)| {
         if let Ok(s) = k.get_string(DistributionName) {
-            s.as_str() == name.as_ref()
+            // `wsl -d` and UNC paths take names in any case
+            s.eq_ignore_ascii_case(name.as_ref())
         } else {
             false
         }
@@ -74,13 +230,26 @@ pub fn try_load_reg<S: AsRef<str>>(name: S) -> Option<Key> {
 }
 
 pub fn try_load<S: AsRef<str>>(name: S) -> Option<Distro> {
+    try_load_with(name, get_override())
+}
+
+/// `try_load` with the override `o` instead of the one set at start
+pub fn try_load_with<S: AsRef<str>>(name: S, o: Option<&DistroOverride>) -> Option<Distro> {
+    if let Some(d) = try_load_override(o, Some(name.as_ref()), None) {
+        return Some(d);
+    }
     try_load_reg(name)
     .and_then(try_load_from_reg_key)
 }
 
 pub fn try_load_from_absolute_path<P: AsRef<Path>>(path: P) -> Option<Distro> {
+    try_load_from_absolute_path_with(path, get_override())
+}
+
+/// `try_load_from_absolute_path` with the override `o` instead of the one set at start
+pub fn try_load_from_absolute_path_with<P: AsRef<Path>>(path: P, o: Option<&DistroOverride>) -> Option<Distro> {
     if let Some(n) = try_get_distro_from_unc_path(path.as_ref()) {
-        return try_load(&n.to_string_lossy())
+        return try_load_with(&n.to_string_lossy(), o)
         .and_then(|mut x| {
             x.source = DistroSource::FilePathUNC;
             Some(x)
@@ -89,10 +258,14 @@ pub fn try_load_from_absolute_path<P: AsRef<Path>>(path: P) -> Option<Distro> {
 
     if is_path_prefix_disk(&try_get_abs_path_prefix(path.as_ref())) {
         let path = normalize_path(path.as_ref()).ok()?;
+        if let Some(mut d) = try_load_override(o, None, Some(&path)) {
+            d.source = DistroSource::FilePathDisk;
+            return Some(d);
+        }
         let lxss = CURRENT_USER.open(REG_LXSS).ok()?;
         return lxss.keys().ok()?
-        .filter_map(|k| lxss.open(k).ok())
-        .filter(|k| {
+        .filter_map(|k| lxss.open(&k).ok().map(|key| (k, key)))
+        .filter(|(_, k)| {
             if let Ok(s) = k.get_string(BasePath) {
                 if let Ok(base_path) = normalize_path(&PathBuf::from(&s)) {
                     return path.starts_with(base_path);
@@ -112,8 +285,13 @@ pub fn try_load_from_absolute_path<P: AsRef<Path>>(path: P) -> Option<Distro> {
 }
 
 pub fn try_load_from_current_dir() -> Option<Distro> {
+    try_load_from_current_dir_with(get_override())
+}
+
+/// `try_load_from_current_dir` with the override `o` instead of the one set at start
+pub fn try_load_from_current_dir_with(o: Option<&DistroOverride>) -> Option<Distro> {
     std::env::current_dir().ok()
-    .and_then(try_load_from_absolute_path)
+    .and_then(|p| try_load_from_absolute_path_with(p, o))
     .map(|mut x| {
         x.source = match x.source {
             DistroSource::FilePathUNC => DistroSource::CurrentDirUNC,
@@ -124,17 +302,50 @@ pub fn try_load_from_current_dir() -> Option<Distro> {
     })
 }
 
-pub fn try_load_from_reg_key(distro_key: Key) -> Option<Distro> {
+/// names of all distros in registry, and the override
+pub fn list_names() -> Vec<String> {
+    list_names_with(get_override())
+}
+
+/// `list_names` with the override `o` instead of the one set at start
+pub fn list_names_with(o: Option<&DistroOverride>) -> Vec<String> {
+    let override_name = o.map(|o| o.name.clone());
+    let Ok(lxss) = CURRENT_USER.open(REG_LXSS) else {
+        return override_name.into_iter().collect();
+    };
+    let Ok(keys) = lxss.keys() else {
+        return override_name.into_iter().collect();
+    };
+    keys.filter_map(|k| lxss.open(k).ok())
+    .filter_map(|k| k.get_string(DistributionName).ok())
+    .chain(override_name)
+    .collect()
+}
+
+/// normalized `BasePath` of all distros in registry, and the override
+pub fn list_base_paths() -> Vec<PathBuf> {
+    let override_path = get_override().and_then(|o| normalize_path(&o.base_path).ok());
+    let Ok(lxss) = CURRENT_USER.open(REG_LXSS) else {
+        return override_path.into_iter().collect();
+    };
+    let Ok(keys) = lxss.keys() else {
+        return override_path.into_iter().collect();
+    };
+    keys.filter_map(|k| lxss.open(k).ok())
+    .filter_map(|k| k.get_string(BasePath).ok())
+    .filter_map(|s| normalize_path(&PathBuf::from(&s)).ok())
+    .chain(override_path)
+    .collect()
+}
+
+pub fn try_load_from_reg_key((reg_key, distro_key): (String, Key)) -> Option<Distro> {
     let name: String = distro_key.get_string(DistributionName).ok()?;
     let base_path: String = distro_key.get_string(BasePath).ok()?;
     let base_path = PathBuf::from(&base_path);
 
     // & 0x08 = 0 -> WSL1
-    let is_wsl2 = if let Ok(flags) = distro_key.get_u32(Flags) {
-        (flags & 0x08) != 0
-    } else {
-        false
-    };
+    let flags = distro_key.get_u32(Flags).ok();
+    let is_wsl2 = flags.is_some_and(|flags| (flags & DISTRO_FLAG_WSL2) != 0);
 
     let fs_type = if !is_wsl2 {
         match distro_key.get_u32(Version) {
@@ -146,30 +357,51 @@ pub fn try_load_from_reg_key(distro_key: Key) -> Option<Distro> {
         None
     };
 
-    let groups = load_groups(&base_path.join("rootfs"));
-    let users = load_users(&base_path.join("rootfs"));
+    let rootfs = rootfs_path(&base_path);
+    let groups = load_groups(&rootfs);
+    let users = load_users(&rootfs);
 
     return Some(Distro {
         name,
         base_path,
         fs_type,
+        flags,
+        reg_key: Some(reg_key),
+        rootfs: None,
         source: DistroSource::Unknown,
         users,
         groups,
     });
 }
 
+/// files of a WSL1 distro are in `BasePath\rootfs`
+fn rootfs_path(base_path: &Path) -> PathBuf {
+    base_path.join("rootfs")
+}
+
 impl Distro {
-    pub fn set_fs_type(&mut self, fs_type: Option<FsType>) -> Result<(), ()> {
-        try_load_reg(&self.name).map_or(Err(()), |k| {
-            match fs_type {
-                None => k.remove_value(Version),
-                Some(FsType::Lxfs) => k.set_u32(Version, FsType::Lxfs as u32),
-                Some(FsType::Wslfs) => k.set_u32(Version, FsType::Wslfs as u32),
-            }.map_err(|_| ())?;
-            self.fs_type = fs_type;            
-            Ok(())
-        })
+    /// the directory of "/" of a WSL1 distro, WSL2 has none
+    pub fn rootfs_path(&self) -> PathBuf {
+        self.rootfs.clone().unwrap_or_else(|| rootfs_path(&self.base_path))
+    }
+
+    /// `Version` in registry, `Err` for the override or an error of registry like access denied
+    pub fn set_fs_type(&mut self, fs_type: Option<FsType>) -> Result<(), String> {
+        let Some(reg_key) = &self.reg_key else {
+            return Err(format!("distro: {} is not in registry", &self.name));
+        };
+        let path = format!(r"{}\{}", REG_LXSS, reg_key);
+        // the key may be removed after loaded, `create` would make a new one
+        CURRENT_USER.open(&path).map_err(|ex| format!("cannot open registry key: {}, error: {ex:?}", path))?;
+        // `open` is read only
+        let k = CURRENT_USER.create(&path).map_err(|ex| format!("cannot open registry key: {} to write, error: {ex:?}", path))?;
+        match fs_type {
+            None => k.remove_value(Version),
+            Some(FsType::Lxfs) => k.set_u32(Version, FsType::Lxfs as u32),
+            Some(FsType::Wslfs) => k.set_u32(Version, FsType::Wslfs as u32),
+        }.map_err(|ex| format!("cannot write {} of registry key: {}, error: {ex:?}", Version, path))?;
+        self.fs_type = fs_type;
+        Ok(())
     }
 
     pub fn uid(&self, user_name: &str) -> Option<u32> {
@@ -203,4 +435,193 @@ impl Distro {
             .find(|u| u.gid == gid).and_then(|u| Some(u.name.as_str()))
         )
     }
+
+    /// primary gid of user from `/etc/passwd`
+    pub fn primary_gid(&self, uid: u32) -> Option<u32> {
+        self.users.as_ref()
+        .and_then(|users|
+            users.iter()
+            .find(|u| u.uid == uid).and_then(|u| Some(u.gid))
+        )
+    }
+
+    /// supplementary groups of user from `/etc/group`
+    pub fn supplementary_groups(&self, user_name: &str) -> Vec<&Group> {
+        self.groups.as_ref()
+        .map(|groups|
+            groups.iter()
+            .filter(|g| g.members.iter().any(|m| m == user_name))
+            .collect()
+        )
+        .unwrap_or_default()
+    }
+
+    /// user names like `user_name`, for a typo
+    pub fn similar_user_names(&self, user_name: &str) -> Vec<&str> {
+        similar_names(user_name, self.users.iter().flatten().map(|u| u.name.as_str()))
+    }
+
+    /// group names like `group_name`, for a typo
+    pub fn similar_group_names(&self, group_name: &str) -> Vec<&str> {
+        similar_names(group_name, self.groups.iter().flatten().map(|g| g.name.as_str()))
+    }
+}
+
+/// at most 3 names within 2 edits of `name`, the closest first
+fn similar_names<'a>(name: &str, names: impl Iterator<Item = &'a str>) -> Vec<&'a str> {
+    let mut similar: Vec<(usize, &str)> = names
+        .map(|n| (edit_distance(name, n), n))
+        .filter(|(d, _)| *d <= 2)
+        .collect();
+    similar.sort_by_key(|(d, _)| *d);
+    similar.into_iter().take(3).map(|(_, n)| n).collect()
+}
+
+/// Levenshtein distance of chars
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut diag = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let above = row[j + 1];
+            row[j + 1] = if ca == *cb { diag } else { 1 + diag.min(above).min(row[j]) };
+            diag = above;
+        }
+    }
+    row[b.len()]
+}
+
+#[test]
+fn test_distro_name_resolution() {
+    let d = Distro {
+        name: "test".to_owned(),
+        base_path: PathBuf::new(),
+        fs_type: Some(FsType::Lxfs),
+        flags: None,
+        reg_key: None,
+        rootfs: None,
+        source: DistroSource::Unknown,
+        users: Some(vec![
+            User { name: "root".to_owned(), uid: 0, gid: 0 },
+            User { name: "alice".to_owned(), uid: 1000, gid: 1000 },
+        ]),
+        groups: Some(vec![
+            Group { name: "root".to_owned(), gid: 0, members: vec![] },
+            Group { name: "sudo".to_owned(), gid: 27, members: vec!["alice".to_owned()] },
+            Group { name: "alice".to_owned(), gid: 1000, members: vec![] },
+        ]),
+    };
+
+    assert_eq!(d.uid("alice"), Some(1000));
+    assert_eq!(d.user_name(1000), Some("alice"));
+    assert_eq!(d.primary_gid(1000), Some(1000));
+    assert_eq!(d.group_name(27), Some("sudo"));
+    assert_eq!(d.gid("sudo"), Some(27));
+    assert_eq!(d.supplementary_groups("alice").iter().map(|g| g.gid).collect::<Vec<_>>(), vec![27]);
+
+    assert_eq!(d.uid("bob"), None);
+    assert_eq!(d.user_name(1001), None);
+    assert!(d.supplementary_groups("root").is_empty());
+
+    assert_eq!(d.similar_user_names("alcie"), vec!["alice"]);
+    assert_eq!(d.similar_user_names("rot"), vec!["root"]);
+    assert!(d.similar_user_names("bob").is_empty());
+    assert_eq!(d.similar_group_names("sud"), vec!["sudo"]);
+}
+
+#[test]
+fn test_edit_distance() {
+    assert_eq!(edit_distance("alice", "alice"), 0);
+    assert_eq!(edit_distance("alice", "alic"), 1);
+    assert_eq!(edit_distance("alice", "alcie"), 2);
+    assert_eq!(edit_distance("", "root"), 4);
+    assert_eq!(edit_distance("kitten", "sitting"), 3);
+}
+
+#[test]
+fn test_distro_override() {
+    assert!(parse_distro_override("fixture").is_err());
+    assert!(parse_distro_override("fixture=C:\\wsl").is_err());
+    assert!(parse_distro_override("fixture=C:\\wsl,ext4").is_err());
+    assert!(parse_distro_override("=C:\\wsl,lxfs").is_err());
+
+    let base_path = std::env::temp_dir().join("wslattr-test-distro-override");
+    let etc = rootfs_path(&base_path).join("etc");
+    std::fs::create_dir_all(&etc).unwrap();
+    std::fs::write(etc.join("passwd"), "root:x:0:0:root:/root:/bin/sh\nalice:x:1000:1000::/home/alice:/bin/sh\n").unwrap();
+    std::fs::write(etc.join("group"), "root:x:0:\nalice:x:1000:\n").unwrap();
+
+    let o = parse_distro_override(&format!("fixture={},Wslfs", base_path.display())).unwrap();
+    assert_eq!(o.fs_type, FsType::Wslfs);
+    let o = Some(&o);
+
+    let d = try_load_with("fixture", o).unwrap();
+    assert_eq!(d.fs_type, Some(FsType::Wslfs));
+    assert_eq!(d.uid("alice"), Some(1000));
+    assert_eq!(d.group_name(1000), Some("alice"));
+
+    let d = try_load_from_absolute_path_with(etc.join("passwd"), o).unwrap();
+    assert_eq!(d.name, "fixture");
+    assert_eq!(d.rootfs_path(), base_path.join("rootfs"));
+    assert_eq!(d.source, DistroSource::FilePathDisk);
+    assert!(list_names_with(o).contains(&"fixture".to_owned()));
+    assert!(try_load_with("fixture", None).is_none());
+
+    std::fs::remove_dir_all(&base_path).unwrap();
+}
+
+#[test]
+fn test_distro_flags_display() {
+    assert_eq!(distro_flags_display(0), "0");
+    assert_eq!(distro_flags_display(0x07), "0x00000007 interop enabled | appendWindowsPath | drvfs mounting");
+    assert_eq!(distro_flags_display(0x0d), "0x0000000d interop enabled | drvfs mounting | WSL2");
+    assert_eq!(distro_flags_display(0x18), "0x00000018 WSL2 | UNKNOWN(0x00000010)");
+}
+
+#[test]
+fn test_set_fs_type_not_in_registry() {
+    let mut d = Distro {
+        name: "fixture".to_owned(),
+        base_path: PathBuf::new(),
+        fs_type: Some(FsType::Wslfs),
+        flags: None,
+        reg_key: None,
+        rootfs: None,
+        source: DistroSource::Unknown,
+        users: None,
+        groups: None,
+    };
+    assert_eq!(d.set_fs_type(Some(FsType::Lxfs)), Err("distro: fixture is not in registry".to_owned()));
+    assert_eq!(d.fs_type, Some(FsType::Wslfs));
+}
+
+#[test]
+fn test_offline_distro() {
+    let rootfs = std::env::temp_dir().join("wslattr-test-offline").join("backup");
+    let etc = rootfs.join("etc");
+    std::fs::create_dir_all(&etc).unwrap();
+    let _ = std::fs::remove_file(etc.join("group"));
+    std::fs::write(etc.join("passwd"), "root:x:0:0:root:/root:/bin/sh\nalice:x:1000:1000::/home/alice:/bin/sh\n").unwrap();
+
+    let offline = OfflineDistro { rootfs: rootfs.clone(), fs_type: FsType::Lxfs, passwd: None, group: None };
+    let warnings = offline.check();
+    assert_eq!(warnings.len(), 1, "{:?}", warnings);
+    assert!(warnings[0].starts_with("no group:"), "{:?}", warnings);
+
+    let d = offline.to_distro();
+    assert_eq!(d.name, OFFLINE_DISTRO_NAME);
+    assert_eq!(d.rootfs_path(), rootfs);
+    assert_eq!(d.fs_type, Some(FsType::Lxfs));
+    assert_eq!(d.uid("alice"), Some(1000));
+    assert!(d.groups.is_none());
+
+    let group = rootfs.parent().unwrap().join("group.bak");
+    std::fs::write(&group, "root:x:0:\nstaff:x:50:alice\n").unwrap();
+    let offline = OfflineDistro { group: Some(group), ..offline };
+    assert!(offline.check().is_empty());
+    assert_eq!(offline.to_distro().gid("staff"), Some(50));
+
+    std::fs::remove_dir_all(rootfs.parent().unwrap()).unwrap();
 }
@@ -0,0 +1,130 @@
+use std::fmt::Display;
+use std::path::PathBuf;
+
+use crate::escape_utils::escape_json_str;
+
+/// failures reported by commands, as `[ERROR]` text or as json object under `--json`
+#[derive(Debug)]
+pub enum WslAttrError {
+    /// the file cannot be opened
+    Open { path: PathBuf, source: std::io::Error },
+    /// EA's of the file cannot be read
+    ReadEa { path: PathBuf, source: std::io::Error },
+    /// both wslfs and lxfs metadata exist
+    AmbiguousFsType { path: PathBuf },
+    /// a unix path like "/usr/bin" without `--distro`
+    UnixPathWithoutDistro { path: PathBuf },
+    /// a WSL UNC path without a distro loaded
+    UncPathWithoutDistro { path: PathBuf },
+    /// a unix path or a WSL UNC path with `--no-distro`
+    DistroDisabled { path: PathBuf },
+    /// the distro loaded is not the one of the WSL UNC path
+    DistroMismatch { path: PathBuf, distro: String },
+    /// neither a local disk path nor a WSL UNC path, like r"\\remote\share\file"
+    UnsupportedPath { path: PathBuf },
+    /// the path cannot be made absolute
+    InvalidPath { path: PathBuf, source: std::io::Error },
+    /// no distro of the name from `--distro`
+    DistroNotFound { name: String },
+    /// the distro from `--distro` or of the path is WSL2, for a command changing or reading lxfs and wslfs metadata
+    DistroWsl2 { name: String },
+}
+
+impl WslAttrError {
+    /// `kind` of the json error object
+    pub fn kind(&self) -> &'static str {
+        match self {
+            WslAttrError::Open { .. } => "open",
+            WslAttrError::ReadEa { .. } => "read_ea",
+            WslAttrError::AmbiguousFsType { .. } => "ambiguous_fs_type",
+            WslAttrError::UnixPathWithoutDistro { .. } => "unix_path_without_distro",
+            WslAttrError::UncPathWithoutDistro { .. } => "unc_path_without_distro",
+            WslAttrError::DistroDisabled { .. } => "distro_disabled",
+            WslAttrError::DistroMismatch { .. } => "distro_mismatch",
+            WslAttrError::UnsupportedPath { .. } => "unsupported_path",
+            WslAttrError::InvalidPath { .. } => "invalid_path",
+            WslAttrError::DistroNotFound { .. } => "distro_not_found",
+            WslAttrError::DistroWsl2 { .. } => "distro_wsl2",
+        }
+    }
+
+    pub fn path(&self) -> Option<&PathBuf> {
+        match self {
+            WslAttrError::Open { path, .. } |
+            WslAttrError::ReadEa { path, .. } |
+            WslAttrError::AmbiguousFsType { path } |
+            WslAttrError::UnixPathWithoutDistro { path } |
+            WslAttrError::UncPathWithoutDistro { path } |
+            WslAttrError::DistroDisabled { path } |
+            WslAttrError::DistroMismatch { path, .. } |
+            WslAttrError::UnsupportedPath { path } |
+            WslAttrError::InvalidPath { path, .. } => Some(path),
+            WslAttrError::DistroNotFound { .. } |
+            WslAttrError::DistroWsl2 { .. } => None,
+        }
+    }
+
+    /// `{"error":{"kind","message","path"}}`, `path` is null if no file involved
+    pub fn to_json(&self) -> String {
+        let mut out = format!("{{\"error\":{{\"kind\":\"{}\",\"message\":\"", self.kind());
+        escape_json_str(&self.to_string(), &mut out).unwrap();
+        out += "\",\"path\":";
+        if let Some(path) = self.path() {
+            out.push('"');
+            escape_json_str(&path.to_string_lossy(), &mut out).unwrap();
+            out.push('"');
+        } else {
+            out += "null";
+        }
+        out += "}}";
+        out
+    }
+
+    /// print as json document to stdout if `json`, or as `[ERROR]` text
+    pub fn report(&self, json: bool) {
+        if json {
+            println!("{}", crate::json::to_output(&self.to_json()));
+        } else {
+            error!("[ERROR] {}", self);
+        }
+    }
+}
+
+impl Display for WslAttrError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            WslAttrError::Open { path, source } => write!(f, "cannot open file: {}, error: {source:?}", path.display()),
+            WslAttrError::ReadEa { path, source } => write!(f, "cannot read EAs of {}, error: {source:?}", path.display()),
+            WslAttrError::AmbiguousFsType { path } => write!(f, "cannot determine fs_type of {}, cause both wslfs and lxfs metadata exist", path.display()),
+            WslAttrError::UnixPathWithoutDistro { path } => write!(f, "a unix-absolute path like /usr/bin requires --distro <name>: {}", path.display()),
+            WslAttrError::UncPathWithoutDistro { path } => write!(f, "no distro loaded for the WSL UNC path: {}", path.display()),
+            WslAttrError::DistroDisabled { path } => write!(f, "a unix path or a WSL UNC path needs a distro, which --no-distro disables, use a Windows disk path: {}", path.display()),
+            WslAttrError::DistroMismatch { path, distro } => write!(f, "distro: {} loaded does not match the WSL UNC path: {}", distro, path.display()),
+            WslAttrError::UnsupportedPath { path } => write!(f, "unsupported path: {}, only local disk paths and WSL UNC paths are supported", path.display()),
+            WslAttrError::InvalidPath { path, source } => write!(f, "invalid path: {:?}, error: {source:?}", path),
+            WslAttrError::DistroNotFound { name } => write!(f, "cannot load distro from arg: {}", name),
+            WslAttrError::DistroWsl2 { name } => write!(f, "distro: {} is WSL2, which keeps attributes inside its ext4 image, lxfs and wslfs metadata do not apply", name),
+        }
+    }
+}
+
+#[test]
+fn test_error_to_json() {
+    let err = WslAttrError::AmbiguousFsType { path: PathBuf::from(r#"C:\a "b""#) };
+    assert_eq!(err.to_json(), concat!(
+        r#"{"error":{"kind":"ambiguous_fs_type","#,
+        r#""message":"cannot determine fs_type of C:\\a \"b\", cause both wslfs and lxfs metadata exist","#,
+        r#""path":"C:\\a \"b\""}}"#,
+    ));
+}
+
+#[test]
+fn test_distro_disabled() {
+    let err = WslAttrError::DistroDisabled { path: PathBuf::from("/etc/passwd") };
+    assert_eq!(err.kind(), "distro_disabled");
+    assert_eq!(err.path(), Some(&PathBuf::from("/etc/passwd")));
+    assert!(err.to_string().contains("--no-distro"));
+
+    let err = WslAttrError::DistroNotFound { name: "Arhc".to_owned() };
+    assert_eq!(err.to_json(), r#"{"error":{"kind":"distro_not_found","message":"cannot load distro from arg: Arhc","path":null}}"#);
+}
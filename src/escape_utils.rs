@@ -38,6 +38,19 @@ pub fn escape_bytes_octal(bytes: &[u8], mut w: impl Write, keep_utf8: bool) -> R
     Ok(())
 }
 
+/// `"..."` escaped as `escape_bytes_octal`, values longer than `max_len` are cut as `"..."... (N bytes)`,
+/// `max_len` 0 means no truncation
+pub fn escape_value_display(bytes: &[u8], max_len: usize, mut w: impl Write) -> Result<(), std::fmt::Error> {
+    let truncated = max_len != 0 && bytes.len() > max_len;
+    w.write_char('"')?;
+    escape_bytes_octal(if truncated { &bytes[..max_len] } else { bytes }, &mut w, true)?;
+    w.write_char('"')?;
+    if truncated {
+        write!(w, "... ({} bytes)", bytes.len())?;
+    }
+    Ok(())
+}
+
 /// 'xy' -> '7879'
 pub fn escape_bytes_hex(bytes: &[u8], mut w: impl Write) -> Result<(), std::fmt::Error> {
     for b in bytes {
@@ -46,12 +59,117 @@ pub fn escape_bytes_hex(bytes: &[u8], mut w: impl Write) -> Result<(), std::fmt:
     Ok(())
 }
 
+/// 16 bytes a line as "0010  hex  ascii", lines after `max_len` bytes are cut with a note
+pub fn hex_dump(bytes: &[u8], max_len: usize, mut w: impl Write) -> Result<(), std::fmt::Error> {
+    for (i, chunk) in bytes[..bytes.len().min(max_len)].chunks(16).enumerate() {
+        write!(w, "{:04x}  ", i * 16)?;
+        let mut hex = String::new();
+        escape_bytes_hex(chunk, &mut hex)?;
+        write!(w, "{:32}  ", hex)?;
+        for b in chunk {
+            w.write_char(if b.is_ascii_graphic() || *b == b' ' { *b as char } else { '.' })?;
+        }
+        w.write_char('\n')?;
+    }
+    if bytes.len() > max_len {
+        writeln!(w, "... {} more bytes", bytes.len() - max_len)?;
+    }
+    Ok(())
+}
+
 pub fn escape_bytes_base64<'a>(bytes: &'a [u8], mut w: impl Write) -> Result<(), std::fmt::Error> {
     use base64::{display::Base64Display, engine::general_purpose::STANDARD};
 
     write!(&mut w, "{}", Base64Display::new(bytes,  &STANDARD))
 }
 
+/// encoding of EA values like `getfattr --encoding`
+#[derive(Debug, Clone, Copy, Default, PartialEq, clap::ValueEnum)]
+pub enum ValueFormat {
+    /// quoted, control chars as octal `\777`
+    #[default]
+    Text,
+    /// '0x' prefixed
+    Hex,
+    /// '0s' prefixed
+    Base64,
+}
+
+/// `"xy"`, `0x7879` or `0seHk=`, hex and base64 are accepted by `unescape`
+pub fn escape_bytes(bytes: &[u8], format: ValueFormat, mut w: impl Write) -> Result<(), std::fmt::Error> {
+    match format {
+        ValueFormat::Text => {
+            w.write_char('"')?;
+            escape_bytes_octal(bytes, &mut w, true)?;
+            w.write_char('"')
+        },
+        ValueFormat::Hex => {
+            w.write_str("0x")?;
+            escape_bytes_hex(bytes, &mut w)
+        },
+        ValueFormat::Base64 => {
+            w.write_str("0s")?;
+            escape_bytes_base64(bytes, &mut w)
+        },
+    }
+}
+
+/// escape as json string content, without quotes
+pub fn escape_json_str(s: &str, mut w: impl Write) -> Result<(), std::fmt::Error> {
+    for ch in s.chars() {
+        match ch {
+            '"' => w.write_str("\\\"")?,
+            '\\' => w.write_str("\\\\")?,
+            '\n' => w.write_str("\\n")?,
+            '\r' => w.write_str("\\r")?,
+            '\t' => w.write_str("\\t")?,
+            ch if ch.is_control() => write!(w, "\\u{:04x}", ch as u32)?,
+            ch => w.write_char(ch)?,
+        }
+    }
+    Ok(())
+}
+
+/// interpretation of a value on the command line, `--input-format`
+#[derive(Debug, Clone, Copy, Default, PartialEq, clap::ValueEnum)]
+pub enum InputFormat {
+    /// by prefix, '0x' for hex, '0s' for base64, otherwise raw
+    #[default]
+    Auto,
+    /// the bytes of the string, even if it starts with '0x' or '0s'
+    Raw,
+    /// the '0x' prefix is optional
+    Hex,
+    /// the '0s' prefix is optional
+    Base64,
+    /// octal escaped as `view` and `get-attr` output, like `"a\\b\012"`, the quotes are optional
+    Escaped,
+}
+
+/// `value` as `format`, `None` if it is not valid in the format
+pub fn unescape_as(value: &str, format: InputFormat) -> Option<Vec<u8>> {
+    use base64::engine::general_purpose::STANDARD;
+
+    fn strip_prefix<'a>(value: &'a str, prefix: &str) -> &'a str {
+        match value.get(..2) {
+            Some(p) if p.eq_ignore_ascii_case(prefix) => &value[2..],
+            _ => value,
+        }
+    }
+
+    match format {
+        InputFormat::Auto => unescape(value),
+        InputFormat::Raw => Some(value.as_bytes().to_vec()),
+        InputFormat::Hex => unescape_hex(strip_prefix(value, "0x")).ok(),
+        // '0s' is valid base64 too, but only one of with and without it has a valid length
+        InputFormat::Base64 => STANDARD.decode(value).or_else(|_| STANDARD.decode(strip_prefix(value, "0s"))).ok(),
+        InputFormat::Escaped => {
+            let quoted = value.len() >= 2 && value.starts_with('"') && value.ends_with('"');
+            unescape_octal(if quoted { &value[1..value.len() - 1] } else { value }).ok()
+        },
+    }
+}
+
 pub fn unescape(value: &str) -> Option<Vec<u8>> {
     use base64::engine::general_purpose::STANDARD;
 
@@ -72,14 +190,16 @@ fn unescape_octal(value: &str) -> Result<Vec<u8>, ()> {
         for b in next[..pos].as_bytes() {
             out.push(*b);
         }
-        if &next[pos..pos+2] == r#"\""# {
+        if next[pos..].starts_with(r#"\""#) {
             out.push('"' as u8);
             next = &next[pos+2..];
-        } else if &next[pos..pos+2] == r#"\\"# {
+        } else if next[pos..].starts_with(r#"\\"#) {
             out.push('\\' as u8);
             next = &next[pos+2..];
         } else {
-            let b = u8::from_str_radix(&next[pos+1..pos+4], 8).map_err(|_| ())?;
+            // a trailing '\' or less than 3 octal digits
+            let digits = next.get(pos+1..pos+4).filter(|d| d.bytes().all(|b| (b'0'..=b'7').contains(&b))).ok_or(())?;
+            let b = u8::from_str_radix(digits, 8).map_err(|_| ())?;
             out.push(b);
             next = &next[pos+4..];
         }
@@ -114,8 +234,24 @@ fn test_unescape() {
 
     assert_eq!(a, b);
     
-    let c = unescape(r#"ab\\t\\n\033$"#).unwrap();
+    let c = unescape_as(r#"ab\\t\\n\033$"#, InputFormat::Escaped).unwrap();
     assert_eq!(a, c);
+    assert_eq!(unescape_as(r#""ab\\t\\n\033$""#, InputFormat::Escaped).unwrap(), a);
+    assert_eq!(unescape_as(r#"ab\"#, InputFormat::Escaped), None);
+    assert_eq!(unescape_as(r#"ab\9"#, InputFormat::Escaped), None);
+}
+
+#[test]
+fn test_unescape_input_format() {
+    assert_eq!(unescape("0xABCD").unwrap(), vec![0xab, 0xcd]);
+    assert_eq!(unescape_as("0xABCD", InputFormat::Auto).unwrap(), vec![0xab, 0xcd]);
+    assert_eq!(unescape_as("0xABCD", InputFormat::Raw).unwrap(), b"0xABCD");
+    assert_eq!(unescape_as("0sYWI=", InputFormat::Raw).unwrap(), b"0sYWI=");
+    assert_eq!(unescape_as("ABCD", InputFormat::Hex).unwrap(), vec![0xab, 0xcd]);
+    assert_eq!(unescape_as("0xABCD", InputFormat::Hex).unwrap(), vec![0xab, 0xcd]);
+    assert_eq!(unescape_as("YWI=", InputFormat::Base64).unwrap(), b"ab");
+    assert_eq!(unescape_as("0sYWI=", InputFormat::Base64).unwrap(), b"ab");
+    assert_eq!(unescape_as("YWI", InputFormat::Base64), None);
 }
 
 #[test]
@@ -134,3 +270,47 @@ fn test_escape() {
     escape_bytes_octal(v.as_slice(), &mut repr, false).unwrap();
     assert_eq!(r#"ab\\t\\n\033$"#, repr);
 }
+
+#[test]
+fn test_escape_bytes_format() {
+    let v = b"ab\"\n";
+    for (format, expected) in [(ValueFormat::Text, r#""ab\"\012""#), (ValueFormat::Hex, "0x6162220a"), (ValueFormat::Base64, "0sYWIiCg==")] {
+        let mut repr = String::new();
+        escape_bytes(v, format, &mut repr).unwrap();
+        assert_eq!(expected, repr);
+        if format != ValueFormat::Text {
+            assert_eq!(unescape(&repr).as_deref(), Some(&v[..]));
+        }
+    }
+}
+
+#[test]
+fn test_escape_value_display() {
+    let display = |v: &[u8], max_len| {
+        let mut repr = String::new();
+        escape_value_display(v, max_len, &mut repr).unwrap();
+        repr
+    };
+    assert_eq!(display(b"abc\n", 256), r#""abc\012""#);
+    assert_eq!(display(b"abc\n", 4), r#""abc\012""#);
+    assert_eq!(display(b"abc\n", 2), r#""ab"... (4 bytes)"#);
+    assert_eq!(display(&[b'x'; 1000], 0).len(), 1002);
+}
+
+#[test]
+fn test_hex_dump() {
+    let mut out = String::new();
+    hex_dump(b"$LXUID\0\x04abcdefghijklmnopq", 18, &mut out).unwrap();
+    assert_eq!(out, concat!(
+        "0000  244c5855494400046162636465666768  $LXUID..abcdefgh\n",
+        "0010  696a                              ij\n",
+        "... 7 more bytes\n",
+    ));
+}
+
+#[test]
+fn test_escape_json_str() {
+    let mut out = String::new();
+    escape_json_str("a\"b\\c\nd\u{1b}é", &mut out).unwrap();
+    assert_eq!(r#"a\"b\\c\nd\u001bé"#, out);
+}
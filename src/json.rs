@@ -0,0 +1,113 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// `schema_version` of every json document, increased on an incompatible change of any of them
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// set by `--json-pretty`
+pub static PRETTY: AtomicBool = AtomicBool::new(false);
+
+/// a compact json object like `{"a":1}` as `{"schema_version":1,"a":1}`
+pub fn document(object: &str) -> String {
+    let fields = object.strip_prefix('{').unwrap_or(object);
+    let sep = if fields.starts_with('}') { "" } else { "," };
+    format!("{{\"schema_version\":{}{}{}", SCHEMA_VERSION, sep, fields)
+}
+
+/// compact json indented by 2 spaces, strings are kept as is
+pub fn pretty(compact: &str) -> String {
+    fn new_line(out: &mut String, indent: usize) {
+        out.push('\n');
+        for _ in 0..indent {
+            out.push_str("  ");
+        }
+    }
+
+    let mut out = String::with_capacity(compact.len() * 2);
+    let mut indent = 0usize;
+    let mut in_string = false;
+    let mut escaped = false;
+    let mut chars = compact.chars().peekable();
+    while let Some(c) = chars.next() {
+        if in_string {
+            out.push(c);
+            if escaped {
+                escaped = false;
+            } else if c == '\\' {
+                escaped = true;
+            } else if c == '"' {
+                in_string = false;
+            }
+            continue;
+        }
+        match c {
+            '"' => {
+                in_string = true;
+                out.push(c);
+            },
+            '{' | '[' => {
+                out.push(c);
+                if let Some(close) = chars.next_if(|next| matches!(next, '}' | ']')) {
+                    // `{}` and `[]` stay in one line
+                    out.push(close);
+                } else {
+                    indent += 1;
+                    new_line(&mut out, indent);
+                }
+            },
+            '}' | ']' => {
+                indent = indent.saturating_sub(1);
+                new_line(&mut out, indent);
+                out.push(c);
+            },
+            ',' => {
+                out.push(c);
+                new_line(&mut out, indent);
+            },
+            ':' => out.push_str(": "),
+            c if c.is_ascii_whitespace() => {},
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// the json document of a compact json object, in one line or indented by `--json-pretty`
+pub fn to_output(object: &str) -> String {
+    let doc = document(object);
+    if PRETTY.load(Ordering::Relaxed) {
+        pretty(&doc)
+    } else {
+        doc
+    }
+}
+
+#[test]
+fn test_json_document() {
+    // whitespace out of strings removed
+    fn compact(json: &str) -> String {
+        let (mut out, mut in_string, mut escaped) = (String::new(), false, false);
+        for c in json.chars() {
+            if in_string {
+                escaped = !escaped && c == '\\';
+                in_string = escaped || c != '"';
+            } else if c.is_ascii_whitespace() {
+                continue;
+            } else {
+                in_string = c == '"';
+            }
+            out.push(c);
+        }
+        out
+    }
+
+    let object = r#"{"path":"a, {b}: \"c\"\\","eas":[],"lxfs":{"uid":0,"attrs":[{"name":"user.x"}]},"wslfs":null}"#;
+    let doc = document(object);
+    assert!(doc.starts_with(&format!("{{\"schema_version\":{},\"path\":", SCHEMA_VERSION)));
+    assert_eq!(document("{}"), format!("{{\"schema_version\":{}}}", SCHEMA_VERSION));
+
+    let pretty_doc = pretty(&doc);
+    assert!(pretty_doc.lines().count() > 1);
+    assert!(pretty_doc.contains("\n  \"path\": \"a, {b}: \\\"c\\\"\\\\\",\n"));
+    assert!(pretty_doc.contains("\"eas\": [],"));
+    assert_eq!(compact(&pretty_doc), doc);
+}
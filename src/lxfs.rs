@@ -1,26 +1,38 @@
 use std::borrow::Cow;
 use std::mem::{offset_of, transmute};
-use std::ptr::{addr_of, slice_from_raw_parts};
+use std::sync::atomic::{AtomicBool, Ordering};
 
 use windows::Wdk::Storage::FileSystem::FILE_BASIC_INFORMATION;
 
 use crate::distro::{Distro, FsType};
-use crate::ea_parse::{force_cast, EaEntry, EaEntryRaw};
-use crate::posix::{lsperms, StModeType, DEFAULT_MODE};
-use crate::ntfs_io::read_data;
-use crate::time_utils::{u64_to_lxfs_time, LxfsTime}; 
-use crate::wsl_file::{WslFile, WslFileAttributes};
+use crate::ea_parse::{read_unaligned, normalize_ea_name, EaEntry, EaEntryRaw, EaOut, STRICT_EA_NAMES};
+use crate::posix::{Mode, StModeType};
+use crate::time_utils::{self, u64_to_lxfs_time, LxfsTime}; 
+use crate::wsl_file::{default_mode, EaStore, FmtOptions, WslFileAttributes};
+use crate::wslfs::WslfsParsed;
 
 pub const LXATTRB: &'static str = "LXATTRB";
 pub const LXXATTR: &'static str = "LXXATTR";
 
+/// the only known version of LXATTRB
+const LXATTRB_VERSION_1: u16 = 1;
+
+/// no flag of LXATTRB v1 is documented, all set bits are shown as unknown
+fn lxattrb_flags_display(flags: u16) -> String {
+    if flags == 0 {
+        "0".to_owned()
+    } else {
+        format!("{:#06x} UNKNOWN({:#06x})", flags, flags)
+    }
+}
+
 #[repr(C)]
 #[derive(Copy, Clone, Debug)]
 pub struct EaLxattrbV1 {
     flags: u16,             // 0
     version: u16,           // 1
 
-    pub st_mode: u32,       // Mode bit mask constants: https://msdn.microsoft.com/en-us/library/3kyc8381.aspx
+    pub st_mode: Mode,      // Mode bit mask constants: https://msdn.microsoft.com/en-us/library/3kyc8381.aspx
     pub st_uid: u32,        // Numeric identifier of user who owns file (Linux-specific).
     pub st_gid: u32,        // Numeric identifier of group that owns the file (Linux-specific)
     pub st_rdev: u32,       // Drive number of the disk containing the file.
@@ -37,7 +49,7 @@ impl EaLxattrbV1 {
         let mut lxattrb = Self {
             flags: 0,
             version: 1,
-            st_mode: DEFAULT_MODE,
+            st_mode: default_mode(basic_file_info),
             st_uid: 0,
             st_gid: 0,
             st_rdev: 0,
@@ -57,15 +69,28 @@ impl EaLxattrbV1 {
 
         return lxattrb;
     }
+
+    /// st_rdev means something only for a character or block device, nonzero for others is corrupt
+    pub fn has_stray_rdev(&self) -> bool {
+        self.st_rdev != 0 && !matches!(self.st_mode.file_type(), StModeType::CHR | StModeType::BLK)
+    }
 }
 
 #[derive(Default)]
 pub struct LxfsParsed<'a> {
     pub lxattrb: Option<Cow<'a, EaLxattrbV1>>,
+    /// version of LXATTRB which is not v1 and cannot be interpreted
+    pub lxattrb_unsupported: Option<u16>,
     lxxattr: Option<Vec<LxxattrEntry<'a>>>,
     pub symlink: Option<String>,
 
     pub basic_file_info: Option<FILE_BASIC_INFORMATION>,
+
+    /// `st_ctime` is set to now by `save` after uid, gid or mode changed, as chmod and chown on linux,
+    /// unless `st_ctime` is set explicitly
+    pub ctime_update: bool,
+    owner_mode_changed: bool,
+    ctime_set: bool,
 }
 
 const MINORBITS: usize = 20;
@@ -82,26 +107,43 @@ pub const fn make_dev(ma: u32, mi: u32) -> u32 {
 }
 
 impl<'a> LxfsParsed<'a> {
-    pub fn load<'b: 'a, 'c>(wsl_file: &'c WslFile, ea_parsed: &'b Option<Vec<EaEntryRaw<'a>>>)-> Self {
+    /// EA names are checked as `--strict` says
+    pub fn load<'b: 'a, 'c>(ea_store: &'c dyn EaStore, ea_parsed: &'b Option<Vec<EaEntryRaw<'a>>>)-> Self {
+        Self::load_with(ea_store, ea_parsed, STRICT_EA_NAMES.load(Ordering::Relaxed))
+    }
+
+    /// EA names with NUL or trailing spaces are ignored if `strict`, or trimmed
+    pub fn load_with<'b: 'a, 'c>(ea_store: &'c dyn EaStore, ea_parsed: &'b Option<Vec<EaEntryRaw<'a>>>, strict: bool)-> Self {
         let mut p = Self::default();
-        p.basic_file_info = wsl_file.basic_file_info;
+        p.basic_file_info = ea_store.basic_file_info();
 
         if let Some(ea_parsed) = ea_parsed {
             for EaEntry { name, value, flags: _ } in ea_parsed {
-                let name = name.as_ref();
+                let Some(name) = normalize_ea_name(name.as_ref(), strict) else {
+                    continue;
+                };
                 if name == LXATTRB.as_bytes() {
-                    p.lxattrb = Some(Cow::Borrowed(force_cast(value.as_ref())));
+                    let value = value.as_ref();
+                    let version = if value.len() >= 4 { u16::from_le_bytes([value[2], value[3]]) } else { 0 };
+                    if version != LXATTRB_VERSION_1 || value.len() < size_of::<EaLxattrbV1>() {
+                        warning!("[WARNING] LXATTRB version: {} size: {} is not supported, ignore it", version, value.len());
+                        p.lxattrb_unsupported = Some(version);
+                        continue;
+                    }
+                    p.lxattrb = Some(Cow::Owned(read_unaligned(value)));
                     
                     if let Some(mode) = p.get_mode() {
-                        if StModeType::from_mode(mode) == StModeType::LNK {
-                            let buf = unsafe { read_data(wsl_file.file_handle) }.unwrap();                
+                        if mode.file_type() == StModeType::LNK {
+                            let buf = ea_store.read_data().unwrap();                
                             let symlink = String::from_utf8(buf).unwrap();
                             p.symlink = Some(symlink);
                         }
                     }
                 } else if name == LXXATTR.as_bytes() {
-                    let lxxattr_parsed = unsafe { parse_lxxattr(value.as_ref()) };
-                    p.lxxattr = Some(lxxattr_parsed);
+                    match parse_lxxattr(value.as_ref()) {
+                        Ok(lxxattr_parsed) => p.lxxattr = Some(lxxattr_parsed),
+                        Err(e) => warning!("[WARNING] LXXATTR is malformed: {}, ignore it", e),
+                    }
                 }
             }
         }
@@ -116,6 +158,118 @@ impl<'a> LxfsParsed<'a> {
         self.lxattrb = Some(lxattrb);
         self.lxattrb.as_mut().unwrap().to_mut()
     }
+
+    /// add changed EA's to `ea_out` without writing them,
+    /// the trailing byte of LXXATTR entries is written as 0 if `trim_trailing`, or as read
+    pub fn save_to(&mut self, ea_out: &mut EaOut, trim_trailing: bool) -> std::io::Result<()> {
+        use crate::ea_parse::get_buffer;
+
+        if let (Some(version), Some(Cow::Owned(_))) = (self.lxattrb_unsupported, &self.lxattrb) {
+            return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, format!("cannot overwrite LXATTRB of unsupported version: {}", version)));
+        }
+
+        if let Some(Cow::Owned(ref x)) = self.lxattrb {
+            ea_out.add(LXATTRB.as_bytes(), get_buffer(x));
+        }
+
+        if let Some(x) = self.lxxattr.as_mut() {
+            let mut lxxattr_out = LxxattrOut::default();
+            for attr in x.iter() {
+                if let Some(ref value) = attr.value {
+                    let trailing = if trim_trailing { 0 } else { attr.trailing };
+                    lxxattr_out.add_with_trailing(&attr.name, value, trailing);
+                }
+            }
+            // check before dropping removed entries, `self` is unchanged on error
+            if lxxattr_out.buffer.len() > u16::MAX as usize {
+                return Err(crate::ea_parse::ea_too_large(lxxattr_out.buffer.len()));
+            }
+            x.retain(|attr| attr.value.is_some());
+            ea_out.add(LXXATTR.as_bytes(), &lxxattr_out.buffer);
+        }
+
+        Ok(())
+    }
+}
+
+impl<'a, 'b> From<&'b WslfsParsed<'a>> for LxfsParsed<'static> {
+    fn from(wslfs: &'b WslfsParsed<'a>) -> Self {
+        let mut lxattrb = EaLxattrbV1::new(&wslfs.basic_file_info);
+        lxattrb.st_uid = wslfs.get_uid().unwrap_or(0);
+        lxattrb.st_gid = wslfs.get_gid().unwrap_or(0);
+        lxattrb.st_mode = wslfs.get_mode().unwrap_or(default_mode(&wslfs.basic_file_info));
+        lxattrb.st_rdev = make_dev(wslfs.get_dev_major().unwrap_or(0), wslfs.get_dev_minor().unwrap_or(0));
+
+        let lxxattr: Vec<_> = wslfs.lx_dot_ea.iter()
+        .filter_map(|dot_ea| Some(LxxattrEntry {
+            name: Cow::Owned(dot_ea.name()),
+            value: Some(Cow::Owned(dot_ea.value_unprefixed()?.to_owned())),
+            trailing: 0,
+        }))
+        .collect();
+
+        LxfsParsed {
+            lxattrb: Some(Cow::Owned(lxattrb)),
+            lxattrb_unsupported: None,
+            lxxattr: if lxxattr.is_empty() { None } else { Some(lxxattr) },
+            symlink: wslfs.symlink.clone(),
+            basic_file_info: wslfs.basic_file_info,
+            ..Default::default()
+        }
+    }
+}
+
+impl<'a, 'b> TryFrom<&'b LxfsParsed<'a>> for WslfsParsed<'static> {
+    type Error = std::io::Error;
+
+    /// attribute names of wslfs are EA names, which must be ASCII
+    fn try_from(lxfs: &'b LxfsParsed<'a>) -> std::io::Result<Self> {
+        use std::io::{Error, ErrorKind};
+        use crate::wslfs::{LxDotAttr, WslfsReparseTag};
+
+        if let Some(version) = lxfs.lxattrb_unsupported {
+            return Err(Error::new(ErrorKind::InvalidData, format!("cannot convert LXATTRB of unsupported version: {}", version)));
+        }
+
+        let mut wslfs = WslfsParsed::default();
+        wslfs.basic_file_info = lxfs.basic_file_info;
+        wslfs.symlink = lxfs.symlink.clone();
+
+        if let Some(lxattrb) = &lxfs.lxattrb {
+            wslfs.set_uid(lxattrb.st_uid);
+            wslfs.set_gid(lxattrb.st_gid);
+            wslfs.set_mode(lxattrb.st_mode);
+            let file_type = lxattrb.st_mode.file_type();
+            if file_type == StModeType::CHR || file_type == StModeType::BLK {
+                wslfs.set_dev_major(dev_major(lxattrb.st_rdev));
+                wslfs.set_dev_minor(dev_minor(lxattrb.st_rdev));
+            }
+            if file_type.tag_id() != 0 {
+                wslfs.reparse_tag = Some(file_type);
+            }
+        }
+
+        for attr in lxfs.lxxattr.iter().flatten() {
+            let Some(value) = &attr.value else {
+                continue;
+            };
+            let name = std::str::from_utf8(&attr.name).ok().filter(|name| name.is_ascii())
+            .ok_or_else(|| Error::new(ErrorKind::InvalidData, format!("cannot convert non-ASCII attribute name: {}", attr.name_display())))?;
+            wslfs.lx_dot_ea.push(LxDotAttr::new_owned(name, value));
+        }
+
+        Ok(wslfs)
+    }
+}
+
+/// the access time is skipped with `--hide-atime`, as it is stale on a noatime mount
+fn fmt_lxattrb_times(f: &mut dyn std::io::Write, l: &EaLxattrbV1, opts: &FmtOptions) -> std::io::Result<()> {
+    if !opts.hide_atime {
+        f.write_fmt(format_args!("{}{}\n", opts.label("  Last file access:"), opts.time(LxfsTime::new(l.st_atime, l.st_atime_nsec), "LXATTRB")))?;
+    }
+    f.write_fmt(format_args!("{}{}\n", opts.label("  Last file modification:"), opts.time(LxfsTime::new(l.st_mtime, l.st_mtime_nsec), "LXATTRB")))?;
+    f.write_fmt(format_args!("{}{}\n", opts.label("  Last status change:"), opts.time(LxfsTime::new(l.st_ctime, l.st_ctime_nsec), "LXATTRB")))?;
+    Ok(())
 }
 
 impl<'a> WslFileAttributes<'a> for LxfsParsed<'a> {
@@ -125,10 +279,11 @@ impl<'a> WslFileAttributes<'a> for LxfsParsed<'a> {
 
     fn maybe(&self) -> bool {
         self.lxattrb.is_some() ||
+        self.lxattrb_unsupported.is_some() ||
         self.lxxattr.is_some()
     }
     
-    fn fmt(&self, f: &mut dyn std::io::Write, distro: Option<&Distro>) -> std::io::Result<()> {
+    fn fmt(&self, f: &mut dyn std::io::Write, distro: Option<&Distro>, opts: &FmtOptions) -> std::io::Result<()> {
         //Symlink:                   -> target
         //LXATTRB:
         //  Flags:                   0
@@ -142,46 +297,61 @@ impl<'a> WslFileAttributes<'a> for LxfsParsed<'a> {
         //  Last status change:      2019-11-19 18:29:52.102270300 +0800
         //Linux extended attributes(LXXATTR):
         //  user.xdg.origin.url:      http://example.url
-        
+
+        if opts.only_times {
+            if let Some(l) = &self.lxattrb {
+                f.write("LXATTRB:\n".as_bytes())?;
+                fmt_lxattrb_times(f, l, opts)?;
+            }
+            return Ok(());
+        }
+
         if let Some(s) = &self.symlink {
-            f.write_fmt(format_args!("{:28}-> {}\n", "Symlink:", s))?;
+            f.write_fmt(format_args!("{}-> {}\n", opts.label("Symlink:"), s))?;
+        }
+
+        if let Some(version) = self.lxattrb_unsupported {
+            f.write_fmt(format_args!("{}unsupported version: {}\n", opts.label("LXATTRB:"), version))?;
         }
 
         if let Some(l) = &self.lxattrb {
             f.write("LXATTRB:\n".as_bytes())?;
-            f.write_fmt(format_args!("{:28}{}\n", "  Flags:", l.flags))?;
-            f.write_fmt(format_args!("{:28}{}\n", "  Version:", l.version))?;
+            f.write_fmt(format_args!("{}{}\n", opts.label("  Flags:"), lxattrb_flags_display(l.flags)))?;
+            f.write_fmt(format_args!("{}{}\n", opts.label("  Version:"), l.version))?;
 
             let uid = l.st_uid;
             if let Some(user_name) = distro.and_then(|d| d.user_name(uid)) {
-                f.write_fmt(format_args!("{:28}{} / {}\n", "  User:", uid, user_name))?;
+                f.write_fmt(format_args!("{}{} / {}\n", opts.label("  User:"), uid, user_name))?;
             } else {
-                f.write_fmt(format_args!("{:28}{}\n", "  User:", uid))?;
+                f.write_fmt(format_args!("{}{}\n", opts.label("  User:"), uid))?;
             }
 
             let gid = l.st_gid;
             if let Some(group_name) = distro.and_then(|d| d.group_name(gid)) {
-                f.write_fmt(format_args!("{:28}{} / {}\n", "  Group:", gid, group_name))?;
+                f.write_fmt(format_args!("{}{} / {}\n", opts.label("  Group:"), gid, group_name))?;
             } else {
-                f.write_fmt(format_args!("{:28}{}\n", "  Group:", l.st_gid))?;
+                f.write_fmt(format_args!("{}{}\n", opts.label("  Group:"), l.st_gid))?;
             }
 
             let mode = l.st_mode;
-            let access = lsperms(mode);
-            f.write_fmt(format_args!("{:28}Mode: {:06o} Access: {}\n", "  Mode:", mode, access))?;
+            f.write_fmt(format_args!("{}Mode: {:06o} Access: {}\n", opts.label("  Mode:"), mode, opts.access(mode)))?;
 
             if l.st_rdev != 0 {
-                f.write_fmt(format_args!("{:28}{}, {}\n", "  Device type:", dev_major(l.st_rdev), dev_minor(l.st_rdev)))?;
+                f.write_fmt(format_args!("{}{}, {}\n", opts.label("  Device type:"), dev_major(l.st_rdev), dev_minor(l.st_rdev)))?;
+                if l.has_stray_rdev() {
+                    f.write_fmt(format_args!("{}[WARNING] device type of a non-device file, fix by `repair`\n", opts.label("")))?;
+                }
             }
-            f.write_fmt(format_args!("{:28}{}\n", "  Last file access:", LxfsTime::new(l.st_atime, l.st_atime_nsec)))?;
-            f.write_fmt(format_args!("{:28}{}\n", "  Last file modification:", LxfsTime::new(l.st_mtime, l.st_mtime_nsec)))?;
-            f.write_fmt(format_args!("{:28}{}\n", "  Last status change:", LxfsTime::new(l.st_ctime, l.st_ctime_nsec)))?;
+            fmt_lxattrb_times(f, l, opts)?;
         }
 
-        if let Some(lxxattr) = &self.lxxattr {
+        let shown: Vec<_> = self.lxxattr.iter().flatten()
+            .filter(|l| opts.shows_name(&l.name_display()))
+            .collect();
+        if shown.len() > 0 {
             f.write("Linux extended attributes(LXXATTR):\n".as_bytes())?;
-            for l in lxxattr {
-                f.write_fmt(format_args!("  {:26}{}\n", l.name_display(), l.value_display()))?;
+            for l in shown {
+                f.write_fmt(format_args!("{}{}\n", opts.label(&format!("  {}", l.name_display())), l.value_display(opts.max_value_len)))?;
             }
         }
         Ok(())
@@ -195,7 +365,7 @@ impl<'a> WslFileAttributes<'a> for LxfsParsed<'a> {
         self.lxattrb.as_ref().map(|l| l.st_gid)
     }
     
-    fn get_mode(&self) -> Option<u32> {
+    fn get_mode(&self) -> Option<Mode> {
         self.lxattrb.as_ref().map(|l| l.st_mode)
     }
     
@@ -208,14 +378,17 @@ impl<'a> WslFileAttributes<'a> for LxfsParsed<'a> {
     }
     
     fn set_uid(&mut self, uid: u32) {
+        self.owner_mode_changed |= self.get_uid() != Some(uid);
         self.lxattrb_mut().st_uid = uid;
     }
     
     fn set_gid(&mut self, gid: u32) {
+        self.owner_mode_changed |= self.get_gid() != Some(gid);
         self.lxattrb_mut().st_gid = gid;
     }
     
-    fn set_mode(&mut self, mode: u32) {
+    fn set_mode(&mut self, mode: Mode) {
+        self.owner_mode_changed |= self.get_mode() != Some(mode);
         self.lxattrb_mut().st_mode = mode;
     }
     
@@ -223,12 +396,48 @@ impl<'a> WslFileAttributes<'a> for LxfsParsed<'a> {
         let mut lxattrb = self.lxattrb_mut();
         let st_rdev = lxattrb.st_rdev;
         lxattrb.st_rdev = make_dev(ma, dev_minor(st_rdev));
+        warn_stray_rdev(lxattrb);
     }
     
     fn set_dev_minor(&mut self, mi: u32) {
         let mut lxattrb = self.lxattrb_mut();
         let st_rdev = lxattrb.st_rdev;
         lxattrb.st_rdev = make_dev(dev_major(st_rdev), mi);
+        warn_stray_rdev(lxattrb);
+    }
+
+    fn get_times(&self) -> Option<(LxfsTime, LxfsTime, LxfsTime)> {
+        self.lxattrb.as_ref().map(|l| (
+            LxfsTime::new(l.st_atime, l.st_atime_nsec),
+            LxfsTime::new(l.st_mtime, l.st_mtime_nsec),
+            LxfsTime::new(l.st_ctime, l.st_ctime_nsec),
+        ))
+    }
+
+    fn set_times(&mut self, atime: Option<LxfsTime>, mtime: Option<LxfsTime>, ctime: Option<LxfsTime>) {
+        let lxattrb = self.lxattrb_mut();
+        if let Some(t) = atime {
+            (lxattrb.st_atime, lxattrb.st_atime_nsec) = t.into();
+        }
+        if let Some(t) = mtime {
+            (lxattrb.st_mtime, lxattrb.st_mtime_nsec) = t.into();
+        }
+        if let Some(t) = ctime {
+            (lxattrb.st_ctime, lxattrb.st_ctime_nsec) = t.into();
+            self.ctime_set = true;
+        }
+    }
+
+    fn list_attrs(&self) -> Vec<(String, &[u8])> {
+        self.lxxattr.iter().flatten()
+            .filter_map(|x| x.value.as_deref().map(|v| (String::from_utf8_lossy(&x.name).into_owned(), v)))
+            .collect()
+    }
+
+    fn get_attr(&self, name: &str) -> Option<&[u8]> {
+        self.lxxattr.as_ref()?.iter()
+            .find(|x| x.name.as_ref() == name.as_bytes())
+            .and_then(|x| x.value.as_deref())
     }
 
     fn set_attr(&mut self, name: &str, value: &[u8]) {
@@ -241,47 +450,64 @@ impl<'a> WslFileAttributes<'a> for LxfsParsed<'a> {
         } else {
             let name = Cow::Owned(name.as_bytes().to_owned());
             let value = Some(Cow::Owned(value.to_owned()));
-            lxxattr.push(LxxattrEntry { name, value });
+            lxxattr.push(LxxattrEntry { name, value, trailing: 0 });
         }
         self.lxxattr = Some(lxxattr);
     }
 
-    fn rm_attr(&mut self, name: &str) {
+    fn rm_attr(&mut self, name: &str) -> bool {
         let mut lxxattr = self.lxxattr.take().unwrap_or_default();
+        let mut found = false;
         if let Some(x) = lxxattr.iter_mut()
             .filter(|x| x.name.as_ref() == name.as_bytes())
             .next()
         {
-            x.value = None;
+            found = x.value.take().is_some();
         }
         self.lxxattr = Some(lxxattr);
+        found
     }
 
-    fn save(&mut self, wsl_file: &mut WslFile) -> std::io::Result<()>  {
-        use crate::ea_parse::{EaOut, get_buffer};
-        use crate::ntfs_io::write_ea;
-
-        let mut ea_out = EaOut::default();
-
-        if let Some(Cow::Owned(ref x)) = self.lxattrb {
-            ea_out.add(LXATTRB.as_bytes(), get_buffer(x));
+    /// LXATTRB from `EaLxattrbV1::new`, a lone LXXATTR without LXATTRB may confuse WSL
+    fn init_default(&mut self) -> bool {
+        if self.lxattrb.is_some() || self.lxattrb_unsupported.is_some() {
+            return false;
         }
+        self.lxattrb_mut();
+        true
+    }
 
-        if let Some(x) = self.lxxattr.take() {            
-            let mut lxxattr_out = LxxattrOut::default();
-            let t = x.into_iter().filter(|attr| {
-                if let Some(ref value) = attr.value {
-                    lxxattr_out.add(&attr.name, value);
-                    true
-                } else {
-                    false
+    fn repair(&mut self, accept: &mut dyn FnMut(&str) -> bool) -> Vec<String> {
+        match self.lxattrb.as_ref() {
+            Some(l) if l.has_stray_rdev() => {
+                let fixed = format!("st_rdev: {}, {} of a non-device file", dev_major(l.st_rdev), dev_minor(l.st_rdev));
+                if !accept(&fixed) {
+                    return vec![];
                 }
-            }).collect();
-            ea_out.add(LXXATTR.as_bytes(), &lxxattr_out.buffer);
-            self.lxxattr = Some(t);
+                self.lxattrb_mut().st_rdev = 0;
+                vec![fixed]
+            },
+            _ => vec![],
+        }
+    }
+
+    fn save(&mut self, ea_store: &mut dyn EaStore) -> std::io::Result<()>  {
+        if self.ctime_update && self.owner_mode_changed && !self.ctime_set {
+            let lxattrb = self.lxattrb_mut();
+            (lxattrb.st_ctime, lxattrb.st_ctime_nsec) = time_utils::now().into();
         }
+        let mut ea_out = EaOut::default();
+        self.save_to(&mut ea_out, TRIM_TRAILING.load(Ordering::Relaxed))?;
 
-        unsafe { write_ea(wsl_file.file_handle, &ea_out.buffer) }
+        ea_store.write_ea(&ea_out.buffer)
+    }
+}
+
+/// device type set on a file not a device, or the mode is set after the device type
+fn warn_stray_rdev(lxattrb: &EaLxattrbV1) {
+    if lxattrb.has_stray_rdev() {
+        warning!("[WARNING] device type: {}, {} set on a {} file, it means nothing but for CHR and BLK",
+            dev_major(lxattrb.st_rdev), dev_minor(lxattrb.st_rdev), lxattrb.st_mode.file_type().name().0);
     }
 }
 
@@ -289,6 +515,8 @@ struct LxxattrEntry<'a> {
     pub name: Cow<'a, [u8]>,
     /// None means will be deleted in save
     pub value: Option<Cow<'a, [u8]>>,
+    /// the unknown byte after the value, written back as read unless `--trim-trailing`
+    pub trailing: u8,
 }
 
 impl<'a> LxxattrEntry<'a> {
@@ -296,15 +524,12 @@ impl<'a> LxxattrEntry<'a> {
         String::from_utf8_lossy(self.name.as_ref()).to_ascii_lowercase()
     }
 
-    fn value_display(&'a self) -> String {
-        use std::fmt::Write;
-
+    /// values longer than `max_len` are truncated, 0 means no truncation
+    fn value_display(&'a self, max_len: usize) -> String {
         if let Some(x) = &self.value {
             let bytes = x.as_ref();
             let mut out = String::with_capacity(bytes.len() + 16);
-            write!(&mut out, "\"").unwrap();
-            crate::escape_utils::escape_bytes_octal(bytes, &mut out, true).unwrap();
-            write!(&mut out, "\"").unwrap();
+            crate::escape_utils::escape_value_display(bytes, max_len, &mut out).unwrap();
 
             out
         } else {
@@ -335,10 +560,6 @@ struct LxxattrEntryRaw {
 }
 
 impl LxxattrEntryRaw {
-    fn size(&self) -> usize {
-        Self::size_inner(self.name_length, self.value_length)
-    }
-
     fn size_inner(name_len: u8, value_len: u16) -> usize {
         let data_len = size_of::<LxxattrEntryRaw>() + name_len as usize + value_len as usize;
         //let full_len = (data_len + LXXATTR_ALIGN - 1) / LXXATTR_ALIGN * LXXATTR_ALIGN;
@@ -351,50 +572,51 @@ impl LxxattrEntryRaw {
 /// |-----------|----|----|
 /// |0          |4   |Always 00 00 01 00|
 /// |4          |4   |LxxattrEntryRaw+|
-#[repr(C)]
-struct LxxattrRaw {
-    flags: u16,            // 0
-    version: u16,          // 1
-    entries: [LxxattrEntryRaw; 1],
-}
+const LXXATTR_HEADER: [u8; 4] = [0, 0, 1, 0];
 
-unsafe fn parse_lxxattr<'a>(buffer: &'a [u8]) -> Vec<LxxattrEntry<'a>> {
+/// set by `--trim-trailing`, the trailing byte of LXXATTR entries is written as 0 instead of as read
+pub static TRIM_TRAILING: AtomicBool = AtomicBool::new(false);
+
+/// entries of a LXXATTR value, with bounds checked, `Err` for a malformed one
+fn parse_lxxattr<'a>(buffer: &'a [u8]) -> Result<Vec<LxxattrEntry<'a>>, String> {
     let mut entries = vec![];
 
-    assert!(buffer.len() >= size_of::<LxxattrRaw>());
+    if buffer.len() < LXXATTR_HEADER.len() + size_of::<LxxattrEntryRaw>() {
+        return Err(format!("too short, size: {}", buffer.len()));
+    }
+    if buffer[..LXXATTR_HEADER.len()] != LXXATTR_HEADER {
+        return Err(format!("unsupported header: {:02x?}", &buffer[..LXXATTR_HEADER.len()]));
+    }
 
-    let buf_range = buffer.as_ptr_range();
-    let praw: &LxxattrRaw = transmute(buffer.as_ptr());
-    assert_eq!(praw.flags, 0);
-    assert_eq!(praw.version, 1);
-    let mut ea_ptr = addr_of!(praw.entries) as *const u8;
-    
+    let mut offset = LXXATTR_HEADER.len();
     loop {
-        assert!(ea_ptr.add(size_of::<LxxattrEntryRaw>()) <= buf_range.end);
-        let pea: &LxxattrEntryRaw = transmute(ea_ptr);
-        let pea_end = ea_ptr.add(pea.size());
+        let header = buffer.get(offset..offset + size_of::<LxxattrEntryRaw>())
+            .ok_or_else(|| format!("truncated entry at offset {}", offset))?;
+        let next_entry_offset = u32::from_le_bytes(header[0..4].try_into().unwrap()) as usize;
+        let value_length = u16::from_le_bytes([header[4], header[5]]);
+        let name_length = header[6];
 
         // invalid ea data may cause read overflow
-        assert!(pea_end <= buf_range.end);
-
-        let pname = &pea.name as *const u8;
-        let name = &*slice_from_raw_parts(pname, pea.name_length as usize);
-
-        let pvalue =  pname.add(pea.name_length as usize);
-        let value = &*slice_from_raw_parts(pvalue, pea.value_length as usize);
+        let size = LxxattrEntryRaw::size_inner(name_length, value_length);
+        if offset + size > buffer.len() {
+            return Err(format!("entry at offset {} exceeds the buffer, size: {}", offset, size));
+        }
 
+        let name_start = offset + offset_of!(LxxattrEntryRaw, name);
+        let value_start = name_start + name_length as usize;
         entries.push(LxxattrEntry {
-            name: Cow::Borrowed(name),
-            value: Some(Cow::Borrowed(value)),
+            name: Cow::Borrowed(&buffer[name_start..value_start]),
+            value: Some(Cow::Borrowed(&buffer[value_start..value_start + value_length as usize])),
+            trailing: buffer[offset + size - 1],
         });
 
-        if pea.next_entry_offset == 0 {
+        if next_entry_offset == 0 {
             break;
         }
-        ea_ptr = ea_ptr.add(pea.next_entry_offset as usize);
+        offset += next_entry_offset;
     }
-    
-    entries
+
+    Ok(entries)
 }
 
 #[derive(Default)]
@@ -412,9 +634,14 @@ impl LxxattrOut {
         self.count
     }
     pub fn add(&mut self, name: &[u8], value: &[u8]) {
+        self.add_with_trailing(name, value, 0);
+    }
+
+    /// `trailing` is the unknown last byte of the entry
+    pub fn add_with_trailing(&mut self, name: &[u8], value: &[u8], trailing: u8) {
         if self.buffer.is_empty() {
             // TODO how about big endian?
-            self.buffer = vec![0, 0, 1, 0];
+            self.buffer = LXXATTR_HEADER.to_vec();
         }
         unsafe {
             let this_size = LxxattrEntryRaw::size_inner(name.len() as u8, value.len() as u16);
@@ -440,9 +667,326 @@ impl LxxattrOut {
             ea.value_length = value.as_ref().len() as u16;
             let pvalue: *mut u8 = pname.add(ea.name_length as usize);
             std::ptr::copy_nonoverlapping(value.as_ptr(), pvalue, ea.value_length as usize);
+            *pvalue.add(ea.value_length as usize) = trailing;
 
             self.last_attr_info = Some((this_pos, this_size));
             self.count += 1;
         }
     }
 }
+
+#[test]
+fn test_lxattrb_flags_display() {
+    assert_eq!(lxattrb_flags_display(0), "0");
+    assert_eq!(lxattrb_flags_display(0x0005), "0x0005 UNKNOWN(0x0005)");
+}
+
+#[test]
+fn test_convert_wslfs_lxfs() {
+    let mut wslfs = WslfsParsed::default();
+    wslfs.set_uid(1000);
+    wslfs.set_gid(1001);
+    wslfs.set_mode(Mode(0o020620));
+    wslfs.set_dev_major(4);
+    wslfs.set_dev_minor(1);
+    wslfs.reparse_tag = Some(StModeType::CHR);
+    wslfs.set_attr("user.test", b"value");
+
+    let lxfs = LxfsParsed::from(&wslfs);
+    assert_eq!(lxfs.get_uid(), Some(1000));
+    assert_eq!(lxfs.get_gid(), Some(1001));
+    assert_eq!(lxfs.get_mode(), Some(Mode(0o020620)));
+    assert_eq!(lxfs.lxattrb.as_ref().unwrap().st_rdev, make_dev(4, 1));
+    let lxxattr = lxfs.lxxattr.as_ref().unwrap();
+    assert_eq!(lxxattr.len(), 1);
+    assert_eq!(lxxattr[0].name.as_ref(), b"user.test");
+    assert_eq!(lxxattr[0].value.as_deref(), Some(b"value".as_slice()));
+
+    let wslfs = WslfsParsed::try_from(&lxfs).unwrap();
+    assert_eq!(wslfs.get_uid(), Some(1000));
+    assert_eq!(wslfs.get_gid(), Some(1001));
+    assert_eq!(wslfs.get_mode(), Some(Mode(0o020620)));
+    assert_eq!((wslfs.get_dev_major(), wslfs.get_dev_minor()), (Some(4), Some(1)));
+    assert_eq!(wslfs.reparse_tag, Some(StModeType::CHR));
+    assert_eq!(wslfs.lx_dot_ea.len(), 1);
+    assert_eq!(wslfs.lx_dot_ea[0].name_display(), "user.test");
+    assert_eq!(wslfs.lx_dot_ea[0].value(), b"value");
+}
+
+#[test]
+fn test_convert_lxfs_to_wslfs_regular_file() {
+    let mut lxfs = LxfsParsed::default();
+    lxfs.set_mode(Mode(0o100644));
+    let wslfs = WslfsParsed::try_from(&lxfs).unwrap();
+    assert_eq!(wslfs.reparse_tag, None);
+    assert!(wslfs.lxdev.is_none());
+
+    lxfs.set_attr("user.\u{e9}", b"value");
+    assert!(WslfsParsed::try_from(&lxfs).is_err());
+}
+
+#[test]
+fn test_stray_rdev() {
+    let mut lxfs = LxfsParsed::default();
+    lxfs.set_mode(Mode(0o100644));
+    lxfs.set_dev_major(1);
+    lxfs.set_dev_minor(3);
+    assert!(lxfs.lxattrb.as_ref().unwrap().has_stray_rdev());
+
+    let mut out = vec![];
+    lxfs.fmt(&mut out, None, &FmtOptions::COMPACT).unwrap();
+    assert!(String::from_utf8(out).unwrap().contains("Device type: 1, 3\n [WARNING] device type of a non-device file"));
+
+    assert_eq!(lxfs.repair(&mut |_| true), vec!["st_rdev: 1, 3 of a non-device file".to_owned()]);
+    assert_eq!(lxfs.lxattrb.as_ref().unwrap().st_rdev, 0);
+    assert!(lxfs.repair(&mut |_| true).is_empty());
+
+    lxfs.set_mode(Mode(0o020620));
+    lxfs.set_dev_major(4);
+    assert!(!lxfs.lxattrb.as_ref().unwrap().has_stray_rdev());
+    assert!(lxfs.repair(&mut |_| true).is_empty());
+}
+
+#[test]
+fn test_fmt_invalid_time() {
+    let mut lxfs = LxfsParsed::default();
+    lxfs.set_uid(1000);
+    lxfs.set_times(None, Some(LxfsTime::new(i64::MAX as u64, 0)), Some(LxfsTime::new(1729741525, 3480100)));
+    lxfs.set_attr("user.after", b"v");
+
+    let mut out = vec![];
+    lxfs.fmt(&mut out, None, &FmtOptions::COMPACT).unwrap();
+    let out = String::from_utf8(out).unwrap();
+    assert!(out.contains("<invalid time: tv_sec: 9223372036854775807, tv_nsec: 0>"), "{}", out);
+    // fields after the corrupt time are still shown
+    assert!(out.contains("2024-10-24 03:45:25.0034801 UTC"), "{}", out);
+    assert!(out.contains("user.after"), "{}", out);
+}
+
+#[test]
+fn test_fmt_name_filter() {
+    let mut lxfs = LxfsParsed::default();
+    lxfs.set_attr("user.a", b"1");
+    lxfs.set_attr("security.b", b"2");
+
+    let mut out = vec![];
+    lxfs.fmt(&mut out, None, &FmtOptions { name_filter: Some("user.*".to_owned()), ..FmtOptions::COMPACT }).unwrap();
+    let out = String::from_utf8(out).unwrap();
+    assert!(out.contains("user.a"), "{}", out);
+    assert!(!out.contains("security.b"), "{}", out);
+
+    // no header if all attributes are filtered out
+    let mut out = vec![];
+    lxfs.fmt(&mut out, None, &FmtOptions { name_filter: Some("trusted.*".to_owned()), ..FmtOptions::COMPACT }).unwrap();
+    let out = String::from_utf8(out).unwrap();
+    assert!(!out.contains("LXXATTR"), "{}", out);
+}
+
+#[test]
+fn test_ctime_update() {
+    use crate::wsl_file::MemEaStore;
+
+    let ctime = LxfsTime::new(1000, 0);
+    let load_ctime = |ea_store: &MemEaStore| {
+        let ea_buffer = ea_store.read_ea().unwrap();
+        let ea_parsed = ea_buffer.as_ref().map(|ea_buffer| crate::ea_parse::parse_ea(ea_buffer));
+        let (_, _, ctime) = LxfsParsed::load(ea_store, &ea_parsed).get_times().unwrap();
+        ctime
+    };
+    for ctime_update in [true, false] {
+        let mut ea_store = MemEaStore::default();
+        let mut lxfs = LxfsParsed::default();
+        lxfs.set_mode(Mode(0o100644));
+        lxfs.set_times(None, None, Some(ctime));
+        lxfs.save(&mut ea_store).unwrap();
+        assert_eq!(load_ctime(&ea_store), ctime);
+
+        let ea_buffer = ea_store.read_ea().unwrap();
+        let ea_parsed = ea_buffer.as_ref().map(|ea_buffer| crate::ea_parse::parse_ea(ea_buffer));
+        let mut lxfs = LxfsParsed::load(&ea_store, &ea_parsed);
+        lxfs.ctime_update = ctime_update;
+        lxfs.set_mode(Mode(0o100755));
+        lxfs.save(&mut ea_store).unwrap();
+        assert_eq!(load_ctime(&ea_store) > ctime, ctime_update);
+    }
+}
+
+#[test]
+fn test_set_times() {
+    let mut lxfs = LxfsParsed::default();
+    assert!(lxfs.get_times().is_none());
+
+    let atime = LxfsTime::new(1729741525, 3480100);
+    let mtime = LxfsTime::new(1729741526, 1);
+    lxfs.set_times(Some(atime), Some(mtime), None);
+    let (a, m, _) = lxfs.get_times().unwrap();
+    assert_eq!((a, m), (atime, mtime));
+
+    let ctime = LxfsTime::new(1729741527, 999999999);
+    lxfs.set_times(None, None, Some(ctime));
+    assert_eq!(lxfs.get_times().unwrap(), (atime, mtime, ctime));
+}
+
+#[test]
+fn test_init_default_for_attr() {
+    use crate::ea_parse::parse_ea;
+    use crate::posix::DEFAULT_MODE;
+    use crate::wsl_file::MemEaStore;
+
+    let mut ea_store = MemEaStore::default();
+    let ea_parsed = None;
+    let mut lxfs = LxfsParsed::load(&ea_store, &ea_parsed);
+    assert!(!lxfs.maybe());
+    assert!(lxfs.init_default());
+    assert!(!lxfs.init_default());
+    lxfs.set_attr("user.test", b"value");
+    lxfs.save(&mut ea_store).unwrap();
+
+    let ea_buffer = ea_store.read_ea().unwrap();
+    let ea_parsed = ea_buffer.as_ref().map(|ea_buffer| parse_ea(ea_buffer));
+    let lxfs = LxfsParsed::load(&ea_store, &ea_parsed);
+    assert_eq!(lxfs.get_mode(), Some(DEFAULT_MODE));
+    assert_eq!((lxfs.get_uid(), lxfs.get_gid()), (Some(0), Some(0)));
+    assert_eq!(lxfs.get_attr("user.test"), Some(b"value".as_slice()));
+}
+
+#[test]
+fn test_empty_attr_value() {
+    use crate::ea_parse::parse_ea;
+    use crate::wsl_file::MemEaStore;
+
+    let mut ea_store = MemEaStore::default();
+    let mut lxfs = LxfsParsed::default();
+    lxfs.init_default();
+    lxfs.set_attr("user.empty", b"");
+    lxfs.set_attr("user.removed", b"value");
+    lxfs.save(&mut ea_store).unwrap();
+
+    let ea_buffer = ea_store.read_ea().unwrap();
+    let ea_parsed = ea_buffer.as_ref().map(|ea_buffer| parse_ea(ea_buffer));
+    let mut lxfs = LxfsParsed::load(&ea_store, &ea_parsed);
+    assert_eq!(lxfs.get_attr("user.empty"), Some(b"".as_slice()));
+    assert_eq!(lxfs.get_attr("user.removed"), Some(b"value".as_slice()));
+    lxfs.rm_attr("user.removed");
+    lxfs.save(&mut ea_store).unwrap();
+    assert_eq!(lxfs.get_attr("user.removed"), None);
+    assert_eq!(lxfs.list_attrs(), vec![("user.empty".to_owned(), b"".as_slice())]);
+}
+
+#[test]
+fn test_fmt_times_options() {
+    use crate::wslfs::WslfsParsed;
+
+    let lxfs = LxfsParsed::from(&WslfsParsed::default());
+    let fmt = |opts: &FmtOptions| {
+        let mut out = vec![];
+        lxfs.fmt(&mut out, None, opts).unwrap();
+        String::from_utf8(out).unwrap()
+    };
+
+    let out = fmt(&FmtOptions { hide_atime: true, ..Default::default() });
+    assert!(out.contains("  User:") && out.contains("Last file modification:"));
+    assert!(!out.contains("Last file access:"));
+
+    let out = fmt(&FmtOptions { only_times: true, ..Default::default() });
+    assert!(out.contains("Last file access:") && out.contains("Last status change:"));
+    assert!(!out.contains("  User:"));
+}
+
+#[test]
+fn test_fmt_epoch_time() {
+    let mut lxfs = LxfsParsed::default();
+    lxfs.set_times(Some(LxfsTime::new(1729741525, 1)), Some(LxfsTime::new(1729741525, 123456789)), Some(LxfsTime::new(0, 999999999)));
+
+    let mut out = vec![];
+    lxfs.fmt(&mut out, None, &FmtOptions { epoch_time: true, only_times: true, ..FmtOptions::COMPACT }).unwrap();
+    assert_eq!(String::from_utf8(out).unwrap(), concat!(
+        "LXATTRB:\n",
+        "  Last file access: 1729741525.000000001 (LXATTRB)\n",
+        "  Last file modification: 1729741525.123456789 (LXATTRB)\n",
+        "  Last status change: 0.999999999 (LXATTRB)\n",
+    ));
+}
+
+#[test]
+fn test_fuzz_parse_lxxattr() {
+    use crate::ea_parse::{ea_corpus, fuzz_inputs, parse_ea};
+
+    let mut corpus: Vec<Vec<u8>> = ea_corpus().iter()
+        .flat_map(|buf| parse_ea(buf).into_iter().filter(|ea| ea.name == LXXATTR.as_bytes()).map(|ea| ea.value.to_vec()).collect::<Vec<_>>())
+        .collect();
+    let mut lxxattr_out = LxxattrOut::default();
+    lxxattr_out.add(b"user.a", b"");
+    lxxattr_out.add(b"security.capability", &[1, 0, 0, 2, 0, 0x20, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+    corpus.push(lxxattr_out.buffer);
+
+    for buf in fuzz_inputs(&corpus, 0x1a77, 20000) {
+        let Ok(entries) = parse_lxxattr(&buf) else {
+            continue;
+        };
+        let mut lxxattr_out = LxxattrOut::default();
+        for entry in &entries {
+            lxxattr_out.add_with_trailing(&entry.name, entry.value.as_deref().unwrap(), entry.trailing);
+        }
+        let round_trip = parse_lxxattr(&lxxattr_out.buffer).unwrap();
+        assert_eq!(round_trip.len(), entries.len());
+        for (a, b) in entries.iter().zip(&round_trip) {
+            assert_eq!((&a.name, &a.value, a.trailing), (&b.name, &b.value, b.trailing));
+        }
+    }
+}
+
+#[test]
+fn test_lxxattr_trailing_byte_round_trip() {
+    let mut lxxattr_out = LxxattrOut::default();
+    lxxattr_out.add_with_trailing(b"user.a", b"1", 0x5a);
+    lxxattr_out.add_with_trailing(b"user.b", b"", 0xff);
+    let buffer = lxxattr_out.buffer;
+    assert_eq!(buffer[4 + 7 + 6 + 1], 0x5a);
+    assert_eq!(*buffer.last().unwrap(), 0xff);
+
+    let mut ea_out = EaOut::default();
+    ea_out.add(LXXATTR.as_bytes(), &buffer);
+    let ea_parsed = Some(crate::ea_parse::parse_ea(&ea_out.buffer));
+    let mut lxfs = LxfsParsed::load(&crate::wsl_file::MemEaStore::default(), &ea_parsed);
+    assert_eq!(lxfs.lxxattr.as_ref().unwrap().iter().map(|x| x.trailing).collect::<Vec<_>>(), vec![0x5a, 0xff]);
+
+    let mut saved = EaOut::default();
+    lxfs.save_to(&mut saved, false).unwrap();
+    assert_eq!(saved.buffer, ea_out.buffer);
+
+    let mut trimmed = EaOut::default();
+    lxfs.save_to(&mut trimmed, true).unwrap();
+    let mut zeroed = LxxattrOut::default();
+    zeroed.add(b"user.a", b"1");
+    zeroed.add(b"user.b", b"");
+    let mut expected = EaOut::default();
+    expected.add(LXXATTR.as_bytes(), &zeroed.buffer);
+    assert_eq!(trimmed.buffer, expected.buffer);
+}
+
+#[test]
+fn test_downgrade_keeps_broken_prefix() {
+    use crate::ea_parse::{parse_ea, EaOut};
+    use crate::wsl_file::{EaStore, MemEaStore};
+
+    let mut ea_out = EaOut::default();
+    ea_out.add(b"LX.user.missing", b"value1");
+    ea_out.add(b"LX.user.ok", b"lxeavalue2");
+    let ea_store = MemEaStore::from_ea_buffer(&ea_out.buffer);
+    let ea_buffer = ea_store.read_ea().unwrap();
+    let ea_parsed = ea_buffer.as_ref().map(|ea_buffer| parse_ea(ea_buffer));
+    let wslfs = WslfsParsed::load(&ea_store, &ea_parsed);
+
+    let mut lxfs = LxfsParsed::from(&wslfs);
+    let mut ea_store = MemEaStore::default();
+    let mut ea_out = EaOut::default();
+    lxfs.save_to(&mut ea_out, false).unwrap();
+    ea_store.write_ea(&ea_out.buffer).unwrap();
+
+    let ea_buffer = ea_store.read_ea().unwrap();
+    let ea_parsed = ea_buffer.as_ref().map(|ea_buffer| parse_ea(ea_buffer));
+    let lxfs = LxfsParsed::load(&ea_store, &ea_parsed);
+    assert_eq!(lxfs.get_attr("user.missing"), Some(b"value1".as_slice()));
+    assert_eq!(lxfs.get_attr("user.ok"), Some(b"value2".as_slice()));
+}
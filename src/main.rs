@@ -1,19 +1,49 @@
 #![cfg_attr(debug_assertions, allow(dead_code, unused_imports, unused_variables, unused_mut))]
 
+use std::ffi::OsString;
+use std::io::{IsTerminal, Write};
+use std::ops::ControlFlow;
 use std::path::{absolute, Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
 use clap::{arg, command, Parser, Subcommand};
 
 use ea_parse::{EaEntry, EaOut};
-use lxfs::{EaLxattrbV1, LxfsParsed, LxxattrOut, LXATTRB, LXXATTR};
+use escape_utils::{InputFormat, ValueFormat};
+use lxfs::LxfsParsed;
 use ntfs_io::{delete_reparse_point, query_file_basic_infomation, write_data};
-use path_utils::{is_path_prefix_disk, is_unix_absolute, try_get_abs_path_prefix, try_get_distro_from_unc_prefix};
+use path_utils::{is_path_prefix_disk, is_unix_absolute, normalize_path, try_get_abs_path_prefix, try_get_distro_from_unc_prefix};
 use distro::{Distro, DistroSource, FsType};
-use posix::{chmod_all, lsperms, StModeType, DEFAULT_MODE};
+use error::WslAttrError;
+use posix::{chmod_all, DevSpec, Mode, StModeType};
 use time_utils::LxfsTime;
-use windows::Win32::Foundation::HANDLE;
-use wsl_file::{open_handle, WslFile, WslFileAttributes};
+use windows::Wdk::Storage::FileSystem::FILE_BASIC_INFORMATION;
+use windows::Win32::Foundation::{HANDLE, NTSTATUS};
+use wsl_file::{open_handle, EaStore, FmtOptions, MemEaStore, WslFile, WslFileAttributes, XattrNamespace};
 use wslfs::WslfsParsed;
 
+/// `eprintln!` for `[WARNING]` and `[NOTE]` lines, silenced by `--quiet` which keeps errors only,
+/// on stderr to keep the output of `--json` and `--porcelain` clean,
+/// defined before the modules to be used by them
+macro_rules! warning {
+    ($($arg:tt)*) => {
+        if !$crate::QUIET.load(std::sync::atomic::Ordering::Relaxed) {
+            eprintln!($($arg)*);
+        }
+    };
+}
+
+/// `println!` for `[ERROR]` lines, on stderr under `--porcelain` to keep its records clean,
+/// defined before the modules to be used by them
+macro_rules! error {
+    ($($arg:tt)*) => {
+        if $crate::PORCELAIN.load(std::sync::atomic::Ordering::Relaxed) {
+            eprintln!($($arg)*);
+        } else {
+            println!($($arg)*);
+        }
+    };
+}
+
 mod distro;
 mod path_utils;
 mod wsl_file;
@@ -24,6 +54,35 @@ mod wslfs;
 mod time_utils;
 mod posix;
 mod escape_utils;
+mod win_acl;
+mod win_attr;
+mod diff;
+mod error;
+mod console;
+mod json;
+
+static QUIET: AtomicBool = AtomicBool::new(false);
+
+/// set by `--porcelain`, the friendly messages of `info!` are suppressed and errors go to stderr
+static PORCELAIN: AtomicBool = AtomicBool::new(false);
+
+/// set by `--events ndjson`
+static EVENTS: AtomicBool = AtomicBool::new(false);
+
+/// set by `--fail-fast`, a recursive or batch run stops at the first failed file
+static FAIL_FAST: AtomicBool = AtomicBool::new(false);
+
+/// set by `--no-distro`, no distro is loaded and the registry is not read
+static NO_DISTRO: AtomicBool = AtomicBool::new(false);
+
+/// `println!` for success and informational output, silenced by `--quiet`
+macro_rules! info {
+    ($($arg:tt)*) => {
+        if !QUIET.load(Ordering::Relaxed) && !PORCELAIN.load(Ordering::Relaxed) {
+            println!($($arg)*);
+        }
+    };
+}
 
 #[derive(Parser, Debug)]
 #[command(version, about, long_about = None, args_conflicts_with_subcommands = true)]
@@ -33,6 +92,110 @@ struct Args {
 
     #[clap(flatten)]
     args_view: Option<ArgsView>,
+
+    /// suppress all output except errors
+    #[arg(long, short, global = true)]
+    quiet: bool,
+
+    /// retries to open a file locked by WSL or another process, before it fails or is skipped
+    #[arg(long, global = true, default_value_t = 3)]
+    open_retries: u32,
+
+    /// delay in ms before the first retry to open a locked file, doubled for each retry
+    #[arg(long, global = true, default_value_t = 50)]
+    open_retry_delay: u32,
+
+    /// ignore EA's whose names have NUL or trailing spaces, instead of trimming the names to match
+    #[arg(long, global = true)]
+    strict: bool,
+
+    /// write 0 as the unknown trailing byte of each LXXATTR entry, instead of the byte WSL wrote
+    #[arg(long, global = true)]
+    trim_trailing: bool,
+
+    /// emit a progress event to stderr for each file processed, like {"path":"a","action":"chmod","result":"ok"}
+    #[arg(long, global = true, value_enum)]
+    events: Option<EventsFormat>,
+
+    /// indent `--json` output for human reading, a json object is no longer in one line
+    #[arg(long, global = true)]
+    json_pretty: bool,
+
+    /// stop a recursive or batch run at the first failed file, exit non-zero immediately
+    #[arg(long, global = true, overrides_with = "skip_errors")]
+    fail_fast: bool,
+
+    /// continue a recursive or batch run after a failed file, exit non-zero at the end if any failed, the default
+    #[arg(long, global = true, overrides_with = "fail_fast")]
+    skip_errors: bool,
+
+    /// print what this build supports as json, the fs types, the reparse tags, the commands and `schema_version`
+    #[arg(long)]
+    capabilities: bool,
+
+    /// code page of the console for non-ASCII names and values, output redirected to a file or a pipe is UTF-8 always
+    #[arg(long, global = true, value_enum, default_value_t = OutputEncoding::Utf8)]
+    output_encoding: OutputEncoding,
+
+    /// offline workflow: the directory of "/" of a distro not in registry, like a copied or backed-up one,
+    /// it is used for all paths without `--distro`, unix paths like /etc/passwd are resolved in it,
+    /// user and group names are from its etc/passwd and etc/group
+    #[arg(long, global = true, value_name = "DIR")]
+    rootfs: Option<PathBuf>,
+
+    /// WSL1 fs type of the `--rootfs` distro, as `Version` in registry of a registered one
+    #[arg(long, global = true, value_enum, default_value_t = FsType::Lxfs, requires = "rootfs")]
+    rootfs_fs_type: FsType,
+
+    /// passwd file for user names of the `--rootfs` distro, instead of its etc/passwd
+    #[arg(long = "passwd", global = true, value_name = "FILE", requires = "rootfs")]
+    passwd_file: Option<PathBuf>,
+
+    /// group file for group names of the `--rootfs` distro, instead of its etc/group
+    #[arg(long = "group", global = true, value_name = "FILE", requires = "rootfs")]
+    group_file: Option<PathBuf>,
+
+    /// never load a distro nor read the registry, to inspect raw metadata of disk paths quickly and quietly:
+    /// uid and gid are shown numeric, user and group names are not accepted, unix paths, WSL UNC paths, `--distro` and `which` are refused
+    #[arg(long, global = true, conflicts_with_all = ["rootfs", "distro_root_override"])]
+    no_distro: bool,
+
+    /// advanced, mainly for tests: a distro not in registry as "NAME=BASE_PATH,FS_TYPE",
+    /// BASE_PATH contains `rootfs`, e.g. a fixture tree or a copied distro,
+    /// it is found by name and by path before distros in registry
+    #[arg(long, global = true, hide = true, env = "WSLATTR_DISTRO_ROOT_OVERRIDE", value_parser = distro::parse_distro_override)]
+    distro_root_override: Option<distro::DistroOverride>,
+}
+
+/// format of `--events`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+enum EventsFormat {
+    /// one json object per line
+    Ndjson,
+}
+
+/// `--output-encoding`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+enum OutputEncoding {
+    /// set the console to UTF-8 until exit
+    Utf8,
+    /// keep the code page of the console
+    Ansi,
+}
+
+/// `{"path","action","result"}`
+fn event_json(path: &Path, action: &str, result: &str) -> String {
+    let mut out = String::from("{\"path\":\"");
+    escape_utils::escape_json_str(&path.to_string_lossy(), &mut out).unwrap();
+    out += &format!("\",\"action\":\"{}\",\"result\":\"{}\"}}", action, result);
+    out
+}
+
+/// one `--events` line to stderr, the normal output is not affected
+fn emit_event(path: &Path, action: &str, result: &str) {
+    if EVENTS.load(Ordering::Relaxed) {
+        eprintln!("{}", json::document(&event_json(path, action, result)));
+    }
 }
 
 #[derive(Parser, Debug)]
@@ -43,6 +206,65 @@ struct ArgsView {
     /// WSL distro from registry, for user and group name
     #[arg(long, short)]
     distro: Option<String>,
+
+    /// write output to file instead of stdout
+    #[arg(long, short)]
+    output: Option<PathBuf>,
+
+    /// minimal spacing between labels and values
+    #[arg(long, conflicts_with = "width")]
+    compact: bool,
+
+    /// width of the label column, default 28, or compact on a narrow console
+    #[arg(long)]
+    width: Option<usize>,
+
+    /// truncate displayed attribute values longer than N bytes, 0 means no truncation,
+    /// `--show-raw-ea` always outputs full values
+    #[arg(long, value_name = "N", default_value_t = wsl_file::DEFAULT_MAX_VALUE_LEN)]
+    max_value_len: usize,
+
+    /// display full attribute values, same as `--max-value-len 0`
+    #[arg(long, conflicts_with = "max_value_len")]
+    full: bool,
+
+    /// do not show access times, which are stale on a noatime mount
+    #[arg(long)]
+    hide_atime: bool,
+
+    /// show only the NTFS times and the lxfs times
+    #[arg(long)]
+    only_times: bool,
+
+    /// colorize the access string and the type of file, `auto` only when stdout is a console
+    #[arg(long, value_enum, default_value_t = ColorWhen::Auto)]
+    color: ColorWhen,
+
+    /// show only attributes and raw EA's whose names match the glob, `*` any chars, `?` one char, case insensitive,
+    /// like `user.*` or `LX.*` with `--show-raw-ea`, not applied to `--json`;
+    /// a name without `*` or `?` selects that one attribute or EA, as `--show-raw-ea --name-filter LXATTRB`
+    #[arg(long, value_name = "GLOB")]
+    name_filter: Option<String>,
+
+    /// show times as `<seconds>.<nanoseconds>` since the Unix epoch, marked NTFS(100ns) or LXATTRB(1ns),
+    /// `--json` always has them
+    #[arg(long)]
+    epoch_time: bool,
+}
+
+impl ArgsView {
+    /// `path` with the defaults of the command line, for a command which loads the file as `view` does
+    fn of_path(path: PathBuf) -> Self {
+        ArgsView::parse_from([OsString::from(env!("CARGO_PKG_NAME")), OsString::from("--"), path.into_os_string()])
+    }
+}
+
+/// `--color`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+enum ColorWhen {
+    Auto,
+    Always,
+    Never,
 }
 
 #[derive(Parser, Debug)]
@@ -50,18 +272,178 @@ struct ArgsChange {
     /// file to change
     path: PathBuf,
 
-    /// WSL1 fs type, if provided ignore fs type from `--distro`
+    /// WSL1 fs type, if provided ignore fs type from `--distro` and the metadata found
     #[arg(long, short = 't')]
     fs_type: Option<distro::FsType>,
 
+    /// WSL1 fs type only if it cannot be determined, cause both or neither wslfs and lxfs metadata exist,
+    /// the precedence is `--fs-type`, `--distro`, the metadata found, `set-attr --create`, then `--assume`
+    #[arg(long, conflicts_with("fs_type"))]
+    assume: Option<distro::FsType>,
+
     /// WSL distro from registry, to get WSL1 fs type
     #[arg(long, short)]
     distro: Option<String>,
+
+    /// do not warn when `--fs-type` disagrees with the fs type of the distro, and write even if nothing changes
+    #[arg(long)]
+    force: bool,
+
+    /// restore NTFS file times after change, this is the default
+    #[arg(long, overrides_with("no_preserve_times"))]
+    preserve_times: bool,
+
+    /// let NTFS update file times after change
+    #[arg(long, overrides_with("preserve_times"))]
+    no_preserve_times: bool,
+
+    /// keep the lxfs `st_ctime` when uid, gid or mode changes, by default it is set to now as linux does
+    #[arg(long)]
+    no_ctime_update: bool,
+
+    #[clap(flatten)]
+    args_recursive: ArgsRecursive,
+
+    /// refuse to change the rootfs of a distro recursively, this is the default,
+    /// with `--no-distro` any directory containing `etc/passwd` is taken as a rootfs
+    #[arg(long, overrides_with("no_preserve_root"))]
+    preserve_root: bool,
+
+    /// allow to change the rootfs of a distro recursively, like `chmod -R 777 /`
+    #[arg(long, overrides_with("preserve_root"))]
+    no_preserve_root: bool,
+
+    /// only change files modified since the time, unix seconds, "@SECS[.NSEC]", "YYYY-MM-DD[ HH:MM:SS[.FRACTION]]" in UTC or RFC 3339
+    #[arg(long, requires("recursive"), value_parser = time_utils::parse_time)]
+    modified_since: Option<LxfsTime>,
+
+    /// only change files owned by the uid, also for `remap-owners` which is always recursive
+    #[arg(long)]
+    uid: Option<u32>,
+
+    /// only change files owned by the gid
+    #[arg(long)]
+    gid: Option<u32>,
+
+    /// print a tab separated line for each mode, uid, gid or attribute changed, instead of the friendly messages:
+    /// ACTION PATH FS_TYPE FIELD OLD NEW, FIELD is `mode`, `uid`, `gid` or `attr:NAME`,
+    /// mode is 7 octal digits, attribute values are quoted and escaped as `view`, `-` is not set,
+    /// errors and warnings are still printed as `[ERROR]` and `[WARNING]` lines on stderr, warnings not with `--quiet`
+    #[arg(long)]
+    porcelain: bool,
+
+    /// prompt before changing each file with its current and proposed values, like `cp -i`:
+    /// `y` applies, `n` skips, `a` applies the rest without prompting, `q` quits,
+    /// disabled if stdin is not interactive
+    #[arg(long, short = 'i', requires("recursive"))]
+    confirm_each: bool,
+
+    /// do not prompt for confirmation before a recursive change, `retype` or `repair`,
+    /// required if stdin is not interactive
+    #[arg(long, short)]
+    yes: bool,
+
+    /// fs type for a file without wslfs nor lxfs metadata, set by `set-attr --create`
+    #[arg(skip)]
+    create: Option<FsType>,
+}
+
+#[derive(Parser, Debug, Default)]
+struct ArgsRecursive {
+    /// all files in the directory recursively
+    #[arg(long, short = 'R')]
+    recursive: bool,
+
+    /// descend at most N directories below the path
+    #[arg(long, requires("recursive"))]
+    max_depth: Option<usize>,
+
+    /// descend into other distros and other file systems
+    #[arg(long, requires("recursive"))]
+    cross_boundaries: bool,
+}
+
+/// what `view` shows besides the metadata, the command line without a command shows none
+#[derive(Parser, Debug, Default)]
+struct ArgsViewOptions {
+    /// output one json object per line for each file, or indented objects by `--json-pretty`
+    #[arg(long)]
+    json: bool,
+
+    /// show Windows owner, group and DACL
+    #[arg(long, conflicts_with("json"))]
+    win_acl: bool,
+
+    /// also list all raw EA's and the reparse tag
+    #[arg(long, conflicts_with("json"))]
+    show_raw_ea: bool,
+
+    /// hex dump the EA's and the reparse point, always for a file with EA's of neither wslfs nor lxfs
+    #[arg(long, conflicts_with("json"))]
+    hex_dump: bool,
+
+    /// show the wslfs and the lxfs metadata of the file side by side, and which one the distro reads
+    #[arg(long, conflicts_with_all(["json", "recursive"]))]
+    both: bool,
+
+    /// report lxfs times which drifted from the NTFS times, fix them by `sync-times`
+    #[arg(long, conflicts_with("json"))]
+    sync_check: bool,
+
+    /// also show the symlink target as a windows path in the rootfs of the distro and whether it exists,
+    /// symlinks in the target path are not followed
+    #[arg(long, conflicts_with("json"))]
+    resolve_symlink: bool,
+
+    /// PATH is a raw EA buffer dumped to a file, decode it offline without NTFS timestamps
+    #[arg(long, conflicts_with_all(["json", "win_acl", "recursive"]))]
+    ea_file: bool,
+
+    /// for a bug report, write the raw EA buffer to FILE, the raw reparse buffer to FILE.reparse if any,
+    /// and the path, fs type guess and NTFS times to FILE.json, nothing is scrubbed,
+    /// FILE is read back by `view --ea-file FILE`
+    #[arg(long, value_name = "FILE", conflicts_with_all(["recursive", "ea_file"]))]
+    dump_ea_raw: Option<PathBuf>,
+
+    /// encoding of raw EA values, also of the whole dump file with `--ea-file`, text is raw bytes
+    #[arg(long, value_enum, default_value_t)]
+    format: ValueFormat,
+}
+
+/// names `set-attr` and `rm-attr` refuse unless allowed
+#[derive(Parser, Debug)]
+struct ArgsAllow {
+    /// change names in the `trusted.*`, `security.*` and `system.*` namespaces treated specially by WSL
+    #[arg(long)]
+    allow_namespace: bool,
+
+    /// change `LX.LINUX.ATTR.*` of wslfs, which are kept by WSL itself
+    #[arg(long)]
+    allow_internal: bool,
+}
+
+impl ArgsChange {
+    fn preserve_times(&self) -> bool {
+        !self.no_preserve_times
+    }
+
+    fn preserve_root(&self) -> bool {
+        !self.no_preserve_root
+    }
 }
 
 #[derive(Subcommand, Debug)]
 enum Command {
-    View(ArgsView),
+    View {
+        #[clap(flatten)]
+        args_view: ArgsView,
+
+        #[clap(flatten)]
+        args_recursive: ArgsRecursive,
+
+        #[clap(flatten)]
+        args_view_options: ArgsViewOptions,
+    },
     Chown {
         /// uid or user name(with valid distro)
         user: String,
@@ -77,29 +459,151 @@ enum Command {
         args_change: ArgsChange,
     },
     Chmod {
-        /// posix modes string, "0844", "u+x,g-t"
+        /// posix modes string, "0644", "u+x,g-t", or octal then symbolic "644,u+x"
         modes: String,
 
         #[clap(flatten)]
         args_change: ArgsChange,
     },
     SetAttr {
+        /// xattr name as on linux like `user.foo`, the same for wslfs and lxfs,
+        /// a wslfs EA name like `LX.USER.FOO` is accepted too,
+        /// names not in `user.*` are changed only with `--allow-namespace`
         #[arg(long, short)]
         name: String,
 
-        #[arg(long, short)]
+        /// value, '' for an empty value, "0x" prefixed hex or "0s" prefixed base64 unless `--input-format`,
+        /// required if no `--from-file`, use `rm-attr` to remove the attribute
+        #[arg(long, short, required_unless_present("from_file"))]
         value: Option<String>,
 
+        /// how `--value` is read, `raw` to store a value like "0xABCD" literally
+        #[arg(long, value_enum, default_value_t = InputFormat::Auto, conflicts_with("from_file"))]
+        input_format: InputFormat,
+
+        /// read the raw bytes of the file as the value, not unescaped
+        #[arg(long, value_name = "FILE", conflicts_with("value"))]
+        from_file: Option<PathBuf>,
+
+        /// for a file without metadata, create default metadata of the fs type,
+        /// LXATTRB is created for lxfs as a lone LXXATTR may confuse WSL
+        #[arg(long, value_name = "FS_TYPE")]
+        create: Option<FsType>,
+
+        #[clap(flatten)]
+        args_allow: ArgsAllow,
+
         #[clap(flatten)]
         args_change: ArgsChange,
     },
+    /// print the value of an attribute escaped as `view`, or write its raw bytes to a file
+    GetAttr {
+        /// file to read
+        path: PathBuf,
+
+        /// xattr name as on linux like `user.foo`, or a wslfs EA name like `LX.USER.FOO`
+        #[arg(long, short)]
+        name: String,
+
+        /// write the raw bytes of the value to the file, for `set-attr --from-file`
+        #[arg(long, value_name = "FILE")]
+        to_file: Option<PathBuf>,
+
+        /// WSL distro from registry, for unix paths
+        #[arg(long, short)]
+        distro: Option<String>,
+    },
     RmAttr {
+        /// xattr name as `set-attr`
         #[arg(long, short)]
         name: String,
 
+        #[clap(flatten)]
+        args_allow: ArgsAllow,
+
+        #[clap(flatten)]
+        args_change: ArgsChange,
+    },
+    /// set file times, both NTFS times and lxfs times with nanoseconds
+    SetTime {
+        /// access time, unix seconds, "@SECS[.NSEC]", "YYYY-MM-DD[ HH:MM:SS[.FRACTION]]" in UTC or RFC 3339
+        #[arg(long, conflicts_with("reference"), value_parser = time_utils::parse_time)]
+        atime: Option<LxfsTime>,
+
+        /// modification time
+        #[arg(long, conflicts_with("reference"), value_parser = time_utils::parse_time)]
+        mtime: Option<LxfsTime>,
+
+        /// status change time
+        #[arg(long, conflicts_with("reference"), value_parser = time_utils::parse_time)]
+        ctime: Option<LxfsTime>,
+
+        /// copy all times from the file, like `touch -r`
+        #[arg(long, short, required_unless_present_any(["atime", "mtime", "ctime"]))]
+        reference: Option<PathBuf>,
+
+        #[clap(flatten)]
+        args_change: ArgsChange,
+    },
+    /// copy NTFS times to the lxfs times in LXATTRB, or the reverse, which drift when a Windows program touches a file
+    SyncTimes {
+        /// copy NTFS times to the lxfs times, which WSL shows
+        #[arg(long, conflicts_with("to_ntfs"), required_unless_present("to_ntfs"))]
+        to_ea: bool,
+
+        /// copy the lxfs times to NTFS times, which Windows shows
+        #[arg(long)]
+        to_ntfs: bool,
+
         #[clap(flatten)]
         args_change: ArgsChange,
     },
+    /// copy owner, group, permissions, extended attributes and times of a file to other files
+    CopyAttr {
+        /// file to copy from
+        src: PathBuf,
+
+        /// files to copy to
+        #[arg(long, num_args = 1.., required_unless_present("to_stdin"))]
+        to: Vec<PathBuf>,
+
+        /// read files to copy to from stdin, one path per line
+        #[arg(long)]
+        to_stdin: bool,
+
+        /// copy extended attributes only, implies `--no-times`
+        #[arg(long)]
+        attrs_only: bool,
+
+        /// do not copy file times
+        #[arg(long)]
+        no_times: bool,
+
+        /// WSL1 fs type of the files to copy to, if provided ignore fs type from `--distro`
+        #[arg(long, short = 't')]
+        fs_type: Option<distro::FsType>,
+
+        /// WSL distro from registry, for unix paths and WSL1 fs type
+        #[arg(long, short)]
+        distro: Option<String>,
+    },
+    /// convert the metadata of one file to another WSL1 fs type, the registry Version is not changed
+    Convert {
+        /// file to change
+        path: PathBuf,
+
+        /// WSL1 fs type to convert to
+        #[arg(long)]
+        to: FsType,
+
+        /// use default metadata for a file without wslfs nor lxfs metadata
+        #[arg(long)]
+        create: bool,
+
+        /// WSL distro from registry, for unix paths
+        #[arg(long, short)]
+        distro: Option<String>,
+    },
     Downgrade {
         /// file to change
         #[clap(conflicts_with("distro"))]
@@ -109,6 +613,131 @@ enum Command {
         #[clap(conflicts_with("path"))]
         #[arg(long, short)]
         distro: Option<String>,
+
+        /// do not prompt for confirmation
+        #[arg(long, short)]
+        yes: bool,
+
+        /// check the NTFS object id of each file is not changed by downgrade, warn if it is
+        #[arg(long)]
+        preserve_object_id: bool,
+    },
+    /// remap uids and gids of all files in the directory recursively, unmapped ids are not changed
+    RemapOwners {
+        /// mapping file of "olduid:newuid" and "g:oldgid:newgid" lines
+        #[arg(long)]
+        map: PathBuf,
+
+        #[clap(flatten)]
+        args_change: ArgsChange,
+    },
+    /// rewrite the file type bits of mode, and the reparse tag of wslfs, to repair a file
+    Retype {
+        /// new file type
+        #[arg(long)]
+        set_type: StModeType,
+
+        #[clap(flatten)]
+        args_change: ArgsChange,
+    },
+    /// fix known corruptions of attributes, like a wrong 'lxea' prefix of wslfs LX.* values
+    Repair {
+        /// only print what would be repaired, nothing is written
+        #[arg(long, conflicts_with("yes"))]
+        dry_run: bool,
+
+        #[clap(flatten)]
+        args_change: ArgsChange,
+    },
+    /// create a character or block device node, or all nodes of a makedev table
+    Mknod {
+        /// file to create
+        #[arg(required_unless_present("template"))]
+        path: Option<PathBuf>,
+
+        /// "c" for character device, "b" for block device
+        #[arg(required_unless_present("template"), value_parser = posix::parse_dev_type)]
+        dev_type: Option<StModeType>,
+
+        #[arg(required_unless_present("template"))]
+        major: Option<u32>,
+
+        #[arg(required_unless_present("template"))]
+        minor: Option<u32>,
+
+        /// permissions, octal "0660" or symbolic "u=rw,g=rw"
+        #[arg(long, short, default_value = "0666", conflicts_with("template"))]
+        mode: String,
+
+        /// makedev table, lines of "path type major minor mode", '#' for comments
+        #[arg(long, conflicts_with_all(["path", "dev_type", "major", "minor"]))]
+        template: Option<PathBuf>,
+
+        /// WSL1 fs type, if provided ignore fs type from `--distro`
+        #[arg(long, short = 't')]
+        fs_type: Option<distro::FsType>,
+
+        /// WSL distro from registry, for unix paths and WSL1 fs type
+        #[arg(long, short)]
+        distro: Option<String>,
+    },
+    /// delete `$LX*` EA's of wslfs, like a wrong `$LXDEV`, other EA's are kept
+    ClearAttr {
+        /// file to change
+        path: PathBuf,
+
+        /// fields to delete
+        #[arg(long, value_enum, required = true, num_args = 1..)]
+        field: Vec<wslfs::WslfsField>,
+
+        /// WSL distro from registry, for unix paths
+        #[arg(long, short)]
+        distro: Option<String>,
+    },
+    /// remove all wslfs and lxfs metadata and the WSL reparse point, leave a plain NTFS file
+    Strip {
+        /// file to change
+        path: PathBuf,
+
+        /// WSL distro from registry, for unix paths
+        #[arg(long, short)]
+        distro: Option<String>,
+
+        /// do not prompt for confirmation
+        #[arg(long, short)]
+        yes: bool,
+    },
+    /// set the case sensitivity flag of a directory, WSL sets it on directories it creates, `view` shows it
+    SetCaseSensitive {
+        /// directory to change
+        path: PathBuf,
+
+        /// on or off
+        #[arg(value_parser = ["on", "off"])]
+        state: String,
+
+        /// WSL distro from registry, for unix paths
+        #[arg(long, short)]
+        distro: Option<String>,
+    },
+    /// show or change NTFS attributes readonly, hidden, system and archive, like `attrib`,
+    /// a readonly file cannot be written by WSL whatever its mode is
+    #[command(disable_help_flag = true)]
+    WinAttr {
+        /// file to change
+        path: PathBuf,
+
+        /// "+ATTRS" to set or "-ATTRS" to clear, ATTRS of r(eadonly), h(idden), s(ystem) and a(rchive), show only if none
+        #[arg(allow_hyphen_values = true)]
+        changes: Vec<String>,
+
+        /// WSL distro from registry, for unix paths
+        #[arg(long, short)]
+        distro: Option<String>,
+
+        /// print help, `-h` is clearing the hidden attribute
+        #[arg(long, action = clap::ArgAction::Help)]
+        help: Option<bool>,
     },
     SetEa {
         /// file to change
@@ -119,6 +748,98 @@ enum Command {
     
         #[arg(long, short)]
         value: Option<String>,
+
+        /// how `--value` is read, as `set-attr`
+        #[arg(long, value_enum, default_value_t = InputFormat::Auto)]
+        input_format: InputFormat,
+    },
+    /// compare wslfs or lxfs attributes of two files, exit code is 0 if same, 1 if different, 2 on error
+    Diff {
+        /// file to compare from
+        a: PathBuf,
+
+        /// file to compare to
+        b: PathBuf,
+
+        /// WSL distro from registry, for unix paths of both files, without it each file is of its own distro
+        #[arg(long, short)]
+        distro: Option<String>,
+
+        /// output changes as a json object
+        #[arg(long)]
+        json: bool,
+    },
+    /// show EA count and size usage
+    Stat {
+        #[clap(flatten)]
+        args_view: ArgsView,
+
+        /// output as json
+        #[arg(long)]
+        json: bool,
+    },
+    /// report files whose mode or owner deviate, like world-writable or setuid files, nothing is changed,
+    /// world-writable and setuid files are reported if no rule is given
+    Audit {
+        /// directory to audit recursively
+        path: PathBuf,
+
+        /// WSL distro from registry, for unix paths
+        #[arg(long, short)]
+        distro: Option<String>,
+
+        /// files writable by others, symlinks and sticky directories like /tmp are not reported
+        #[arg(long)]
+        world_writable: bool,
+
+        /// setuid and setgid files
+        #[arg(long)]
+        setuid: bool,
+
+        /// files owned by the uid
+        #[arg(long, value_name = "UID")]
+        owned_by: Option<u32>,
+
+        /// directories with entries differing only by case, like `Makefile` and `makefile`,
+        /// which break when the directory is not case sensitive or is copied to Windows
+        #[arg(long)]
+        case_collisions: bool,
+
+        /// descend at most N directories below the path
+        #[arg(long)]
+        max_depth: Option<usize>,
+
+        /// descend into other distros and other file systems
+        #[arg(long)]
+        cross_boundaries: bool,
+
+        /// output one json object per line for each file reported
+        #[arg(long)]
+        json: bool,
+    },
+    /// print the registered distro a Windows path belongs to, and the unix path in it
+    Which {
+        /// Windows path, a local disk path or a WSL UNC path
+        path: PathBuf,
+    },
+    /// print uid, gid and groups of a user in distro
+    Id {
+        /// uid to look up
+        #[clap(conflicts_with("name"))]
+        uid: Option<u32>,
+
+        /// user name to look up
+        #[arg(long, short)]
+        name: Option<String>,
+
+        /// WSL distro from registry, for user and group name
+        #[arg(long, short)]
+        distro: String,
+    },
+    /// print shell completion script
+    #[command(hide = true)]
+    Completions {
+        shell: clap_complete::Shell,
     },
 }
 
@@ -127,25 +848,108 @@ fn main() {
     use Command::*;
 
     let args = Args::parse();
+    QUIET.store(args.quiet, Ordering::Relaxed);
+    EVENTS.store(args.events == Some(EventsFormat::Ndjson), Ordering::Relaxed);
+    wsl_file::OPEN_RETRIES.store(args.open_retries, Ordering::Relaxed);
+    wsl_file::OPEN_RETRY_DELAY.store(args.open_retry_delay, Ordering::Relaxed);
+    ea_parse::STRICT_EA_NAMES.store(args.strict, Ordering::Relaxed);
+    lxfs::TRIM_TRAILING.store(args.trim_trailing, Ordering::Relaxed);
+    json::PRETTY.store(args.json_pretty, Ordering::Relaxed);
+    FAIL_FAST.store(args.fail_fast, Ordering::Relaxed);
+    NO_DISTRO.store(args.no_distro, Ordering::Relaxed);
+    if args.output_encoding == OutputEncoding::Utf8 {
+        console::set_output_utf8();
+    }
+    if let Some(distro_override) = args.distro_root_override {
+        distro::set_override(distro_override);
+    }
+    if let Some(rootfs) = args.rootfs {
+        let offline = distro::OfflineDistro { rootfs, fs_type: args.rootfs_fs_type, passwd: args.passwd_file, group: args.group_file };
+        for warning in offline.check() {
+            warning!("[WARNING] {}", warning);
+        }
+        distro::set_offline(offline);
+    }
     //println!("args: {:?}!", args);
 
+    if args.capabilities {
+        println!("{}", json::to_output(&capabilities_json()));
+        return;
+    }
+
     if let Some(cmd) = args.command {
         match cmd {
-            View(args_view) => view(args_view),
+            View { args_view, args_view_options: ArgsViewOptions { ea_file: true, show_raw_ea, format, .. }, .. } => {
+                view_ea_file(args_view, format, show_raw_ea)
+            },
+            View { args_view, args_recursive, args_view_options } => view(args_view, args_recursive, args_view_options),
             Chown { args_change, user } => chown(args_change, user),
             Chgrp { args_change, group } => chgrp(args_change, group),
             Chmod { args_change, modes } => chmod(args_change, modes),
-            SetAttr { args_change, name, value } => set_attr(args_change, name, value),
-            RmAttr { args_change, name } => rm_attr(args_change, name),
-            Downgrade { path, distro } => {
+            SetAttr { mut args_change, name, value, input_format, from_file, create, args_allow } => {
+                let value_bytes = match (value, from_file) {
+                    (Some(value), _) => escape_utils::unescape_as(&value, input_format).unwrap_or_else(|| {
+                        error!("[ERROR] invalid value: {}", value);
+                        std::process::exit(failure_exit_code());
+                    }),
+                    (None, Some(from_file)) => match std::fs::read(&from_file) {
+                        Ok(value_bytes) => {
+                            info!("value from file: {}, {} bytes", from_file.display(), value_bytes.len());
+                            value_bytes
+                        },
+                        Err(ex) => {
+                            error!("[ERROR] cannot read value from file: {}, error: {ex:?}", from_file.display());
+                            std::process::exit(failure_exit_code());
+                        },
+                    },
+                    (None, None) => unreachable!("--value or --from-file is required by clap"),
+                };
+                let Ok(name) = check_xattr_name(&name, args_allow.allow_namespace) else {
+                    std::process::exit(failure_exit_code());
+                };
+                args_change.create = create;
+                set_attr(args_change, name, value_bytes, args_allow.allow_internal)
+            },
+            GetAttr { path, name, to_file, distro } => {
+                if let Err(()) = get_attr(&path, &wsl_file::xattr_name(&name), to_file.as_deref(), distro.as_ref()) {
+                    std::process::exit(failure_exit_code());
+                }
+            },
+            RmAttr { args_change, name, args_allow } => {
+                let Ok(name) = check_xattr_name(&name, args_allow.allow_namespace) else {
+                    std::process::exit(failure_exit_code());
+                };
+                rm_attr(args_change, name, args_allow.allow_internal)
+            },
+            SetTime { args_change, atime, mtime, ctime, reference } => set_time(args_change, atime, mtime, ctime, reference),
+            SyncTimes { args_change, to_ntfs, .. } => sync_times(args_change, to_ntfs),
+            RemapOwners { args_change, map } => remap_owners(args_change, &map),
+            Retype { args_change, set_type } => retype(args_change, set_type),
+            CopyAttr { src, mut to, to_stdin, attrs_only, no_times, fs_type, distro } => {
+                if to_stdin {
+                    to.extend(std::io::stdin().lines().map_while(Result::ok)
+                        .map(|line| line.trim().to_owned()).filter(|line| !line.is_empty()).map(PathBuf::from));
+                }
+                if let Err(()) = copy_attr(&src, &to, attrs_only, !attrs_only && !no_times, fs_type, distro) {
+                    std::process::exit(failure_exit_code());
+                }
+            },
+            Repair { args_change, dry_run } => repair(args_change, dry_run),
+            Convert { path, to, create, distro } => {
+                if let Err(()) = convert(&path, to, create, distro.as_ref()) {
+                    std::process::exit(failure_exit_code());
+                }
+            },
+            Downgrade { path, distro, yes, preserve_object_id } => {
                 if path.is_some() && distro.is_some() {
-                    println!("[ERROR] path and distro args are conflicted");
+                    error!("[ERROR] path and distro args are conflicted");
                     return;
                 }
                 if path.is_none() && distro.is_none() {
-                    println!("[ERROR] there must be one of path or distro args");
+                    error!("[ERROR] there must be one of path or distro args");
                     return;
                 }
+                refuse_distro_arg(distro.as_deref());
                 if let Some(name) = distro {
                     if let Some(mut d) = distro::try_load(&name) {
                         if d.fs_type.is_none() {
@@ -156,208 +960,2069 @@ fn main() {
                             print!("[ERROR] WSL distro: {} is LxFs already", &d.name);
                             return;
                         }
-                        downgrade_distro(&mut d);
-                    } else {
-                        println!("[ERROR] there must be one of path or distro args");
+                        if !yes {
+                            let count = distro_files(&d).count();
+                            if !confirm(&format!("downgrade {} files of WSL distro: {} in {}", count, &d.name, d.rootfs_path().display())) {
+                                info!("downgrade canceled");
+                                return;
+                            }
+                        }
+                        if let Err(()) = downgrade_distro(&mut d, preserve_object_id) {
+                            std::process::exit(failure_exit_code());
+                        }
+                    } else {
+                        error!("[ERROR] there must be one of path or distro args");
                         return;
                     }
                 } else if let Some(path) = path {
-                    open_to_view(ArgsView { path, distro: None }, |mut wsl_file, _distro, wslfs, lxfs| {
-                        downgrade(&mut wsl_file, &wslfs, &lxfs);
+                    let mut downgraded = Ok(());
+                    let result = open_to_view(ArgsView::of_path(path), |mut wsl_file, distro, wslfs, lxfs| {
+                        if let Some(d) = distro.as_ref().filter(|d| is_wsl2(Some(d))) {
+                            error!("[ERROR] WSL distro: {} is WSL2", &d.name);
+                            downgraded = Err(());
+                            return;
+                        }
+                        if let Err(ex) = downgrade(&mut wsl_file, &wslfs, &lxfs, preserve_object_id) {
+                            error!("[ERROR] downgrade {}, error: {ex:?}", unsafe { wsl_file.full_path.Buffer.display() });
+                            downgraded = Err(());
+                        }
                     });
+                    if let Err(err) = result {
+                        err.report(false);
+                        std::process::exit(failure_exit_code());
+                    }
+                    if downgraded.is_err() {
+                        std::process::exit(failure_exit_code());
+                    }
+                }
+            },
+            Mknod { path, dev_type, major, minor, mode, template, fs_type, distro } => {
+                let result = if let Some(template) = template {
+                    mknod_template(&template, fs_type, distro.as_ref())
+                } else {
+                    mknod(path.unwrap(), dev_type.unwrap(), major.unwrap(), minor.unwrap(), &mode, fs_type, distro.as_ref())
+                };
+                if let Err(()) = result {
+                    std::process::exit(failure_exit_code());
+                }
+            },
+            ClearAttr { path, field, distro } => {
+                if let Err(()) = clear_attr(&path, &field, distro.as_ref()) {
+                    std::process::exit(failure_exit_code());
+                }
+            },
+            Strip { path, distro, yes } => {
+                if let Err(()) = strip(&path, distro.as_ref(), yes) {
+                    std::process::exit(failure_exit_code());
+                }
+            },
+            SetCaseSensitive { path, state, distro } => {
+                if let Err(()) = set_case_sensitive(&path, state == "on", distro.as_ref()) {
+                    std::process::exit(failure_exit_code());
+                }
+            },
+            WinAttr { path, changes, distro, .. } => {
+                if let Err(()) = win_attr(&path, &changes, distro.as_ref()) {
+                    std::process::exit(failure_exit_code());
                 }
             },
-            SetEa { path, name, value } => {
-                let wsl_file = unsafe { open_handle(&path, true) }.unwrap();
-                let value_bytes = value.map(|v| escape_utils::unescape(&v).expect("invalid value"));
+            SetEa { path, name, value, input_format } => {
+                let value_bytes = value.map(|v| escape_utils::unescape_as(&v, input_format).unwrap_or_else(|| {
+                    error!("[ERROR] invalid value: {}", v);
+                    std::process::exit(failure_exit_code());
+                }));
+                let wsl_file = unsafe { open_handle(&path, true) }.unwrap_or_else(|source| {
+                    WslAttrError::Open { path: path.clone(), source }.report(false);
+                    std::process::exit(failure_exit_code());
+                });
                 set_ea(wsl_file.file_handle, name.as_bytes(), value_bytes.as_ref().map(|v| v.as_slice()));
             },
+            Stat { args_view, json } => stat(args_view, json),
+            Diff { a, b, distro, json } => diff(&a, &b, distro.as_ref(), json),
+            Audit { path, distro, world_writable, setuid, owned_by, case_collisions, max_depth, cross_boundaries, json } => {
+                let mut rules = posix::AuditRules { world_writable, setuid, owned_by };
+                if !world_writable && !setuid && owned_by.is_none() && !case_collisions {
+                    rules.world_writable = true;
+                    rules.setuid = true;
+                }
+                let args_recursive = ArgsRecursive { recursive: true, max_depth, cross_boundaries };
+                if let Err(()) = audit(&path, distro.as_ref(), &rules, case_collisions, &args_recursive, json) {
+                    std::process::exit(failure_exit_code());
+                }
+            },
+            Which { path } => {
+                if let Err(()) = which(&path) {
+                    std::process::exit(failure_exit_code());
+                }
+            },
+            Id { uid, name, distro } => {
+                if let Err(()) = id(&distro, uid, name.as_deref()) {
+                    std::process::exit(failure_exit_code());
+                }
+            },
+            Completions { shell } => completions(shell),
+        }
+
+    } else if let Some(args_view) = args.args_view {
+        view(args_view, ArgsRecursive::default(), ArgsViewOptions::default());
+    } else {
+        // fail
+        print!("[ERROR] argument <PATH> or command must be provided")
+    }
+
+    if ntfs_io::PERMISSION_DENIED.load(Ordering::Relaxed) {
+        std::process::exit(EXIT_PERMISSION_DENIED);
+    }
+}
+
+/// exit code if any file failed for lack of privileges, 1 for other failures
+const EXIT_PERMISSION_DENIED: i32 = 3;
+
+/// exit code of a failure
+fn failure_exit_code() -> i32 {
+    if ntfs_io::PERMISSION_DENIED.load(Ordering::Relaxed) { EXIT_PERMISSION_DENIED } else { 1 }
+}
+
+fn open_to_view(args: ArgsView, f: impl FnOnce(WslFile, Option<Distro>, WslfsParsed, LxfsParsed) -> ()) -> Result<(), WslAttrError> {
+    let distro = try_load_distro(args.distro.as_ref(), Some(&args.path), true)?;
+
+    let wsl_file = load_wsl_file(&args.path, distro.as_ref())?;
+    // files of WSL2 are served by 9P, which has no EA's
+    let ea_buffer = if is_wsl2(distro.as_ref()) {
+        None
+    } else {
+        wsl_file.read_ea().map_err(|source| WslAttrError::ReadEa { path: args.path.clone(), source })?
+    };
+
+    if ea_buffer.is_none() {
+        info!("no EAs exists");
+    }
+    
+    let ea_parsed = ea_buffer.as_ref()
+    .map(|ea_buffer| {
+        ea_parse::parse_ea(&ea_buffer)
+    });
+
+    let wslfs = wslfs::WslfsParsed::load(&wsl_file, &ea_parsed);
+
+    let lxfs = lxfs::LxfsParsed::load(&wsl_file, &ea_parsed);
+
+    f(wsl_file, distro, wslfs, lxfs);
+    Ok(())
+}
+
+/// file from `--output` or stdout, exit if the file cannot be created
+fn open_output(output: Option<&Path>) -> Box<dyn std::io::Write> {
+    if let Some(path) = output {
+        match std::fs::File::create(path) {
+            Ok(file) => Box::new(std::io::BufWriter::new(file)),
+            Err(ex) => {
+                error!("[ERROR] cannot create output file: {}, error: {ex:?}", path.display());
+                std::process::exit(failure_exit_code());
+            },
+        }
+    } else {
+        Box::new(std::io::stdout().lock())
+    }
+}
+
+fn view(args_view: ArgsView, args_recursive: ArgsRecursive, args_view_options: ArgsViewOptions) {
+    let ArgsViewOptions { json, win_acl, show_raw_ea, hex_dump, both, sync_check, resolve_symlink, dump_ea_raw, format, .. } = args_view_options;
+    // the encoding of raw EA values if `--show-raw-ea`
+    let raw_ea = show_raw_ea.then_some(format);
+    let mut out = open_output(args_view.output.as_deref());
+    let opts = fmt_options(&args_view);
+    if !args_recursive.recursive {
+        let path = args_view.path.clone();
+        let mut dumped = Ok(());
+        let result = open_to_view(args_view, |wsl_file, distro, wslfs, lxfs| {
+            if let Some(dump) = &dump_ea_raw {
+                dumped = dump_ea_raw_files(dump, &path, &wsl_file, distro.as_ref(), &wslfs, &lxfs);
+            }
+            if json {
+                writeln!(out, "{}", json::to_output(&view_json(&path, &wsl_file, &wslfs, &lxfs))).unwrap();
+            } else {
+                print_file_time(&mut out, &wsl_file, &opts).unwrap();
+                print_case_sensitive(&mut out, &wsl_file, &opts).unwrap();
+                print_win_attributes(&mut out, &wsl_file, &opts).unwrap();
+                if is_wsl2(distro.as_ref()) {
+                    writeln!(out, "{}", WSL2_NOTE).unwrap();
+                }
+
+                if both {
+                    fmt_both(&mut out, distro.as_ref(), &wslfs, &lxfs, &opts).unwrap();
+                } else {
+                    wslfs.fmt(&mut out, distro.as_ref(), &opts).unwrap();
+                    lxfs.fmt(&mut out, distro.as_ref(), &opts).unwrap();
+                }
+                if sync_check {
+                    print_time_drift(&mut out, &wsl_file, &lxfs, &opts).unwrap();
+                }
+                if resolve_symlink {
+                    if let Ok(real_path) = get_real_path(&path, distro.as_ref()) {
+                        print_symlink_resolved(&mut out, &real_path, distro.as_ref(), &wslfs, &lxfs, &opts).unwrap();
+                    }
+                }
+                if win_acl {
+                    let _ = win_acl::fmt_win_acl(&mut out, wsl_file.file_handle, &opts);
+                }
+                if let Some(format) = raw_ea.filter(|_| !is_wsl2(distro.as_ref())) {
+                    // read again, the EA's parsed are borrowed by wslfs and lxfs
+                    match wsl_file.read_ea() {
+                        Ok(ea_buffer) => {
+                            let ea_parsed = ea_buffer.as_ref().map(|ea_buffer| ea_parse::parse_ea(&ea_buffer)).unwrap_or_default();
+                            fmt_raw_ea(&mut out, wsl_file.reparse_tag, &ea_parsed, format, &opts).unwrap();
+                        },
+                        Err(ex) => error!("[ERROR] cannot read EAs, error: {ex:?}"),
+                    }
+                }
+                if (hex_dump || !wslfs.maybe() && !lxfs.maybe()) && !is_wsl2(distro.as_ref()) {
+                    match wsl_file.read_ea() {
+                        Ok(ea_buffer) => fmt_hex_dump(&mut out, &wsl_file, ea_buffer.as_deref(), hex_dump, &opts).unwrap(),
+                        Err(ex) => error!("[ERROR] cannot read EAs, error: {ex:?}"),
+                    }
+                }
+            }
+        });
+        out.flush().unwrap();
+        if let Err(err) = result {
+            err.report(json);
+            std::process::exit(failure_exit_code());
+        }
+        if dumped.is_err() {
+            std::process::exit(failure_exit_code());
+        }
+        return;
+    }
+
+    let distro = try_load_distro(args_view.distro.as_ref(), Some(&args_view.path), true).unwrap_or_else(|err| {
+        err.report(json);
+        std::process::exit(failure_exit_code());
+    });
+    let real_path = get_real_path(&args_view.path, distro.as_ref()).unwrap_or_else(|err| {
+        err.report(json);
+        std::process::exit(failure_exit_code());
+    });
+    let wsl2 = is_wsl2(distro.as_ref());
+    if wsl2 && !json {
+        writeln!(out, "{}", WSL2_NOTE).unwrap();
+    }
+    let mut failures = Failures::new();
+    walk_files(&real_path, &args_recursive, &mut failures, |path, wsl_file| {
+        let ea_buffer = if wsl2 { Ok(None) } else { wsl_file.read_ea() };
+        let ea_buffer = match ea_buffer {
+            Ok(ea_buffer) => ea_buffer,
+            Err(source) => {
+                // a json line for each failed file keeps the output valid json lines
+                emit_event(path, "view", "failed");
+                let err = WslAttrError::ReadEa { path: path.to_path_buf(), source };
+                if json {
+                    writeln!(out, "{}", json::to_output(&err.to_json())).unwrap();
+                } else {
+                    err.report(false);
+                }
+                return Err(());
+            },
+        };
+        let ea_parsed = ea_buffer.as_ref().map(|ea_buffer| ea_parse::parse_ea(&ea_buffer));
+        let wslfs = wslfs::WslfsParsed::load(&wsl_file, &ea_parsed);
+        let lxfs = lxfs::LxfsParsed::load(&wsl_file, &ea_parsed);
+
+        emit_event(path, "view", "ok");
+        if json {
+            writeln!(out, "{}", json::to_output(&view_json(path, &wsl_file, &wslfs, &lxfs))).unwrap();
+        } else {
+            writeln!(out, "{}{}", opts.label("Path:"), path.display()).unwrap();
+            print_file_time(&mut out, &wsl_file, &opts).unwrap();
+            print_case_sensitive(&mut out, &wsl_file, &opts).unwrap();
+            print_win_attributes(&mut out, &wsl_file, &opts).unwrap();
+            wslfs.fmt(&mut out, distro.as_ref(), &opts).unwrap();
+            lxfs.fmt(&mut out, distro.as_ref(), &opts).unwrap();
+            if sync_check {
+                print_time_drift(&mut out, &wsl_file, &lxfs, &opts).unwrap();
+            }
+            if resolve_symlink {
+                print_symlink_resolved(&mut out, &real_path.join(path), distro.as_ref(), &wslfs, &lxfs, &opts).unwrap();
+            }
+            if win_acl {
+                let _ = win_acl::fmt_win_acl(&mut out, wsl_file.file_handle, &opts);
+            }
+            if let Some(format) = raw_ea {
+                fmt_raw_ea(&mut out, wsl_file.reparse_tag, ea_parsed.as_deref().unwrap_or_default(), format, &opts).unwrap();
+            }
+            if (hex_dump || !wslfs.maybe() && !lxfs.maybe()) && !wsl2 {
+                fmt_hex_dump(&mut out, &wsl_file, ea_buffer.as_deref(), hex_dump, &opts).unwrap();
+            }
+            writeln!(out).unwrap();
+        }
+        Ok(())
+    });
+    out.flush().unwrap();
+    if failures.finish("view").is_err() {
+        std::process::exit(failure_exit_code());
+    }
+}
+
+/// `view --dump-ea-raw`, the raw buffers as read, `FILE.reparse` only for a file with a reparse point
+fn dump_ea_raw_files(dump: &Path, path: &Path, wsl_file: &WslFile, distro: Option<&Distro>, wslfs: &WslfsParsed, lxfs: &LxfsParsed) -> Result<(), ()> {
+    let with_ext = |ext: &str| {
+        let mut p = dump.as_os_str().to_owned();
+        p.push(ext);
+        PathBuf::from(p)
+    };
+    let write = |file: &Path, content: &[u8]| std::fs::write(file, content)
+        .map_err(|ex| error!("[ERROR] cannot write dump file: {}, error: {ex:?}", file.display()));
+
+    if is_wsl2(distro) {
+        error!("[ERROR] no EA's to dump, files of WSL2 are served by 9P");
+        return Err(());
+    }
+    let ea_buffer = wsl_file.read_ea().map_err(|ex| error!("[ERROR] cannot read EAs, error: {ex:?}"))?.unwrap_or_default();
+    write(dump, &ea_buffer)?;
+    let mut reparse_len = None;
+    if wsl_file.reparse_tag.is_some() {
+        let reparse_buffer = wsl_file.read_reparse_point().map_err(|ex| error!("[ERROR] cannot read reparse point, error: {ex:?}"))?;
+        write(&with_ext(".reparse"), &reparse_buffer)?;
+        reparse_len = Some(reparse_buffer.len());
+    }
+    let fs_type = select_fs_type(None, distro, wslfs, lxfs).ok();
+    let manifest = dump_manifest_json(path, fs_type, wsl_file.reparse_tag, wsl_file.basic_file_info.as_ref(), ea_buffer.len(), reparse_len);
+    write(&with_ext(".json"), format!("{}\n", manifest).as_bytes())?;
+    info!("EA dump: {} bytes written to {}", ea_buffer.len(), dump.display());
+    Ok(())
+}
+
+/// `{"path","fs_type","reparse_tag","ea_len","reparse_len","ntfs_times":{..},"file_attributes"}`,
+/// `fs_type` is null if it cannot be guessed, NTFS times are raw 100ns values since 1601 to be exact
+fn dump_manifest_json(path: &Path, fs_type: Option<FsType>, reparse_tag: Option<u32>, fbi: Option<&FILE_BASIC_INFORMATION>, ea_len: usize, reparse_len: Option<usize>) -> String {
+    let json_opt = |v: Option<String>| v.unwrap_or_else(|| "null".to_owned());
+    let mut out = String::from("{\"path\":\"");
+    escape_utils::escape_json_str(&path.to_string_lossy(), &mut out).unwrap();
+    out += &format!("\",\"fs_type\":{},\"reparse_tag\":{},\"ea_len\":{},\"reparse_len\":{},\"ntfs_times\":",
+        json_opt(fs_type.map(|t| format!("\"{}\"", format!("{:?}", t).to_lowercase()))),
+        json_opt(reparse_tag.map(|t| format!("\"{:#010x}\"", t))),
+        ea_len, json_opt(reparse_len.map(|l| l.to_string())));
+    if let Some(fbi) = fbi {
+        out += &format!("{{\"creation_time\":{},\"last_access_time\":{},\"last_write_time\":{},\"change_time\":{}}},\"file_attributes\":{}}}",
+            fbi.CreationTime, fbi.LastAccessTime, fbi.LastWriteTime, fbi.ChangeTime, fbi.FileAttributes);
+    } else {
+        out += "null,\"file_attributes\":null}";
+    }
+    out
+}
+
+/// the raw EA buffer of a dump file in `format`, hex and base64 may be wrapped and prefixed with `0x` or `0s`
+fn decode_ea_dump(content: &[u8], format: ValueFormat) -> Option<Vec<u8>> {
+    if format == ValueFormat::Text {
+        return Some(content.to_vec());
+    }
+    let content: String = std::str::from_utf8(content).ok()?.chars().filter(|c| !c.is_ascii_whitespace()).collect();
+    let prefix = if format == ValueFormat::Hex { "0x" } else { "0s" };
+    if content.get(..2).is_some_and(|p| p.eq_ignore_ascii_case(prefix)) {
+        escape_utils::unescape(&content)
+    } else {
+        escape_utils::unescape(&format!("{}{}", prefix, content))
+    }
+}
+
+/// `view --ea-file`, decode a raw EA dump with an in memory EA store as `view` without NTFS timestamps
+fn view_ea_file(args_view: ArgsView, format: ValueFormat, show_raw_ea: bool) {
+    let content = std::fs::read(&args_view.path).unwrap_or_else(|ex| {
+        error!("[ERROR] cannot read EA dump file: {}, error: {ex:?}", args_view.path.display());
+        std::process::exit(failure_exit_code());
+    });
+    let Some(ea_buffer) = decode_ea_dump(&content, format) else {
+        error!("[ERROR] EA dump file: {} is not valid {:?}", args_view.path.display(), format);
+        std::process::exit(failure_exit_code());
+    };
+    if !ea_buffer.is_empty() {
+        if let Err(msg) = ea_parse::check_ea_buffer(&ea_buffer) {
+            error!("[ERROR] malformed EA dump file: {}, {}", args_view.path.display(), msg);
+            std::process::exit(failure_exit_code());
+        }
+    }
+
+    let ea_store = MemEaStore::from_ea_buffer(&ea_buffer);
+    let ea_buffer = ea_store.read_ea().unwrap();
+    if ea_buffer.is_none() {
+        info!("no EAs exists");
+    }
+    let ea_parsed = ea_buffer.as_ref().map(|ea_buffer| ea_parse::parse_ea(&ea_buffer));
+    let wslfs = wslfs::WslfsParsed::load(&ea_store, &ea_parsed);
+    let lxfs = lxfs::LxfsParsed::load(&ea_store, &ea_parsed);
+
+    refuse_distro_arg(args_view.distro.as_deref());
+    // no distro from the path of a dump file
+    let distro = args_view.distro.as_ref().and_then(distro::try_load);
+    let mut out = open_output(args_view.output.as_deref());
+    let opts = fmt_options(&args_view);
+    wslfs.fmt(&mut out, distro.as_ref(), &opts).unwrap();
+    lxfs.fmt(&mut out, distro.as_ref(), &opts).unwrap();
+    if show_raw_ea {
+        fmt_raw_ea(&mut out, None, ea_parsed.as_deref().unwrap_or_default(), format, &opts).unwrap();
+    }
+    out.flush().unwrap();
+}
+
+/// bytes of the EA buffer and of the reparse buffer shown by `view --hex-dump`
+const HEX_DUMP_MAX: usize = 1024;
+
+/// `view --both`, the wslfs and the lxfs interpretations of one file side by side, their differences,
+/// and which one WSL reads as the `Version` of the distro in registry
+fn fmt_both(f: &mut dyn std::io::Write, distro: Option<&Distro>, wslfs: &WslfsParsed, lxfs: &LxfsParsed, opts: &FmtOptions) -> std::io::Result<()> {
+    let mut columns = [vec![], vec![]];
+    wslfs.fmt(&mut columns[0], distro, opts)?;
+    lxfs.fmt(&mut columns[1], distro, opts)?;
+    let [wslfs_text, lxfs_text] = columns.map(|c| String::from_utf8_lossy(&c).into_owned());
+    f.write_all(side_by_side(("wslfs:", &wslfs_text), ("lxfs:", &lxfs_text)).as_bytes())?;
+
+    if wslfs.maybe() && lxfs.maybe() {
+        let diff = diff::AttrsDiff::new(Some(wslfs as &dyn WslFileAttributes), Some(lxfs as &dyn WslFileAttributes));
+        if diff.is_empty() {
+            writeln!(f, "wslfs and lxfs metadata agree")?;
+        } else {
+            writeln!(f, "wslfs --> lxfs:")?;
+            diff.fmt(f, opts)?;
+        }
+    }
+
+    match distro.and_then(|d| d.fs_type.map(|fs_type| (d, fs_type))) {
+        Some((d, fs_type)) => writeln!(f, "[NOTE] distro: {} has Version: {} in registry, WSL reads the {:?} metadata and ignores the other",
+            &d.name, fs_type as u32, fs_type),
+        None if is_wsl2(distro) => Ok(()),
+        None => writeln!(f, "[NOTE] no distro loaded, use --distro <name> to know which metadata WSL reads"),
+    }
+}
+
+/// two texts in columns under their headers, ANSI colors of `--color` take no space,
+/// `none` for an empty text
+fn side_by_side(left: (&str, &str), right: (&str, &str)) -> String {
+    fn display_width(line: &str) -> usize {
+        let mut width = 0;
+        let mut in_escape = false;
+        for ch in line.chars() {
+            match ch {
+                '\x1b' => in_escape = true,
+                'm' if in_escape => in_escape = false,
+                _ if in_escape => {},
+                _ => width += 1,
+            }
+        }
+        width
+    }
+    fn lines<'a>((header, text): (&'a str, &'a str)) -> Vec<&'a str> {
+        let mut lines = vec![header];
+        lines.extend(text.lines());
+        if lines.len() == 1 {
+            lines.push("none");
+        }
+        lines
+    }
+
+    let left = lines(left);
+    let right = lines(right);
+    let width = left.iter().map(|l| display_width(l)).max().unwrap_or(0);
+    let mut out = String::new();
+    for i in 0..left.len().max(right.len()) {
+        let l = left.get(i).copied().unwrap_or("");
+        let r = right.get(i).copied().unwrap_or("");
+        out += format!("{}{} | {}", l, " ".repeat(width - display_width(l)), r).trim_end();
+        out.push('\n');
+    }
+    out
+}
+
+/// the EA buffer and the reparse buffer of a file not recognized as wslfs nor lxfs, or of any file if `always`,
+/// nothing for a file without EA's nor reparse point unless `always`
+fn fmt_hex_dump(f: &mut dyn std::io::Write, wsl_file: &WslFile, ea_buffer: Option<&[u8]>, always: bool, opts: &FmtOptions) -> std::io::Result<()> {
+    if !always && (opts.only_times || ea_buffer.is_none() && wsl_file.reparse_tag.is_none()) {
+        return Ok(());
+    }
+    let mut dump = |label: &str, bytes: &[u8]| -> std::io::Result<()> {
+        let mut out = String::new();
+        escape_utils::hex_dump(bytes, HEX_DUMP_MAX, &mut out).unwrap();
+        writeln!(f, "{}{} bytes", opts.label(label), bytes.len())?;
+        write!(f, "{}", out)
+    };
+    dump("EA Buffer:", ea_buffer.unwrap_or_default())?;
+    if wsl_file.reparse_tag.is_some() {
+        match wsl_file.read_reparse_point() {
+            Ok(reparse_buffer) => dump("Reparse Buffer:", &reparse_buffer)?,
+            Err(ex) => writeln!(f, "[ERROR] cannot read reparse point, error: {ex:?}")?,
+        }
+    }
+    Ok(())
+}
+
+/// all EA's as on disk and the reparse tag, for `view --show-raw-ea`
+fn fmt_raw_ea(f: &mut dyn std::io::Write, reparse_tag: Option<u32>, ea_parsed: &[EaEntry<&[u8]>], format: ValueFormat, opts: &FmtOptions) -> std::io::Result<()> {
+    use wslfs::WslfsReparseTag;
+
+    match reparse_tag {
+        Some(t) => writeln!(f, "{}{:#010x} / {}", opts.label("Raw Reparse Tag:"), t, StModeType::from_tag_id(t).name().0)?,
+        None => writeln!(f, "{}{}", opts.label("Raw Reparse Tag:"), "none")?,
+    }
+    writeln!(f, "{}{}", opts.label("Raw EAs:"), ea_parsed.len())?;
+    for ea in ea_parsed.iter().filter(|ea| opts.shows_name(&String::from_utf8_lossy(ea.name))) {
+        let mut value = String::new();
+        escape_utils::escape_bytes(ea.value, format, &mut value).unwrap();
+        if ea.need_ea() {
+            value += " (FILE_NEED_EA)";
+        }
+        writeln!(f, "{}{}", opts.label(&format!("  {}", String::from_utf8_lossy(ea.name))), value)?;
+    }
+    Ok(())
+}
+
+/// one line json object of a file for `view --json`
+fn view_json(path: &Path, wsl_file: &WslFile, wslfs: &WslfsParsed, lxfs: &LxfsParsed) -> String {
+    fn json_u32(v: Option<u32>) -> String {
+        v.map_or("null".to_owned(), |v| v.to_string())
+    }
+
+    fn attrs_json(wsl_attrs: &dyn WslFileAttributes, symlink: Option<&str>, out: &mut String) {
+        if !wsl_attrs.maybe() {
+            *out += "null";
+            return;
+        }
+        *out += &format!("{{\"uid\":{},\"gid\":{},\"mode\":{},\"dev_major\":{},\"dev_minor\":{},\"symlink\":",
+            json_u32(wsl_attrs.get_uid()), json_u32(wsl_attrs.get_gid()), json_u32(wsl_attrs.get_mode().map(u32::from)),
+            json_u32(wsl_attrs.get_dev_major()), json_u32(wsl_attrs.get_dev_minor()));
+        if let Some(symlink) = symlink {
+            *out += "\"";
+            escape_utils::escape_json_str(symlink, &mut *out).unwrap();
+            *out += "\"";
+        } else {
+            *out += "null";
+        }
+        *out += "}";
+    }
+
+    let mut json_out = String::new();
+    json_out += "{\"path\":\"";
+    escape_utils::escape_json_str(&path.to_string_lossy(), &mut json_out).unwrap();
+    json_out += &format!("\",\"reparse_tag\":{},\"file_attributes\":{},\"wslfs\":",
+        json_u32(wsl_file.reparse_tag), json_u32(wsl_file.basic_file_info.map(|fbi| fbi.FileAttributes)));
+    attrs_json(wslfs, wslfs.symlink.as_deref(), &mut json_out);
+    json_out += ",\"lxfs\":";
+    attrs_json(lxfs, lxfs.symlink.as_deref(), &mut json_out);
+    json_out += ",\"times\":";
+    times_json(wsl_file.basic_file_info.as_ref(), lxfs.get_times(), &mut json_out);
+    json_out += "}";
+    json_out
+}
+
+/// `{"ntfs":{"creation_time","last_access_time","last_write_time","change_time"},"lxattrb":{"atime","mtime","ctime"}}`
+/// as `<seconds>.<nanoseconds>` strings, NTFS times are of 100ns, LXATTRB times are exact; a missing source is null
+fn times_json(fbi: Option<&FILE_BASIC_INFORMATION>, lxfs_times: Option<(LxfsTime, LxfsTime, LxfsTime)>, out: &mut String) {
+    fn fields(times: &[(&str, LxfsTime)], out: &mut String) {
+        let fields: Vec<String> = times.iter().map(|(name, t)| format!("\"{}\":\"{}\"", name, t.to_epoch_string())).collect();
+        *out += &format!("{{{}}}", fields.join(","));
+    }
+
+    *out += "{\"ntfs\":";
+    if let Some(fbi) = fbi {
+        let ntfs_time = |t: i64| LxfsTime::from(t as u64);
+        fields(&[
+            ("creation_time", ntfs_time(fbi.CreationTime)),
+            ("last_access_time", ntfs_time(fbi.LastAccessTime)),
+            ("last_write_time", ntfs_time(fbi.LastWriteTime)),
+            ("change_time", ntfs_time(fbi.ChangeTime)),
+        ], out);
+    } else {
+        *out += "null";
+    }
+    *out += ",\"lxattrb\":";
+    if let Some((atime, mtime, ctime)) = lxfs_times {
+        fields(&[("atime", atime), ("mtime", mtime), ("ctime", ctime)], out);
+    } else {
+        *out += "null";
+    }
+    out.push('}');
+}
+
+/// what `open_to_change` did to a file
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Change {
+    Written,
+    /// the new value equals the current one, nothing written
+    Unchanged,
+    /// skipped by `--modified-since`, `--uid` or `--gid`
+    Filtered,
+    /// answered `n` or `q` to `--confirm-each`
+    Declined,
+    Failed,
+}
+
+impl Change {
+    /// `result` of `--events`
+    fn event_result(&self) -> &'static str {
+        match self {
+            Change::Written => "ok",
+            Change::Unchanged => "unchanged",
+            Change::Filtered => "skipped",
+            Change::Declined => "declined",
+            Change::Failed => "failed",
+        }
+    }
+}
+
+/// `action` names the command in `--events`
+fn open_to_change(args: ArgsChange, action: &str, f: impl Fn(&mut WslFile, Option<&Distro>, &mut dyn WslFileAttributes ) -> Change) {
+    if args.porcelain {
+        PORCELAIN.store(true, Ordering::Relaxed);
+    }
+    let distro = try_load_distro(args.distro.as_ref(), Some(&args.path), true).unwrap_or_else(|err| {
+        err.report(false);
+        std::process::exit(failure_exit_code());
+    });
+    if let Some(d) = distro.as_ref().filter(|d| is_wsl2(Some(d))) {
+        error!("[ERROR] distro: {} is WSL2, attributes of its files cannot be changed", &d.name);
+        std::process::exit(failure_exit_code());
+    }
+
+    if let (Some(fs_type), Some(d)) = (args.fs_type, distro.as_ref()) {
+        if !args.force && d.fs_type.is_some_and(|t| t != fs_type) {
+            warning!("[WARNING] fs_type: {:?} from arg --fs_type disagrees with fs_type: {:?} of distro: {} in registry, \
+                WSL reads files as the registry Version says, not as the metadata written, use --force to suppress this warning",
+                fs_type, d.fs_type.unwrap(), &d.name);
+        }
+    }
+
+    if !args.args_recursive.recursive {
+        match load_wsl_file(&args.path, distro.as_ref()) {
+            Ok(mut wsl_file) => {
+                let change = change_file(&args, &args.path, action, &mut wsl_file, distro.as_ref(), None, &f);
+                emit_event(&args.path, action, change.event_result());
+                if change == Change::Failed {
+                    std::process::exit(failure_exit_code());
+                }
+            },
+            Err(err) => {
+                err.report(false);
+                emit_event(&args.path, action, Change::Failed.event_result());
+                std::process::exit(failure_exit_code());
+            },
+        }
+        return;
+    }
+
+    let real_path = get_real_path(&args.path, distro.as_ref()).unwrap_or_else(|err| {
+        err.report(false);
+        std::process::exit(failure_exit_code());
+    });
+    if args.preserve_root() && distro.as_ref().is_some_and(|d| is_distro_root(&real_path, d)) {
+        error!("[ERROR] {} is the root of distro: {}, changing all its files recursively may break the distro, \
+            use --no-preserve-root to override", args.path.display(), &distro.as_ref().unwrap().name);
+        std::process::exit(failure_exit_code());
+    }
+    if args.preserve_root() && distro.is_none() && NO_DISTRO.load(Ordering::Relaxed) && looks_like_rootfs(&real_path) {
+        error!("[ERROR] {} contains etc/passwd, it looks like the root of a distro which --no-distro cannot check, \
+            changing all its files recursively may break the distro, use --no-preserve-root to override", args.path.display());
+        std::process::exit(failure_exit_code());
+    }
+    // --confirm-each prompts for each file instead
+    if !args.yes && !args.confirm_each {
+        let count = count_files(&real_path, &args.args_recursive);
+        if !confirm(&format!("{} {} files in {}", action, count, args.path.display())) {
+            info!("{} canceled", action);
+            return;
+        }
+    }
+    let mut confirm = None;
+    if args.confirm_each {
+        if std::io::stdin().is_terminal() {
+            confirm = Some(ConfirmEach::default());
+        } else {
+            warning!("[NOTE] --confirm-each is disabled, cause stdin is not interactive");
+        }
+    }
+    let mut skipped = 0;
+    let mut unchanged = 0;
+    let mut written = 0;
+    let mut failures = Failures::new();
+    walk_files_until(&real_path, &args.args_recursive, &mut failures, |path, mut wsl_file| {
+        info!("path: {}", path.display());
+        let change = change_file(&args, path, action, &mut wsl_file, distro.as_ref(), confirm.as_mut(), &f);
+        emit_event(path, action, change.event_result());
+        match change {
+            Change::Filtered => skipped += 1,
+            Change::Unchanged => unchanged += 1,
+            Change::Declined | Change::Failed => {},
+            Change::Written => written += 1,
+        }
+        let result = if change == Change::Failed { Err(()) } else { Ok(()) };
+        if confirm.as_ref().is_some_and(|c| c.quit) {
+            ControlFlow::Break(result)
+        } else {
+            ControlFlow::Continue(result)
+        }
+    });
+    info!("{} files skipped by filter", skipped);
+    info!("{} files unchanged", unchanged);
+    if let Some(confirm) = confirm {
+        let quit = if confirm.quit { "quit, " } else { "" };
+        info!("{}: {}{} files applied, {} files skipped ({} declined, {} filtered, {} unchanged)",
+            action, quit, written, confirm.declined + skipped + unchanged, confirm.declined, skipped, unchanged);
+    }
+    if failures.finish(action).is_err() {
+        std::process::exit(failure_exit_code());
+    }
+}
+
+/// files `walk_files` would visit, for the prompt before a recursive change
+fn count_files(real_path: &Path, args: &ArgsRecursive) -> usize {
+    let real_path = normalize_path(real_path).unwrap_or(real_path.to_path_buf());
+    let other_distros = other_distros(&real_path, args);
+    let mut walker = walkdir::WalkDir::new(&real_path).same_file_system(!args.cross_boundaries);
+    if let Some(max_depth) = args.max_depth {
+        walker = walker.max_depth(max_depth);
+    }
+    let mut walker = walker.into_iter();
+    let mut count = 0;
+    while let Some(entry) = walker.next() {
+        let Ok(entry) = entry else { continue };
+        if entry.file_type().is_dir() && other_distros.iter().any(|p| p == entry.path()) {
+            walker.skip_current_dir();
+            continue;
+        }
+        if entry.depth() > 0 && entry.path_is_symlink() && entry.path().is_dir() {
+            continue;
+        }
+        count += 1;
+    }
+    count
+}
+
+/// base paths of other distros, where `walk_files` stops
+fn other_distros(real_path: &Path, args: &ArgsRecursive) -> Vec<PathBuf> {
+    // no distro boundary is known without the registry
+    if args.cross_boundaries || NO_DISTRO.load(Ordering::Relaxed) {
+        vec![]
+    } else {
+        distro::list_base_paths().into_iter().filter(|p| !real_path.starts_with(p)).collect()
+    }
+}
+
+/// `real_path` is `base_path/rootfs` of the distro, that is "/" in it
+fn is_distro_root(real_path: &Path, distro: &Distro) -> bool {
+    let rootfs = distro.rootfs_path();
+    normalize_path(real_path).unwrap_or(real_path.to_path_buf()) == normalize_path(&rootfs).unwrap_or(rootfs)
+}
+
+/// a directory containing `etc/passwd`, the root of a distro without one loaded
+fn looks_like_rootfs(real_path: &Path) -> bool {
+    real_path.join("etc").join("passwd").is_file()
+}
+
+/// failed files of a recursive or batch run, `--fail-fast` or `--skip-errors` decides whether the run goes on
+#[derive(Debug, Default)]
+struct Failures {
+    fail_fast: bool,
+    count: usize,
+}
+
+impl Failures {
+    fn new() -> Self {
+        Failures { fail_fast: FAIL_FAST.load(Ordering::Relaxed), count: 0 }
+    }
+
+    /// counts a failed file, `true` if the run should stop
+    fn fail(&mut self) -> bool {
+        self.count += 1;
+        self.fail_fast
+    }
+
+    /// `true` if the run should stop
+    fn stopped(&self) -> bool {
+        self.fail_fast && self.count > 0
+    }
+
+    /// reports the total, `Err` if any file failed
+    fn finish(&self, action: &str) -> Result<(), ()> {
+        if self.count == 0 {
+            return Ok(());
+        }
+        if self.fail_fast {
+            error!("[ERROR] {}: stopped at the first failed file, cause --fail-fast", action);
+        } else {
+            error!("[ERROR] {}: {} files failed", action, self.count);
+        }
+        Err(())
+    }
+}
+
+/// walk `real_path` within `--max-depth`, stop at other distros, junctions and mount points,
+/// `f` is called with the path relative to `real_path` and the file opened to read
+/// `f` returns `Err` for a failed file, failed walks and failed opens are counted too,
+/// the walk stops at the first failure under `--fail-fast`
+fn walk_files(real_path: &Path, args: &ArgsRecursive, failures: &mut Failures, mut f: impl FnMut(&Path, WslFile) -> Result<(), ()>) {
+    walk_files_until(real_path, args, failures, |path, wsl_file| ControlFlow::Continue(f(path, wsl_file)))
+}
+
+/// `walk_files` stopped after the file `f` returns `Break` for, like `q` answered to `--confirm-each`,
+/// the result of that file is counted too
+fn walk_files_until(real_path: &Path, args: &ArgsRecursive, failures: &mut Failures, mut f: impl FnMut(&Path, WslFile) -> ControlFlow<Result<(), ()>, Result<(), ()>>) {
+    let real_path = normalize_path(real_path).unwrap_or(real_path.to_path_buf());
+    let other_distros = other_distros(&real_path, args);
+
+    let mut walker = walkdir::WalkDir::new(&real_path).same_file_system(!args.cross_boundaries);
+    if let Some(max_depth) = args.max_depth {
+        walker = walker.max_depth(max_depth);
+    }
+    let mut walker = walker.into_iter();
+    while let Some(entry) = walker.next() {
+        let entry = match entry {
+            Ok(entry) => entry,
+            Err(ex) => {
+                eprintln!("[ERROR] walk directory: {}, error: {ex:?}", real_path.display());
+                if failures.fail() {
+                    return;
+                }
+                continue;
+            },
+        };
+        if entry.file_type().is_dir() && other_distros.iter().any(|p| p == entry.path()) {
+            warning!("[NOTE] stop at another distro: {}", entry.path().display());
+            walker.skip_current_dir();
+            continue;
+        }
+        if entry.depth() > 0 && entry.path_is_symlink() && entry.path().is_dir() {
+            // walkdir does not follow links, but opening the link would change its target outside the tree
+            warning!("[NOTE] stop at junction or mount point: {}", entry.path().display());
+            continue;
+        }
+        let step = match unsafe { open_handle(entry.path(), false) } {
+            Ok(wsl_file) => {
+                let rel_path = entry.path().strip_prefix(&real_path).unwrap_or(entry.path());
+                f(if rel_path.as_os_str().is_empty() { Path::new(".") } else { rel_path }, wsl_file)
+            },
+            Err(ex) if ex.raw_os_error().is_some_and(|e| wsl_file::is_open_transient_error(NTSTATUS(e))) => {
+                warning!("[WARNING] skip locked file: {}, error: {ex:?}", entry.path().display());
+                ControlFlow::Continue(Ok(()))
+            },
+            Err(ex) => {
+                eprintln!("[ERROR] open file: {}, error: {ex:?}", entry.path().display());
+                ControlFlow::Continue(Err(()))
+            },
+        };
+        let (ControlFlow::Continue(result) | ControlFlow::Break(result)) = step;
+        if result.is_err() && failures.fail() {
+            return;
+        }
+        if step.is_break() {
+            return;
+        }
+    }
+}
+
+/// `(field, value)` compared by `--porcelain`, `-` is not set
+fn porcelain_fields(wsl_attrs: &dyn WslFileAttributes) -> Vec<(String, String)> {
+    let not_set = || "-".to_owned();
+    let mut fields = vec![
+        ("mode".to_owned(), wsl_attrs.get_mode().map_or_else(not_set, |m| format!("{:07o}", m.0))),
+        ("uid".to_owned(), wsl_attrs.get_uid().map_or_else(not_set, |v| v.to_string())),
+        ("gid".to_owned(), wsl_attrs.get_gid().map_or_else(not_set, |v| v.to_string())),
+    ];
+    for (name, value) in wsl_attrs.list_attrs() {
+        let mut field = String::from("attr:");
+        escape_utils::escape_bytes_octal(name.as_bytes(), &mut field, true).unwrap();
+        let mut escaped = String::new();
+        escape_utils::escape_value_display(value, 0, &mut escaped).unwrap();
+        fields.push((field, escaped));
+    }
+    fields
+}
+
+/// `ACTION\tPATH\tFS_TYPE\tFIELD\tOLD\tNEW` for each field changed, fields removed are listed last
+fn porcelain_lines(action: &str, path: &Path, fs_type: FsType, before: &[(String, String)], after: &[(String, String)]) -> Vec<String> {
+    let mut escaped_path = String::new();
+    escape_utils::escape_bytes_octal(path.to_string_lossy().as_bytes(), &mut escaped_path, true).unwrap();
+    let fs_type = format!("{:?}", fs_type).to_lowercase();
+    changed_fields(before, after).into_iter()
+        .map(|(field, old, new)| format!("{}\t{}\t{}\t{}\t{}\t{}", action, escaped_path, fs_type, field, old, new))
+        .collect()
+}
+
+/// `(field, old, new)` of `porcelain_fields` changed, fields removed are listed last
+fn changed_fields(before: &[(String, String)], after: &[(String, String)]) -> Vec<(String, String, String)> {
+    let value_of = |fields: &[(String, String)], field: &str| fields.iter().find(|(f, _)| f == field).map(|(_, v)| v.clone());
+
+    let mut changes = vec![];
+    for (field, new) in after {
+        let old = value_of(before, field).unwrap_or_else(|| "-".to_owned());
+        if &old != new {
+            changes.push((field.clone(), old, new.clone()));
+        }
+    }
+    for (field, old) in before {
+        if value_of(after, field).is_none() {
+            changes.push((field.clone(), old.clone(), "-".to_owned()));
+        }
+    }
+    changes
+}
+
+/// state of `--confirm-each` through a recursive run
+#[derive(Debug, Default)]
+struct ConfirmEach {
+    /// `a` answered, the rest is applied without prompting
+    all: bool,
+    /// `q` answered, the run stops after the current file
+    quit: bool,
+    declined: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ConfirmAnswer {
+    Yes,
+    No,
+    All,
+    Quit,
+}
+
+impl ConfirmAnswer {
+    /// `None` for an answer to ask again
+    fn parse(answer: &str) -> Option<Self> {
+        match answer.trim().to_ascii_lowercase().as_str() {
+            "y" | "yes" => Some(ConfirmAnswer::Yes),
+            "n" | "no" => Some(ConfirmAnswer::No),
+            "a" | "all" => Some(ConfirmAnswer::All),
+            "q" | "quit" => Some(ConfirmAnswer::Quit),
+            _ => None,
+        }
+    }
+}
+
+impl ConfirmEach {
+    /// show `changes` of `path` and read the answer, end of input quits
+    fn ask(&mut self, action: &str, path: &Path, changes: &[(String, String, String)]) -> ConfirmAnswer {
+        use std::io::stdin;
+
+        println!("{} {}:", action, path.display());
+        for (field, old, new) in changes {
+            println!("  {:<24}{} --> {}", format!("{}:", field), old, new);
+        }
+        let answer = loop {
+            print!("apply? [y/n/a/q] ");
+            let _ = std::io::stdout().flush();
+            let mut line = String::new();
+            match stdin().read_line(&mut line) {
+                Ok(0) | Err(_) => break ConfirmAnswer::Quit,
+                Ok(_) => if let Some(answer) = ConfirmAnswer::parse(&line) {
+                    break answer;
+                },
+            }
+        };
+        match answer {
+            ConfirmAnswer::Yes => {},
+            ConfirmAnswer::All => self.all = true,
+            ConfirmAnswer::No => self.declined += 1,
+            ConfirmAnswer::Quit => {
+                self.declined += 1;
+                self.quit = true;
+            },
+        }
+        answer
+    }
+}
+
+/// change one file, file times are not touched if nothing changed,
+/// with `confirm` the change is previewed and written only if answered `y` or `a`
+fn change_file(args: &ArgsChange, path: &Path, action: &str, wsl_file: &mut WslFile, distro: Option<&Distro>, confirm: Option<&mut ConfirmEach>, f: &impl Fn(&mut WslFile, Option<&Distro>, &mut dyn WslFileAttributes ) -> Change) -> Change {
+    if let Some(since) = args.modified_since {
+        let last_write_time = wsl_file.basic_file_info.map(|fbi| LxfsTime::from(fbi.LastWriteTime as u64));
+        if !last_write_time.is_some_and(|t| t >= since) {
+            return Change::Filtered;
+        }
+    }
+
+    // a failed file of a recursive run is counted, not the end of the run
+    let ea_buffer = match wsl_file.read_ea() {
+        Ok(ea_buffer) => ea_buffer,
+        Err(ex) => {
+            error!("[ERROR] cannot read EAs of {}, error: {ex:?}", path.display());
+            return Change::Failed;
+        },
+    };
+
+    if ea_buffer.is_none() {
+        info!("no EAs exists");
+    }
+    
+    let ea_parsed = ea_buffer.as_ref()
+    .map(|ea_buffer| {
+        ea_parse::parse_ea(&ea_buffer)
+    });
+
+    let mut wslfs = wslfs::WslfsParsed::load(wsl_file, &ea_parsed);
+
+    let mut lxfs = lxfs::LxfsParsed::load(wsl_file, &ea_parsed);
+    lxfs.ctime_update = !args.no_ctime_update;
+
+    let fs_type = match (select_fs_type(args.fs_type, distro, &wslfs, &lxfs), args.create, args.assume) {
+        (Err(_), Some(create), _) if !wslfs.maybe() && !lxfs.maybe() => {
+            info!("use fs_type: {:?} from arg --create", create);
+            Ok(create)
+        },
+        (Err(msg), _, Some(assume)) => {
+            info!("use fs_type: {:?} from arg --assume, {}", assume, msg);
+            Ok(assume)
+        },
+        (fs_type, _, _) => fs_type,
+    };
+    let wsl_attrs: &mut dyn WslFileAttributes = match fs_type {
+        Ok(FsType::Lxfs) => &mut lxfs,
+        Ok(FsType::Wslfs) => &mut wslfs,
+        Err(msg) => {
+            error!("[ERROR] {}", msg);
+            return Change::Failed;
+        },
+    };
+
+    if args.uid.is_some_and(|uid| wsl_attrs.get_uid() != Some(uid))
+    || args.gid.is_some_and(|gid| wsl_attrs.get_gid() != Some(gid)) {
+        let owner = |id: Option<u32>| id.map_or("none".to_owned(), |id| id.to_string());
+        let note = format!("skipped: {} owned by uid {} gid {}, not matching --uid or --gid", path.display(), owner(wsl_attrs.get_uid()), owner(wsl_attrs.get_gid()));
+        if args.args_recursive.recursive {
+            info!("{}", note);
+        } else {
+            // a single file skipped silently looks like a success
+            warning!("[NOTE] {}", note);
+        }
+        return Change::Filtered;
+    }
+
+    if let Err(ex) = wsl_file.reopen_to_write() {
+        error!("[ERROR] cannot open file to write, error: {ex:?}");
+        return Change::Failed;
+    }
+    if let Some(confirm) = confirm.filter(|c| !c.all) {
+        // run `f` on another copy of the attributes with writes dropped, to get the values proposed
+        let mut preview_wslfs = wslfs::WslfsParsed::load(wsl_file, &ea_parsed);
+        let mut preview_lxfs = lxfs::LxfsParsed::load(wsl_file, &ea_parsed);
+        preview_lxfs.ctime_update = !args.no_ctime_update;
+        let preview_attrs: &mut dyn WslFileAttributes = match wsl_attrs.fs_type() {
+            FsType::Lxfs => &mut preview_lxfs,
+            FsType::Wslfs => &mut preview_wslfs,
+        };
+        let (fbi, quiet) = (wsl_file.basic_file_info, QUIET.swap(true, Ordering::Relaxed));
+        wsl_file.preview = true;
+        let preview = f(wsl_file, distro, preview_attrs);
+        wsl_file.preview = false;
+        wsl_file.basic_file_info = fbi;
+        QUIET.store(quiet, Ordering::Relaxed);
+        if preview != Change::Written {
+            return preview;
+        }
+
+        let changes = changed_fields(&porcelain_fields(wsl_attrs), &porcelain_fields(preview_attrs));
+        if matches!(confirm.ask(action, path, &changes), ConfirmAnswer::No | ConfirmAnswer::Quit) {
+            return Change::Declined;
+        }
+    }
+
+    let before = args.porcelain.then(|| porcelain_fields(wsl_attrs));
+    let change = f(wsl_file, distro, wsl_attrs);
+    if let (Some(before), Change::Written) = (before, change) {
+        for line in porcelain_lines(action, path, wsl_attrs.fs_type(), &before, &porcelain_fields(wsl_attrs)) {
+            println!("{}", line);
+        }
+    }
+
+    if change != Change::Unchanged && args.preserve_times() {
+        if let Err(ex) = wsl_file.restore_file_times() {
+            warning!("[WARNING] cannot restore file times, error: {ex:?}");
+        }
+    }
+    change
+}
+
+/// fs_type from `--fs-type`, from `--distro`, or from the only kind of metadata found
+fn select_fs_type(fs_type: Option<FsType>, distro: Option<&Distro>, wslfs: &WslfsParsed, lxfs: &LxfsParsed) -> Result<FsType, &'static str> {
+    if let Some(fs_type) = fs_type {
+        info!("use fs_type: {:?} from arg --fs_type", fs_type);
+        Ok(fs_type)
+    } else if let Some(d) = distro.filter(|d| d.source == DistroSource::Arg && d.fs_type.is_some()) {
+        let fs_type = d.fs_type.unwrap();
+        info!("use fs_type: {:?} from arg --distro {}", fs_type, &d.name);
+        Ok(fs_type)
+    } else if wslfs.maybe() && lxfs.maybe() {
+        Err("cannot determine fs_type, cause both wslfs and lxfs metadata exist")
+    } else if wslfs.maybe() {
+        Ok(FsType::Wslfs)
+    } else if lxfs.maybe() {
+        Ok(FsType::Lxfs)
+    } else {
+        Err("cannot determine fs_type, cause no wslfs nor lxfs metadata exists")
+    }
+}
+
+fn chown(args: ArgsChange, user: String) {
+    let force = args.force;
+    open_to_change(args, "chown", |wsl_file, distro, wsl_attrs| {
+        let uid = if let Ok(uid) = u32::from_str_radix(&user, 10) {
+            uid
+        } else if let Some(uid) = distro.and_then(|d| d.uid(&user)) {
+            uid
+        } else {
+            owner_name_not_found("user", &user, distro);
+            return Change::Failed;
+        };
+
+        let olduid = wsl_attrs.get_uid();
+        if !force && olduid == Some(uid) {
+            info!("chown for {:?} {}: no change", wsl_attrs.fs_type(), uid);
+            return Change::Unchanged;
+        }
+
+        wsl_attrs.set_uid(uid);
+        if let Err(ex) = wsl_attrs.save(wsl_file) {
+            error!("[ERROR] chown for {:?} {:?} --> {}, error: {ex:?}", wsl_attrs.fs_type(), olduid, uid);
+            Change::Failed
+        } else {
+            info!("chown for {:?} {:?} --> {}", wsl_attrs.fs_type(), olduid, uid);
+            Change::Written
+        }
+    });
+}
+
+/// `kind` is "user" or "group", suggests similar names in `distro`, or the numeric form and the distros to pass by `-d`
+fn owner_name_not_found(kind: &str, name: &str, distro: Option<&Distro>) {
+    let id = if kind == "user" { "uid" } else { "gid" };
+    let Some(distro) = distro else {
+        if NO_DISTRO.load(Ordering::Relaxed) {
+            error!("[ERROR] {}: {} cannot be resolved with --no-distro, pass a numeric {} like 1000", kind, name, id);
+            return;
+        }
+        let names = distro::list_names();
+        let distros = if names.is_empty() { "no distro registered".to_owned() } else { format!("distros: {}", names.join(", ")) };
+        error!("[ERROR] {}: {} without -d <distro>, pass a numeric {} like 1000 or a distro by -d, {}", kind, name, id, distros);
+        return;
+    };
+    let similar = if kind == "user" { distro.similar_user_names(name) } else { distro.similar_group_names(name) };
+    if similar.is_empty() {
+        error!("[ERROR] no {}: {} in distro: {}, a numeric {} is accepted too", kind, name, &distro.name, id);
+    } else {
+        error!("[ERROR] no {}: {} in distro: {}, did you mean: {}", kind, name, &distro.name, similar.join(", "));
+    }
+}
+
+fn chgrp(args: ArgsChange, group: String) {
+    let force = args.force;
+    open_to_change(args, "chgrp", |wsl_file, distro, wsl_attrs| {
+        let gid = if let Ok(gid) = u32::from_str_radix(&group, 10) {
+            gid
+        } else if let Some(gid) = distro.and_then(|d| d.gid(&group)) {
+            gid
+        } else {
+            owner_name_not_found("group", &group, distro);
+            return Change::Failed;
+        };
+
+        let oldgid = wsl_attrs.get_gid();
+        if !force && oldgid == Some(gid) {
+            info!("chgrp for {:?} {}: no change", wsl_attrs.fs_type(), gid);
+            return Change::Unchanged;
+        }
+
+        wsl_attrs.set_gid(gid);
+        if let Err(ex) = wsl_attrs.save(wsl_file) {
+            error!("[ERROR] chgrp for {:?} {:?} --> {}, error: {ex:?}", wsl_attrs.fs_type(), oldgid, gid);
+            Change::Failed
+        } else {
+            info!("chgrp for {:?} {:?} --> {}", wsl_attrs.fs_type(), oldgid, gid);
+            Change::Written
+        }
+    });
+}
+
+fn remap_owners(mut args: ArgsChange, map_path: &Path) {
+    use std::cell::Cell;
+
+    let map = std::fs::read_to_string(map_path).map_err(|ex| format!("cannot read: {ex:?}"))
+        .and_then(|content| posix::parse_owner_map(&content))
+        .unwrap_or_else(|msg| {
+            error!("[ERROR] owner map: {}, {}", map_path.display(), msg);
+            std::process::exit(failure_exit_code());
+        });
+    args.args_recursive.recursive = true;
+    let uid_counts = vec![Cell::new(0usize); map.uids.len()];
+    let gid_counts = vec![Cell::new(0usize); map.gids.len()];
+
+    open_to_change(args, "remap-owners", |wsl_file, _, wsl_attrs| {
+        let (olduid, oldgid) = (wsl_attrs.get_uid(), wsl_attrs.get_gid());
+        let uid_index = olduid.and_then(|uid| map.uids.iter().position(|(old, _)| *old == uid));
+        let gid_index = oldgid.and_then(|gid| map.gids.iter().position(|(old, _)| *old == gid));
+        if uid_index.is_none() && gid_index.is_none() {
+            return Change::Unchanged;
         }
 
-    } else if let Some(args_view) = args.args_view {
-        view(args_view);
-    } else {
-        // fail
-        print!("[ERROR] argument <PATH> or command must be provided")
+        if let Some(i) = uid_index {
+            wsl_attrs.set_uid(map.uids[i].1);
+        }
+        if let Some(i) = gid_index {
+            wsl_attrs.set_gid(map.gids[i].1);
+        }
+        if let Err(ex) = wsl_attrs.save(wsl_file) {
+            error!("[ERROR] remap owners for {:?} {:?}:{:?}, error: {ex:?}", wsl_attrs.fs_type(), olduid, oldgid);
+            return Change::Failed;
+        }
+        info!("remap owners for {:?} {:?}:{:?} --> {:?}:{:?}", wsl_attrs.fs_type(), olduid, oldgid, wsl_attrs.get_uid(), wsl_attrs.get_gid());
+        if let Some(i) = uid_index {
+            uid_counts[i].set(uid_counts[i].get() + 1);
+        }
+        if let Some(i) = gid_index {
+            gid_counts[i].set(gid_counts[i].get() + 1);
+        }
+        Change::Written
+    });
+
+    for ((old, new), count) in map.uids.iter().zip(&uid_counts) {
+        info!("uid {} --> {}: {} files", old, new, count.get());
+    }
+    for ((old, new), count) in map.gids.iter().zip(&gid_counts) {
+        info!("gid {} --> {}: {} files", old, new, count.get());
     }
 }
 
-fn open_to_view(args: ArgsView, f: impl FnOnce(WslFile, Option<Distro>, WslfsParsed, LxfsParsed) -> ()) {
-    let distro = try_load_distro(args.distro.as_ref(), Some(&args.path));
-
-    if let Some(wsl_file) = load_wsl_file(&args.path, distro.as_ref()) {
-        let ea_buffer = wsl_file.read_ea().unwrap_or(None);
+fn chmod(args: ArgsChange, modes: String) {
+    let force = args.force;
+    open_to_change(args, "chmod", |wsl_file, _distro, wsl_attrs| {
+        let mode = wsl_attrs.get_mode().unwrap_or(wsl_file.default_mode());
+        if let Ok(newmode) = chmod_all(mode, &modes) {
+            if !force && wsl_attrs.get_mode() == Some(newmode) {
+                info!("chmod for {:?}: {:06o} / {}: no change", wsl_attrs.fs_type(), mode, mode);
+                return Change::Unchanged;
+            }
+            wsl_attrs.set_mode(newmode);
+            if let Err(ex) = wsl_attrs.save(wsl_file) {
+                error!("[ERROR] chmod for {:?}: {:06o} / {} --> {:06o} / {}, error: {ex:?}", wsl_attrs.fs_type(), mode, mode, newmode, newmode);
+                Change::Failed
+            } else {
+                info!("chmod for {:?}: {:06o} / {} --> {:06o} / {}", wsl_attrs.fs_type(), mode, mode, newmode, newmode);
+                Change::Written
+            }
+        } else {
+            error!("[ERROR] invalid mode: {}", modes);
+            Change::Failed
+        }
+    });
+}
 
-        if ea_buffer.is_none() {
-            println!("no EAs exists");
+fn set_attr(args: ArgsChange, name: String, value_bytes: Vec<u8>, allow_internal: bool) {
+    let force = args.force;
+    let create = args.create;
+    open_to_change(args, "set-attr", |wsl_file, _distro, wsl_attrs| {
+        if let Some(change) = refuse_internal_attr(&name, allow_internal, wsl_attrs) {
+            return change;
         }
-        
-        let ea_parsed = ea_buffer.as_ref()
-        .map(|ea_buffer| {
-            ea_parse::parse_ea(&ea_buffer)
-        });
+        if !force && wsl_attrs.get_attr(&name) == Some(value_bytes.as_slice()) {
+            info!("set_attr for {:?}: no change", wsl_attrs.fs_type());
+            return Change::Unchanged;
+        }
+        if wsl_attrs.fs_type() == FsType::Lxfs && wsl_attrs.get_mode().is_none() {
+            if create == Some(FsType::Lxfs) {
+                wsl_attrs.init_default();
+                info!("create default LXATTRB");
+            } else {
+                warning!("[WARNING] no LXATTRB, a lone LXXATTR may confuse WSL, use --create lxfs to create a default LXATTRB");
+            }
+        }
+        wsl_attrs.set_attr(&name, &value_bytes);
+        if let Err(ex) = wsl_attrs.save(wsl_file) {
+            error!("[ERROR] set_attr for {:?}, error: {ex:?}", wsl_attrs.fs_type());
+            Change::Failed
+        } else {
+            info!("set_attr for {:?}", wsl_attrs.fs_type());
+            Change::Written
+        }
+    });
+}
 
-        let wslfs = wslfs::WslfsParsed::load(&wsl_file, &ea_parsed);
-    
-        let lxfs = lxfs::LxfsParsed::load(&wsl_file, &ea_parsed);
+/// `Err` if the file has no such attribute
+fn get_attr(path: &Path, name: &str, to_file: Option<&Path>, distro_name: Option<&String>) -> Result<(), ()> {
+    let distro = try_load_distro(distro_name, Some(path), false).map_err(|err| err.report(false))?;
+    let wsl_file = load_wsl_file(path, distro.as_ref()).map_err(|err| err.report(false))?;
+    let ea_buffer = wsl_file.read_ea().map_err(|source| WslAttrError::ReadEa { path: path.to_path_buf(), source }.report(false))?;
+    let ea_parsed = ea_buffer.as_ref().map(|ea_buffer| ea_parse::parse_ea(&ea_buffer));
+    let wslfs = WslfsParsed::load(&wsl_file, &ea_parsed);
+    let lxfs = LxfsParsed::load(&wsl_file, &ea_parsed);
+    let wsl_attrs: &dyn WslFileAttributes = match select_fs_type(None, distro.as_ref(), &wslfs, &lxfs) {
+        Ok(FsType::Wslfs) => &wslfs,
+        Ok(FsType::Lxfs) => &lxfs,
+        Err(msg) => {
+            error!("[ERROR] {}", msg);
+            return Err(());
+        },
+    };
+    let Some(value) = wsl_attrs.get_attr(name) else {
+        error!("[ERROR] no attribute: {} of {:?}", name, wsl_attrs.fs_type());
+        return Err(());
+    };
 
-        f(wsl_file, distro, wslfs, lxfs)
+    if let Some(to_file) = to_file {
+        std::fs::write(to_file, value).map_err(|ex| error!("[ERROR] cannot write value to file: {}, error: {ex:?}", to_file.display()))?;
+        info!("value of {} written to file: {}, {} bytes", name, to_file.display(), value.len());
     } else {
-        println!("[ERROR] load file failed");
+        let mut escaped = String::new();
+        escape_utils::escape_bytes_octal(value, &mut escaped, true).unwrap();
+        println!("\"{}\"", escaped);
     }
+    Ok(())
 }
 
-fn view(args_view: ArgsView) {
-    open_to_view(args_view, |wsl_file, distro, wslfs, lxfs| {        
-        print_file_time(&wsl_file);
+fn set_time(args: ArgsChange, atime: Option<LxfsTime>, mtime: Option<LxfsTime>, ctime: Option<LxfsTime>, reference: Option<PathBuf>) {
+    let (creation_time, atime, mtime, ctime) = if let Some(reference) = reference {
+        let Some([creation_time, atime, mtime, ctime]) = reference_times(&reference, args.distro.as_ref()) else {
+            return;
+        };
+        (Some(creation_time), Some(atime), Some(mtime), Some(ctime))
+    } else {
+        (None, atime, mtime, ctime)
+    };
+
+    open_to_change(args, "set-time", |wsl_file, _distro, wsl_attrs| {
+        wsl_attrs.set_times(atime, mtime, ctime);
+        if let Err(ex) = wsl_attrs.save(wsl_file) {
+            error!("[ERROR] set_time for {:?}, error: {ex:?}", wsl_attrs.fs_type());
+            return Change::Failed;
+        }
 
-        wslfs.fmt(&mut std::io::stdout().lock(), distro.as_ref()).unwrap();
-        lxfs.fmt(&mut std::io::stdout().lock(), distro.as_ref()).unwrap();
+        if let Err(ex) = set_file_times(wsl_file, [creation_time, atime, mtime, ctime]) {
+            error!("[ERROR] set_time for {:?}, error: {ex:?}", wsl_attrs.fs_type());
+            Change::Failed
+        } else {
+            info!("set_time for {:?}", wsl_attrs.fs_type());
+            Change::Written
+        }
     });
 }
 
-fn open_to_change(args: ArgsChange, f: impl FnOnce(WslFile, Option<Distro>, &mut dyn WslFileAttributes ) -> ()) {
-    let distro = try_load_distro(args.distro.as_ref(), Some(&args.path));
-
-    if let Some(mut wsl_file) = load_wsl_file(&args.path, distro.as_ref()) {
-        let ea_buffer = wsl_file.read_ea().unwrap_or(None);
+/// set NTFS creation time, atime, mtime and ctime, `None` keeps the time
+fn set_file_times(wsl_file: &mut WslFile, times: [Option<LxfsTime>; 4]) -> std::io::Result<()> {
+    let Some(fbi) = wsl_file.basic_file_info.as_mut() else {
+        return Err(std::io::Error::other("cannot query file times"));
+    };
+    let set = |ft: &mut i64, t: Option<LxfsTime>| if let Some(t) = t {
+        *ft = time_utils::lxfs_time_to_u64(t) as i64;
+    };
+    let [creation_time, atime, mtime, ctime] = times;
+    set(&mut fbi.CreationTime, creation_time);
+    set(&mut fbi.LastAccessTime, atime);
+    set(&mut fbi.LastWriteTime, mtime);
+    set(&mut fbi.ChangeTime, ctime);
+    // written again after change if `--preserve-times`
+    wsl_file.restore_file_times()
+}
 
-        if ea_buffer.is_none() {
-            println!("no EAs exists");
+/// lxfs keeps its own times, copy them from or to the NTFS times
+fn sync_times(args: ArgsChange, to_ntfs: bool) {
+    let force = args.force;
+    open_to_change(args, "sync-times", |wsl_file, _distro, wsl_attrs| {
+        if wsl_attrs.fs_type() != FsType::Lxfs {
+            error!("[ERROR] sync_times for {:?}: only lxfs keeps its own times", wsl_attrs.fs_type());
+            return Change::Failed;
+        }
+        let (Some(fbi), Some(times)) = (wsl_file.basic_file_info, wsl_attrs.get_times()) else {
+            error!("[ERROR] sync_times: cannot query NTFS times or no LXATTRB");
+            return Change::Failed;
+        };
+        if !force && time_drift(&fbi, times, false).is_empty() {
+            info!("sync_times: no change");
+            return Change::Unchanged;
         }
-        
-        let ea_parsed = ea_buffer.as_ref()
-        .map(|ea_buffer| {
-            ea_parse::parse_ea(&ea_buffer)
-        });
 
-        let mut wslfs = wslfs::WslfsParsed::load(&wsl_file, &ea_parsed);
-    
-        let mut lxfs = lxfs::LxfsParsed::load(&wsl_file, &ea_parsed);
-
-        let wsl_attrs: &mut dyn WslFileAttributes = if let Some(fs_type) = args.fs_type {
-            println!("use fs_type: {:?} from arg --fs_type", fs_type);
-            match fs_type {
-                FsType::Lxfs => &mut lxfs,
-                FsType::Wslfs => &mut wslfs,
-            }
-        } else if let Some(d) = distro.as_ref().filter(|d| d.source == DistroSource::Arg && d.fs_type.is_some()) {
-            let fs_type = d.fs_type.unwrap();
-            println!("use fs_type: {:?} from arg --distro {}", fs_type, &d.name);
-            match fs_type {
-                FsType::Lxfs => &mut lxfs,
-                FsType::Wslfs => &mut wslfs,
-            }
-        } else if wslfs.maybe() && lxfs.maybe() {
-            println!("[ERROR] cannot determine fs_type, cause both wslfs and lxfs metadata exist");
-            return;
-        } else if wslfs.maybe() {
-            &mut wslfs
-        } else if lxfs.maybe() {
-            &mut lxfs
+        let result = if to_ntfs {
+            let (atime, mtime, ctime) = times;
+            set_file_times(wsl_file, [None, Some(atime), Some(mtime), Some(ctime)])
         } else {
-            println!("[ERROR] cannot determine fs_type, cause no wslfs nor lxfs metadata exists");
-            return;
+            let ntfs_time = |t: i64| Some(LxfsTime::from(t as u64));
+            wsl_attrs.set_times(ntfs_time(fbi.LastAccessTime), ntfs_time(fbi.LastWriteTime), ntfs_time(fbi.ChangeTime));
+            wsl_attrs.save(wsl_file)
         };
+        if let Err(ex) = result {
+            error!("[ERROR] sync_times to {}, error: {ex:?}", if to_ntfs { "NTFS" } else { "EA" });
+            Change::Failed
+        } else {
+            info!("sync_times to {}", if to_ntfs { "NTFS" } else { "EA" });
+            Change::Written
+        }
+    });
+}
 
-        wsl_file.reopen_to_write().unwrap();
-        f(wsl_file, distro, wsl_attrs)
-    } else {
-        println!("[ERROR] load file failed");
+/// `(name, NTFS time, lxfs time)` of access, modification and change times differ at the 100ns of NTFS,
+/// the access time is skipped if `hide_atime`
+fn time_drift(fbi: &FILE_BASIC_INFORMATION, lxfs_times: (LxfsTime, LxfsTime, LxfsTime), hide_atime: bool) -> Vec<(&'static str, LxfsTime, LxfsTime)> {
+    let (atime, mtime, ctime) = lxfs_times;
+    [("atime", fbi.LastAccessTime, atime), ("mtime", fbi.LastWriteTime, mtime), ("ctime", fbi.ChangeTime, ctime)].into_iter()
+        .filter(|(name, _, _)| !(hide_atime && *name == "atime"))
+        .filter(|(_, ntfs, lxfs)| *ntfs as u64 != time_utils::lxfs_time_to_u64(*lxfs))
+        .map(|(name, ntfs, lxfs)| (name, LxfsTime::from(ntfs as u64), lxfs))
+        .collect()
+}
+
+/// `view --resolve-symlink`, nothing for a file not a symlink
+fn print_symlink_resolved(f: &mut dyn std::io::Write, link: &Path, distro: Option<&Distro>, wslfs: &WslfsParsed, lxfs: &LxfsParsed, opts: &FmtOptions) -> std::io::Result<()> {
+    let Some(target) = wslfs.symlink.as_deref().or(lxfs.symlink.as_deref()) else {
+        return Ok(());
+    };
+    let Some(d) = distro.filter(|d| !is_wsl2(Some(d))) else {
+        return writeln!(f, "{}{}", opts.label("Symlink Resolved:"), "no WSL1 distro loaded, cannot resolve the target");
+    };
+    let link = normalize_path(link).unwrap_or(link.to_path_buf());
+    let rootfs = d.rootfs_path();
+    let rootfs = normalize_path(&rootfs).unwrap_or(rootfs);
+    let Some(resolved) = path_utils::resolve_symlink_target(&link, &rootfs, target) else {
+        return writeln!(f, "{}not inside the rootfs of distro: {}, cannot resolve the target", opts.label("Symlink Resolved:"), &d.name);
+    };
+    // a lxfs symlink is a regular file, a wslfs symlink is a reparse point, neither is followed by windows
+    let exists = if std::fs::symlink_metadata(&resolved).is_ok() { "exists" } else { "missing, broken symlink" };
+    writeln!(f, "{}{} ({})", opts.label("Symlink Resolved:"), resolved.display(), exists)
+}
+
+/// `view --sync-check`
+fn print_time_drift(f: &mut dyn std::io::Write, wsl_file: &WslFile, lxfs: &LxfsParsed, opts: &FmtOptions) -> std::io::Result<()> {
+    let (Some(fbi), Some(times)) = (wsl_file.basic_file_info, lxfs.get_times()) else {
+        return writeln!(f, "{}{}", opts.label("Time Sync:"), "no lxfs times, only lxfs keeps its own times");
+    };
+    let drift = time_drift(&fbi, times, opts.hide_atime);
+    if drift.is_empty() {
+        return writeln!(f, "{}{}", opts.label("Time Sync:"), "lxfs times match NTFS times");
+    }
+    for (name, ntfs, lxfs) in drift {
+        writeln!(f, "{}NTFS: {} lxfs: {}", opts.label(&format!("Time Drift {}:", name)), ntfs, lxfs)?;
     }
+    writeln!(f, "[WARNING] lxfs times drifted from NTFS times, fix by `sync-times --to-ea` or `sync-times --to-ntfs`")
 }
 
-fn chown(args: ArgsChange, user: String) {
-    open_to_change(args, |mut wsl_file, distro, wsl_attrs| {
-        let uid = if let Ok(uid) = u32::from_str_radix(&user, 10) {
-            uid
-        } else if let Some(distro) = &distro {
-            if let Some(uid) = distro.uid(&user) {
-                uid
-            } else {
-                println!("[ERROR] no user: {} in distro: {}", &user, &distro.name);
-                return;
+/// attributes of the file for `copy-attr`, owned to apply to many files
+struct AttrsSnapshot {
+    fs_type: FsType,
+    uid: Option<u32>,
+    gid: Option<u32>,
+    mode: Option<Mode>,
+    attrs: Vec<(String, Vec<u8>)>,
+}
+
+fn copy_attr(src: &Path, dsts: &[PathBuf], attrs_only: bool, times: bool, fs_type: Option<FsType>, distro_name: Option<String>) -> Result<(), ()> {
+    let snapshot = {
+        let distro = try_load_distro(distro_name.as_ref(), Some(src), false).map_err(|err| err.report(false))?;
+        let loaded = load_wsl_file(src, distro.as_ref()).and_then(|wsl_file| {
+            match wsl_file.read_ea() {
+                Ok(ea_buffer) => Ok((wsl_file, ea_buffer)),
+                Err(source) => Err(WslAttrError::ReadEa { path: src.to_path_buf(), source }),
             }
-        } else {
-            println!("[ERROR] user: {} without -d <distro>", &user);
-            return;
+        });
+        let (wsl_file, ea_buffer) = loaded.map_err(|err| err.report(false))?;
+        let ea_parsed = ea_buffer.as_ref().map(|ea_buffer| ea_parse::parse_ea(&ea_buffer));
+        let wslfs = WslfsParsed::load(&wsl_file, &ea_parsed);
+        let lxfs = LxfsParsed::load(&wsl_file, &ea_parsed);
+        let src_attrs: &dyn WslFileAttributes = match select_fs_type(None, distro.as_ref(), &wslfs, &lxfs) {
+            Ok(FsType::Lxfs) => &lxfs,
+            Ok(FsType::Wslfs) => &wslfs,
+            Err(msg) => {
+                error!("[ERROR] copy_attr from {}: {}", src.display(), msg);
+                return Err(());
+            },
         };
+        AttrsSnapshot {
+            fs_type: src_attrs.fs_type(),
+            uid: src_attrs.get_uid(),
+            gid: src_attrs.get_gid(),
+            mode: src_attrs.get_mode(),
+            attrs: src_attrs.list_attrs().into_iter().map(|(name, value)| (name, value.to_vec())).collect(),
+        }
+    };
+    let src_times = if times {
+        Some(reference_times(src, distro_name.as_ref()).ok_or(())?)
+    } else {
+        None
+    };
 
-        let olduid = wsl_attrs.get_uid();
+    let (mut copied, mut skipped) = (0, 0);
+    let mut failures = Failures::new();
+    for dst in dsts {
+        let args = ArgsChange {
+            path: dst.clone(),
+            fs_type,
+            distro: distro_name.clone(),
+            assume: None,
+            force: false,
+            preserve_times: false,
+            no_preserve_times: false,
+            no_ctime_update: false,
+            args_recursive: ArgsRecursive { recursive: false, max_depth: None, cross_boundaries: false },
+            preserve_root: false,
+            no_preserve_root: false,
+            modified_since: None,
+            uid: None,
+            gid: None,
+            porcelain: false,
+            confirm_each: false,
+            yes: false,
+            create: Some(snapshot.fs_type),
+        };
+        let loaded = try_load_distro(args.distro.as_ref(), Some(dst), false)
+            .and_then(|distro| load_wsl_file(dst, distro.as_ref()).map(|wsl_file| (distro, wsl_file)));
+        let change = match loaded {
+            Ok((distro, mut wsl_file)) => change_file(&args, dst, "copy-attrs", &mut wsl_file, distro.as_ref(), None, &|wsl_file, _distro, wsl_attrs| {
+                copy_attrs_to(&snapshot, src_times, attrs_only, wsl_file, wsl_attrs)
+            }),
+            Err(err) => {
+                err.report(false);
+                Change::Failed
+            },
+        };
+        match change {
+            Change::Written => {
+                info!("copy_attr to {}: done", dst.display());
+                copied += 1;
+            },
+            Change::Unchanged | Change::Filtered | Change::Declined => {
+                info!("copy_attr to {}: skipped", dst.display());
+                skipped += 1;
+            },
+            Change::Failed => {
+                error!("[ERROR] copy_attr to {}: failed", dst.display());
+                if failures.fail() {
+                    break;
+                }
+            },
+        }
+    }
+    info!("{} files copied, {} skipped", copied, skipped);
+    failures.finish("copy-attr")
+}
 
-        wsl_attrs.set_uid(uid);
-        if let Err(ex) = wsl_attrs.save(&mut wsl_file) {
-            println!("[ERROR] chown for {:?} {:?} --> {}, error: {ex:?}", wsl_attrs.fs_type(), olduid, uid);
-        } else {
-            println!("chown for {:?} {:?} --> {}", wsl_attrs.fs_type(), olduid, uid);
+/// extended attributes not of the source are removed, type of file is kept
+fn copy_attrs_to(snapshot: &AttrsSnapshot, times: Option<[LxfsTime; 4]>, attrs_only: bool, wsl_file: &mut WslFile, wsl_attrs: &mut dyn WslFileAttributes) -> Change {
+    if wsl_attrs.fs_type() == FsType::Lxfs && wsl_attrs.get_mode().is_none() {
+        wsl_attrs.init_default();
+    }
+    if !attrs_only {
+        if let Some(uid) = snapshot.uid {
+            wsl_attrs.set_uid(uid);
         }
-    });
+        if let Some(gid) = snapshot.gid {
+            wsl_attrs.set_gid(gid);
+        }
+        if let Some(mode) = snapshot.mode {
+            let dst_mode = wsl_attrs.get_mode().unwrap_or(wsl_file.default_mode());
+            wsl_attrs.set_mode(dst_mode.with_perms(mode.perms()));
+        }
+    }
+    let names: Vec<String> = wsl_attrs.list_attrs().into_iter().map(|(name, _)| name).collect();
+    for name in names.iter().filter(|name| !snapshot.attrs.iter().any(|(n, _)| n == *name)) {
+        wsl_attrs.rm_attr(name);
+    }
+    for (name, value) in &snapshot.attrs {
+        wsl_attrs.set_attr(name, value);
+    }
+    if let Some([_, atime, mtime, ctime]) = times {
+        wsl_attrs.set_times(Some(atime), Some(mtime), Some(ctime));
+    }
+    if let Err(ex) = wsl_attrs.save(wsl_file) {
+        error!("[ERROR] copy_attr for {:?}, error: {ex:?}", wsl_attrs.fs_type());
+        return Change::Failed;
+    }
+    if let Some(times) = times {
+        if let Err(ex) = set_file_times(wsl_file, times.map(Some)) {
+            error!("[ERROR] copy_attr times for {:?}, error: {ex:?}", wsl_attrs.fs_type());
+            return Change::Failed;
+        }
+    }
+    Change::Written
 }
 
-fn chgrp(args: ArgsChange, group: String) {
-    open_to_change(args, |mut wsl_file, distro, wsl_attrs| {
-        let gid = if let Ok(gid) = u32::from_str_radix(&group, 10) {
-            gid
-        } else if let Some(distro) = &distro {
-            if let Some(gid) = distro.gid(&group) {
-                gid
-            } else {
-                println!("[ERROR] no group: {} in distro: {}", &group, &distro.name);
-                return;
-            }
-        } else {
-            println!("[ERROR] group: {} without -d <distro>", &group);
-            return;
-        };
+/// creation time, atime, mtime and ctime of the file for `set-time --reference`,
+/// with nanoseconds from LXATTRB, wslfs keeps no times in EA's so NTFS times are used
+fn reference_times(path: &Path, distro_name: Option<&String>) -> Option<[LxfsTime; 4]> {
+    let distro = try_load_distro(distro_name, Some(path), false).map_err(|err| err.report(false)).ok()?;
+    let wsl_file = match load_wsl_file(path, distro.as_ref()) {
+        Ok(wsl_file) => wsl_file,
+        Err(err) => {
+            error!("[ERROR] load reference file failed: {}", err);
+            return None;
+        },
+    };
+    let Some(fbi) = wsl_file.basic_file_info else {
+        error!("[ERROR] cannot query file times of reference file: {}", path.display());
+        return None;
+    };
 
-        let oldgid = wsl_attrs.get_gid();
+    let ea_buffer = read_ea_or_exit(&wsl_file);
+    let ea_parsed = ea_buffer.as_ref().map(|ea_buffer| ea_parse::parse_ea(&ea_buffer));
+    let lxfs = LxfsParsed::load(&wsl_file, &ea_parsed);
+    let (atime, mtime, ctime) = lxfs.get_times().unwrap_or((
+        (fbi.LastAccessTime as u64).into(),
+        (fbi.LastWriteTime as u64).into(),
+        (fbi.ChangeTime as u64).into(),
+    ));
+    Some([(fbi.CreationTime as u64).into(), atime, mtime, ctime])
+}
 
-        wsl_attrs.set_gid(gid);
-        if let Err(ex) = wsl_attrs.save(&mut wsl_file) {
-            println!("[ERROR] chgrp for {:?} {:?} --> {}, error: {ex:?}", wsl_attrs.fs_type(), oldgid, gid);
+/// the xattr name of `--name`, also given as a wslfs EA name like `LX.USER.FOO`,
+/// `Err` if it is in no namespace, or in a namespace other than `user.*` without `--allow-namespace`
+fn check_xattr_name(name: &str, allow_namespace: bool) -> Result<String, ()> {
+    let name = wsl_file::xattr_name(name);
+    if wslfs::is_internal_attr_name(&name) {
+        // refused or warned for each file by `refuse_internal_attr`
+        return Ok(name);
+    }
+    match XattrNamespace::of_name(&name) {
+        None => {
+            error!("[ERROR] {} is in no namespace, xattr names start with user., trusted., security. or system.", name);
+            return Err(());
+        },
+        Some(XattrNamespace::User) => {},
+        Some(ns) if allow_namespace => warning!("[WARNING] {} is in the {}* namespace treated specially by WSL, changed as --allow-namespace", name, ns.prefix()),
+        Some(ns) => {
+            error!("[ERROR] {} is in the {}* namespace treated specially by WSL, use --allow-namespace to change it", name, ns.prefix());
+            return Err(());
+        },
+    }
+    if name.bytes().any(|b| b.is_ascii_uppercase()) {
+        warning!("[WARNING] {} has upper case letters, wslfs saves it in upper case and reads it back in lower case", name);
+    }
+    Ok(name)
+}
+
+/// `LX.LINUX.ATTR.*` of wslfs are kept by WSL, changed only with `--allow-internal`
+fn refuse_internal_attr(name: &str, allow_internal: bool, wsl_attrs: &dyn WslFileAttributes) -> Option<Change> {
+    if wsl_attrs.fs_type() != FsType::Wslfs || !wslfs::is_internal_attr_name(name) {
+        return None;
+    }
+    if allow_internal {
+        warning!("[WARNING] {} is a WSL internal attribute, changed as --allow-internal", name);
+        None
+    } else {
+        error!("[ERROR] {} is a WSL internal attribute, not an xattr of users, use --allow-internal to change it", name);
+        Some(Change::Failed)
+    }
+}
+
+fn rm_attr(args: ArgsChange, name: String, allow_internal: bool) {
+    let force = args.force;
+    open_to_change(args, "rm-attr", |wsl_file, _distro, wsl_attrs| {
+        if let Some(change) = refuse_internal_attr(&name, allow_internal, wsl_attrs) {
+            return change;
+        }
+        // matched by name, a wslfs value with a broken 'lxea' prefix is removed too
+        if !wsl_attrs.rm_attr(&name) && !force {
+            info!("rm_attr for {:?}: no change", wsl_attrs.fs_type());
+            return Change::Unchanged;
+        }
+        if let Err(ex) = wsl_attrs.save(wsl_file) {
+            error!("[ERROR] rm_attr for {:?}, error: {ex:?}", wsl_attrs.fs_type());
+            Change::Failed
         } else {
-            println!("chgrp for {:?} {:?} --> {}", wsl_attrs.fs_type(), oldgid, gid);
+            info!("rm_attr for {:?}", wsl_attrs.fs_type());
+            Change::Written
         }
     });
 }
 
-fn chmod(args: ArgsChange, modes: String) {
-    open_to_change(args, |mut wsl_file, _distro, wsl_attrs| {
-        let mode = wsl_attrs.get_mode().unwrap_or(DEFAULT_MODE);
-        if let Ok(newmode) = chmod_all(mode, &modes) {
-            wsl_attrs.set_mode(newmode);
-            if let Err(ex) = wsl_attrs.save(&mut wsl_file) {
-                println!("[ERROR] chmod for {:?}: {:06o} / {} --> {:06o} / {}, error: {ex:?}", wsl_attrs.fs_type(), mode, lsperms(mode), newmode, lsperms(newmode));
-            } else {
-                println!("chmod for {:?}: {:06o} / {} --> {:06o} / {}", wsl_attrs.fs_type(), mode, lsperms(mode), newmode, lsperms(newmode));
+fn retype(args: ArgsChange, file_type: StModeType) {
+    if args.args_recursive.recursive {
+        error!("[ERROR] retype cannot be recursive");
+        return;
+    }
+    if !args.yes && !confirm(&format!("change file type of {} to {}", args.path.display(), file_type.name().0)) {
+        info!("retype canceled");
+        return;
+    }
+
+    open_to_change(args, "retype", |wsl_file, _distro, wsl_attrs| {
+        if wsl_file.is_dir() != (file_type == StModeType::DIR) {
+            error!("[ERROR] cannot retype a {} to {}", if wsl_file.is_dir() { "directory" } else { "non-directory" }, file_type.name().0);
+            return Change::Failed;
+        }
+
+        let mode = wsl_attrs.get_mode().unwrap_or(wsl_file.default_mode());
+        let newmode = mode.with_type(file_type);
+        if wsl_attrs.fs_type() == FsType::Wslfs {
+            if let Err(ex) = unsafe { wslfs::retype_wslfs_reparse_point(wsl_file, file_type) } {
+                error!("[ERROR] retype reparse point to {}, error: {ex:?}", file_type.name().0);
+                return Change::Failed;
             }
+        }
+        wsl_attrs.set_mode(newmode);
+        if let Err(ex) = wsl_attrs.save(wsl_file) {
+            error!("[ERROR] retype for {:?}: {:06o} / {} --> {:06o} / {}, error: {ex:?}", wsl_attrs.fs_type(), mode, mode, newmode, newmode);
+            // the reparse point is retyped already, keep it of the old mode saved
+            if wsl_attrs.fs_type() == FsType::Wslfs {
+                if let Err(ex) = unsafe { wslfs::retype_wslfs_reparse_point(wsl_file, mode.file_type()) } {
+                    error!("[ERROR] cannot restore reparse point to {}, error: {ex:?}", mode.file_type().name().0);
+                }
+            }
+            Change::Failed
         } else {
-            println!("[ERROR] invalid mode: {}", modes);
+            info!("retype for {:?}: {:06o} / {} --> {:06o} / {}", wsl_attrs.fs_type(), mode, mode, newmode, newmode);
+            Change::Written
         }
     });
 }
 
-fn set_attr(args: ArgsChange, name: String, value: Option<String>) {
-    open_to_change(args, |mut wsl_file, _distro, wsl_attrs| {
-        let value_bytes = value.map_or(vec![], |v| escape_utils::unescape(&v).expect("invalid value"));
-        wsl_attrs.set_attr(&name, &value_bytes);
-        if let Err(ex) = wsl_attrs.save(&mut wsl_file) {
-            println!("[ERROR] set_attr for {:?}, error: {ex:?}", wsl_attrs.fs_type());
+fn repair(args: ArgsChange, dry_run: bool) {
+    let yes = args.yes;
+    open_to_change(args, "repair", |wsl_file, _distro, wsl_attrs| {
+        // each fix is confirmed alone, a value which really starts with 'lxea' looks like a double prefix
+        let mut found = 0;
+        let fixes = wsl_attrs.repair(&mut |fix| {
+            found += 1;
+            info!("  {}", fix);
+            !dry_run && (yes || confirm(&format!("repair {}", fix)))
+        });
+        if found == 0 {
+            info!("repair for {:?}: nothing to repair", wsl_attrs.fs_type());
+            return Change::Unchanged;
+        }
+        if dry_run {
+            info!("repair for {:?}: {} attributes would be fixed, dry run", wsl_attrs.fs_type(), found);
+            return Change::Unchanged;
+        }
+        if fixes.is_empty() {
+            info!("repair canceled");
+            return Change::Declined;
+        }
+        if let Err(ex) = wsl_attrs.save(wsl_file) {
+            error!("[ERROR] repair for {:?}, error: {ex:?}", wsl_attrs.fs_type());
+            Change::Failed
         } else {
-            println!("set_attr for {:?}", wsl_attrs.fs_type());
+            info!("repair for {:?}: {} attributes fixed", wsl_attrs.fs_type(), fixes.len());
+            Change::Written
         }
     });
 }
 
-fn rm_attr(args: ArgsChange, name: String) {
-    open_to_change(args, |mut wsl_file, _distro, wsl_attrs| {
-        wsl_attrs.rm_attr(&name);
-        if let Err(ex) = wsl_attrs.save(&mut wsl_file) {
-            println!("[ERROR] rm_attr for {:?}, error: {ex:?}", wsl_attrs.fs_type());
-        } else {
-            println!("rm_attr for {:?}", wsl_attrs.fs_type());
+fn mknod(path: PathBuf, dev_type: StModeType, major: u32, minor: u32, mode: &str, fs_type: Option<FsType>, distro_name: Option<&String>) -> Result<(), ()> {
+    let Some(perms) = posix::parse_mode(mode) else {
+        error!("[ERROR] invalid mode: {}", mode);
+        return Err(());
+    };
+    let spec = DevSpec { path: path.to_string_lossy().into_owned(), mode: Mode(perms).with_type(dev_type), major, minor };
+    let distro = try_load_distro(distro_name, Some(&path), false).map_err(|err| err.report(false))?;
+    let fs_type = mknod_fs_type(fs_type, distro.as_ref())?;
+    match create_dev_node(&spec, fs_type, distro.as_ref()) {
+        Ok(()) => {
+            info!("mknod for {:?}: {} {} {} {}", fs_type, &spec.path, spec.mode.file_type().name().1, major, minor);
+            Ok(())
+        },
+        Err(msg) => {
+            error!("[ERROR] mknod {}: {}", &spec.path, msg);
+            Err(())
+        },
+    }
+}
+
+/// create all nodes of the makedev table, a bad line is reported and skipped
+fn mknod_template(template: &Path, fs_type: Option<FsType>, distro_name: Option<&String>) -> Result<(), ()> {
+    let table = match std::fs::read_to_string(template) {
+        Ok(table) => table,
+        Err(ex) => {
+            error!("[ERROR] read template: {}, error: {ex:?}", template.display());
+            return Err(());
+        },
+    };
+    let distro = try_load_distro(distro_name, None::<&Path>, false).map_err(|err| err.report(false))?;
+    let fs_type = mknod_fs_type(fs_type, distro.as_ref())?;
+
+    let (mut created, mut failed) = (0, 0);
+    for (i, line) in table.lines().enumerate() {
+        let line_no = i + 1;
+        let spec = match posix::parse_dev_spec(line) {
+            Ok(Some(spec)) => spec,
+            Ok(None) => continue,
+            Err(msg) => {
+                error!("[ERROR] line {}: {}", line_no, msg);
+                failed += 1;
+                continue;
+            },
+        };
+        match create_dev_node(&spec, fs_type, distro.as_ref()) {
+            Ok(()) => {
+                info!("line {}: created {} {} {} {} {:04o}", line_no, &spec.path, spec.mode.file_type().name().1, spec.major, spec.minor, spec.mode.perms());
+                created += 1;
+            },
+            Err(msg) => {
+                error!("[ERROR] line {}: {}: {}", line_no, &spec.path, msg);
+                failed += 1;
+            },
+        }
+    }
+    info!("{} nodes created, {} failed", created, failed);
+    if failed > 0 { Err(()) } else { Ok(()) }
+}
+
+/// a new file has no metadata to tell the fs type
+fn mknod_fs_type(fs_type: Option<FsType>, distro: Option<&Distro>) -> Result<FsType, ()> {
+    if let Some(fs_type) = fs_type.or(distro.and_then(|d| d.fs_type)) {
+        Ok(fs_type)
+    } else {
+        error!("[ERROR] cannot determine fs_type, use --fs-type or --distro");
+        Err(())
+    }
+}
+
+/// create an empty file owned by root, with mode and device number of `spec`, an existing file is not touched
+fn create_dev_node(spec: &DevSpec, fs_type: FsType, distro: Option<&Distro>) -> Result<(), String> {
+    let real_path = get_real_path(Path::new(&spec.path), distro).map_err(|err| err.to_string())?;
+    std::fs::OpenOptions::new().write(true).create_new(true).open(&real_path)
+        .map_err(|ex| format!("create {}, error: {ex:?}", real_path.display()))?;
+    let result = (|| -> Result<(), String> {
+        let mut wsl_file = unsafe { open_handle(&real_path, true) }
+            .map_err(|ex| format!("open {}, error: {ex:?}", real_path.display()))?;
+
+        let ea_parsed = None;
+        let mut wslfs = WslfsParsed::load(&wsl_file, &ea_parsed);
+        let mut lxfs = LxfsParsed::load(&wsl_file, &ea_parsed);
+        let wsl_attrs: &mut dyn WslFileAttributes = match fs_type {
+            FsType::Wslfs => {
+                unsafe { wslfs::set_wslfs_reparse_point(&mut wsl_file, spec.mode.file_type(), None) }
+                    .map_err(|ex| format!("set reparse point, error: {ex:?}"))?;
+                &mut wslfs
+            },
+            FsType::Lxfs => {
+                lxfs.init_default();
+                &mut lxfs
+            },
+        };
+        wsl_attrs.set_uid(0);
+        wsl_attrs.set_gid(0);
+        wsl_attrs.set_mode(spec.mode);
+        wsl_attrs.set_dev_major(spec.major);
+        wsl_attrs.set_dev_minor(spec.minor);
+        wsl_attrs.save(&mut wsl_file).map_err(|ex| format!("save attributes, error: {ex:?}"))
+    })();
+    // the file is created by us, do not leave a plain empty file for a device node
+    if result.is_err() {
+        let _ = std::fs::remove_file(&real_path);
+    }
+    result
+}
+
+fn diff(path_a: &Path, path_b: &Path, distro: Option<&String>, json: bool) {
+    use std::process::exit;
+
+    // each path is resolved by its own distro, the files may be of two distros
+    let load = |path: &Path| {
+        let loaded = try_load_distro(distro, Some(path), false)
+        .and_then(|distro| load_wsl_file(path, distro.as_ref()))
+        .and_then(|wsl_file| {
+            match wsl_file.read_ea() {
+                Ok(ea_buffer) => Ok((wsl_file, ea_buffer)),
+                Err(source) => Err(WslAttrError::ReadEa { path: path.to_path_buf(), source }),
+            }
+        });
+        loaded.unwrap_or_else(|err| {
+            err.report(json);
+            exit(2);
+        })
+    };
+    let (file_a, ea_buffer_a) = load(path_a);
+    let (file_b, ea_buffer_b) = load(path_b);
+    let ea_parsed_a = ea_buffer_a.as_ref().map(|ea_buffer| ea_parse::parse_ea(&ea_buffer));
+    let ea_parsed_b = ea_buffer_b.as_ref().map(|ea_buffer| ea_parse::parse_ea(&ea_buffer));
+    let (wslfs_a, lxfs_a) = (WslfsParsed::load(&file_a, &ea_parsed_a), LxfsParsed::load(&file_a, &ea_parsed_a));
+    let (wslfs_b, lxfs_b) = (WslfsParsed::load(&file_b, &ea_parsed_b), LxfsParsed::load(&file_b, &ea_parsed_b));
+
+    let diff = diff::AttrsDiff::new(diff_side(path_a, &wslfs_a, &lxfs_a, json), diff_side(path_b, &wslfs_b, &lxfs_b, json));
+    if json {
+        println!("{}", json::to_output(&diff.to_json()));
+    } else {
+        diff.fmt(&mut std::io::stdout().lock(), &FmtOptions::default()).unwrap();
+    }
+    exit(if diff.is_empty() { 0 } else { 1 });
+}
+
+/// attributes of the file to compare, `None` if no metadata, exit if both wslfs and lxfs metadata exist
+fn diff_side<'x, 'a>(path: &Path, wslfs: &'x WslfsParsed<'a>, lxfs: &'x LxfsParsed<'a>, json: bool) -> Option<&'x dyn WslFileAttributes<'a>> {
+    match (wslfs.maybe(), lxfs.maybe()) {
+        (true, true) => {
+            WslAttrError::AmbiguousFsType { path: path.to_path_buf() }.report(json);
+            std::process::exit(2);
+        },
+        (true, false) => Some(wslfs),
+        (false, true) => Some(lxfs),
+        (false, false) => None,
+    }
+}
+
+fn stat(args: ArgsView, json: bool) {
+    use ea_parse::EA_SIZE_MAX;
+    use wslfs::WslfsReparseTag;
+
+    let mut out = open_output(args.output.as_deref());
+    let opts = fmt_options(&args);
+    let distro = try_load_distro(args.distro.as_ref(), Some(&args.path), false).unwrap_or_else(|err| {
+        err.report(json);
+        std::process::exit(failure_exit_code());
+    });
+    let loaded = load_wsl_file(&args.path, distro.as_ref()).and_then(|wsl_file| {
+        match wsl_file.read_ea() {
+            Ok(ea_buffer) => Ok((wsl_file, ea_buffer)),
+            Err(source) => Err(WslAttrError::ReadEa { path: args.path.clone(), source }),
         }
     });
+    let (wsl_file, ea_buffer) = loaded.unwrap_or_else(|err| {
+        err.report(json);
+        std::process::exit(failure_exit_code());
+    });
+    let ea_parsed = ea_buffer.as_ref().map(|ea_buffer| ea_parse::parse_ea(&ea_buffer)).unwrap_or_default();
+    let ea_size = ea_buffer.as_ref().map_or(0, Vec::len);
+    let reparse = wsl_file.reparse_tag.map(|t| (t, StModeType::from_tag_id(t).name().0));
+
+    if json {
+        let mut json_out = String::new();
+        json_out += &format!("{{\"ea_count\":{},\"ea_size\":{},\"ea_size_max\":{},", ea_parsed.len(), ea_size, EA_SIZE_MAX);
+        match reparse {
+            Some((t, name)) => json_out += &format!("\"reparse_tag\":{},\"reparse_type\":\"{}\",", t, name),
+            None => json_out += "\"reparse_tag\":null,\"reparse_type\":null,",
+        }
+        json_out += "\"eas\":[";
+        for (i, ea) in ea_parsed.iter().enumerate() {
+            if i > 0 {
+                json_out += ",";
+            }
+            json_out += "{\"name\":\"";
+            escape_utils::escape_json_str(&String::from_utf8_lossy(ea.name), &mut json_out).unwrap();
+            json_out += &format!("\",\"name_len\":{},\"value_len\":{},\"size\":{}}}", ea.name.len(), ea.value.len(), ea.size());
+        }
+        json_out += "]}";
+        writeln!(out, "{}", json::to_output(&json_out)).unwrap();
+        out.flush().unwrap();
+        return;
+    }
+
+    writeln!(out, "{}{}", opts.label("EA count:"), ea_parsed.len()).unwrap();
+    writeln!(out, "{}{} / {} bytes", opts.label("EA size:"), ea_size, EA_SIZE_MAX).unwrap();
+    match reparse {
+        Some((t, name)) => writeln!(out, "{}{:#010x} / {}", opts.label("Reparse point:"), t, name).unwrap(),
+        None => writeln!(out, "{}{}", opts.label("Reparse point:"), "none").unwrap(),
+    }
+    if !ea_parsed.is_empty() {
+        writeln!(out, "{}{:>10}{:>10}{:>10}", opts.label("EA name"), "name len", "value len", "size").unwrap();
+        for ea in &ea_parsed {
+            writeln!(out, "{}{:>10}{:>10}{:>10}", opts.label(&format!("  {}", String::from_utf8_lossy(ea.name))), ea.name.len(), ea.value.len(), ea.size()).unwrap();
+        }
+    }
+    out.flush().unwrap();
+}
+
+/// `{"version","fs_types":[],"reparse_tags":[{"tag","type"}],"commands":[]}` of the compiled-in values,
+/// for scripts and GUIs to adapt to the installed version
+fn capabilities_json() -> String {
+    use clap::{CommandFactory, ValueEnum};
+    use wslfs::WslfsReparseTag;
+
+    fn json_names<'a>(names: impl Iterator<Item = &'a str>) -> String {
+        names.map(|n| format!("\"{}\"", n)).collect::<Vec<_>>().join(",")
+    }
+
+    let fs_types: Vec<String> = FsType::value_variants().iter()
+        .filter_map(|t| t.to_possible_value().map(|v| v.get_name().to_owned()))
+        .collect();
+    let reparse_tags: Vec<String> = StModeType::value_variants().iter()
+        .filter(|t| t.tag_id() != 0)
+        .map(|t| format!("{{\"tag\":\"{:#010x}\",\"type\":\"{}\"}}", t.tag_id(), t.name().0))
+        .collect();
+    let cmd = Args::command();
+    let commands = cmd.get_subcommands().filter(|c| !c.is_hide_set()).map(|c| c.get_name());
+
+    format!("{{\"version\":\"{}\",\"fs_types\":[{}],\"reparse_tags\":[{}],\"commands\":[{}]}}",
+        env!("CARGO_PKG_VERSION"), json_names(fs_types.iter().map(String::as_str)), reparse_tags.join(","), json_names(commands))
+}
+
+fn completions(shell: clap_complete::Shell) {
+    use clap::CommandFactory;
+
+    // scripts cannot call back to list distros, so the distros registered now are the values of `--distro`,
+    // the names are leaked as clap keeps `&'static str` and the process exits soon
+    let names: Vec<&'static str> = if NO_DISTRO.load(Ordering::Relaxed) {
+        Vec::new()
+    } else {
+        distro::list_names().into_iter().map(|n| &*n.leak()).collect()
+    };
+    let mut cmd = Args::command();
+    if !names.is_empty() {
+        cmd = with_distro_values(cmd, &names);
+    }
+    clap_complete::generate(shell, &mut cmd, env!("CARGO_PKG_NAME"), &mut std::io::stdout());
+}
+
+fn with_distro_values(cmd: clap::Command, names: &[&'static str]) -> clap::Command {
+    use clap::builder::PossibleValuesParser;
+
+    cmd.mut_args(|arg| if arg.get_id() == "distro" {
+        arg.value_parser(PossibleValuesParser::new(names.iter().copied()))
+    } else {
+        arg
+    })
+    .mut_subcommands(|sub| with_distro_values(sub, names))
+}
+
+fn id(distro_name: &str, uid: Option<u32>, name: Option<&str>) -> Result<(), ()> {
+    refuse_distro_arg(Some(distro_name));
+    let Some(distro) = distro::try_load(distro_name) else {
+        error!("[ERROR] cannot load distro: {}", distro_name);
+        return Err(());
+    };
+    if distro.users.is_none() {
+        error!("[ERROR] cannot read /etc/passwd of distro: {}", &distro.name);
+        return Err(());
+    }
+
+    let uid = match (uid, name) {
+        (Some(uid), _) => uid,
+        (None, Some(name)) => if let Some(uid) = distro.uid(name) {
+            uid
+        } else {
+            error!("[ERROR] no user: {} in distro: {}", name, &distro.name);
+            return Err(());
+        },
+        (None, None) => {
+            error!("[ERROR] there must be one of uid or name args");
+            return Err(());
+        },
+    };
+
+    let Some(user_name) = distro.user_name(uid) else {
+        error!("[ERROR] no uid: {} in distro: {}", uid, &distro.name);
+        return Err(());
+    };
+
+    let fmt_group = |gid: u32| match distro.group_name(gid) {
+        Some(group_name) => format!("{}({})", gid, group_name),
+        None => format!("{}", gid),
+    };
+
+    let mut out = format!("uid={}({})", uid, user_name);
+    let primary_gid = distro.primary_gid(uid);
+    if let Some(gid) = primary_gid {
+        out += &format!(" gid={}", fmt_group(gid));
+    }
+    let groups: Vec<String> = primary_gid.into_iter()
+    .chain(distro.supplementary_groups(user_name).iter().map(|g| g.gid).filter(|gid| Some(*gid) != primary_gid))
+    .map(fmt_group)
+    .collect();
+    if !groups.is_empty() {
+        out += &format!(" groups={}", groups.join(","));
+    }
+    println!("{}", out);
+    Ok(())
+}
+
+/// `None` when the file has no EA's, exit if the EA query failed
+/// for commands of a single file, `change_file` returns `Change::Failed` instead
+fn read_ea_or_exit(wsl_file: &WslFile) -> Option<Vec<u8>> {
+    match wsl_file.read_ea() {
+        Ok(ea_buffer) => ea_buffer,
+        Err(ex) => {
+            error!("[ERROR] cannot read EAs, error: {ex:?}");
+            std::process::exit(failure_exit_code());
+        },
+    }
 }
 
 fn test_ea_write(ea_buffer: &Option<Vec<u8>>, ea_parsed: &Option<Vec<EaEntry<&[u8]>>>) {
@@ -383,134 +3048,485 @@ fn set_ea(file_handle: HANDLE, name: &[u8], value: Option<&[u8]>) {
     unsafe {
         let _ = ntfs_io::write_ea(file_handle, &ea_out.buffer);
     }
-}
+}
+
+/// `--distro` conflicts with `--no-distro`, exits if both given
+fn refuse_distro_arg(arg_distro: Option<&str>) {
+    if let Some(distro_name) = arg_distro.filter(|_| NO_DISTRO.load(Ordering::Relaxed)) {
+        error!("[ERROR] --distro {} conflicts with --no-distro", distro_name);
+        std::process::exit(failure_exit_code());
+    }
+}
+
+const WSL2_NOTE: &str = "[NOTE] file of a WSL2 distro, which keeps attributes inside its ext4 image, lxfs and wslfs metadata do not apply";
+
+/// `None` fs_type means WSL2
+fn is_wsl2(distro: Option<&Distro>) -> bool {
+    distro.is_some_and(|d| d.fs_type.is_none())
+}
+
+/// WSL2 distro from arg, file path or current dir for a relative path is returned only if `allow_wsl2`, for read only commands
+fn try_load_distro<S: AsRef<str>, P: AsRef<Path>>(arg_distro: Option<S>, path: Option<P>, allow_wsl2: bool) -> Result<Option<Distro>, WslAttrError> {
+    try_load_distro_with(arg_distro, path, allow_wsl2, NO_DISTRO.load(Ordering::Relaxed), distro::get_override())
+}
+
+/// `try_load_distro` with `--no-distro` and the `--distro-root-override` distro `o`
+fn try_load_distro_with<S: AsRef<str>, P: AsRef<Path>>(arg_distro: Option<S>, path: Option<P>, allow_wsl2: bool, no_distro: bool, o: Option<&distro::DistroOverride>) -> Result<Option<Distro>, WslAttrError> {
+    if no_distro {
+        if let Some(distro_name) = arg_distro {
+            error!("[ERROR] --distro {} conflicts with --no-distro", distro_name.as_ref());
+            std::process::exit(failure_exit_code());
+        }
+        return Ok(None);
+    }
+
+    // the offline distro of `--rootfs` is used for all paths without `--distro`
+    if arg_distro.is_none() {
+        if let Some(d) = distro::try_load_offline() {
+            info!("distro: {} loaded from --rootfs: {}", &d.name, d.rootfs_path().display());
+            return Ok(Some(d));
+        }
+    }
 
-fn try_load_distro<S: AsRef<str>, P: AsRef<Path>>(arg_distro: Option<S>, path: Option<P>) -> Option<Distro> {
     // try load distro fron argument
     if let Some(distro_name) = arg_distro {
         let distro_name = distro_name.as_ref();
         //println!("try load distro fron arg: {}", distro_name);
-        let distro = distro::try_load(distro_name);
+        let distro = distro::try_load_with(distro_name, o);
         if let Some(mut d) = distro {
             d.source = DistroSource::Arg;
-            if d.fs_type.is_none() {
-                panic!("[ERROR] distro from arg: {} is WSL2", &d.name)
+            if d.fs_type.is_none() && !allow_wsl2 {
+                return Err(WslAttrError::DistroWsl2 { name: d.name });
             } else {
-                println!("distro: {} loaded from arg", distro_name);
-                return Some(d);
+                info!("distro: {} loaded from arg", distro_name);
+                return Ok(Some(d));
             }
         } else {
-            panic!("[ERROR] cannot load distro from arg: {}", distro_name)
+            return Err(WslAttrError::DistroNotFound { name: distro_name.to_owned() });
         }
     }
 
     // try load distro fron file path
+    let mut is_relative = true;
     if let Some(p) = path {
         let in_path = p.as_ref();
+        is_relative = in_path.is_relative() && !is_unix_absolute(in_path);
         if !is_unix_absolute(in_path) && in_path.is_absolute() {
             //println!("try load distro fron file path: {}", in_path.display());
-            let distro = distro::try_load_from_absolute_path(in_path);
+            let unc_distro_name = path_utils::try_get_distro_from_unc_path(in_path);
+            let distro = distro::try_load_from_absolute_path_with(in_path, o);
             if let Some(mut d) = distro {
-                if d.fs_type.is_none() {
-                    println!("[WARNING] distro: {} loaded from file path is WSL2, ignore it", &d.name);
+                if d.fs_type.is_none() && !allow_wsl2 {
+                    warning!("[WARNING] distro: {} loaded from file path is WSL2, ignore it", &d.name);
                 } else {
-                    println!("distro: {} loaded from file path: {}", &d.name, in_path.display());
-                    return Some(d);
+                    info!("distro: {} loaded from file path: {}", &d.name, in_path.display());
+                    return Ok(Some(d));
                 }
+            } else if let Some(name) = &unc_distro_name {
+                warning!("[WARNING] cannot load distro: {} of the WSL UNC path", name.to_string_lossy());
+            }
+            if unc_distro_name.is_some() {
+                // the UNC path names its distro, the one of current dir or the default would not match
+                return Ok(None);
             }
         }
     }
 
     // try load distro fron current path
-    if let Some(mut d) = distro::try_load_from_current_dir() {
-        if d.fs_type.is_none() {
-            println!("[WARNING] distro: {} loaded from current dir is WSL2, ignore it", &d.name);
+    if let Some(mut d) = distro::try_load_from_current_dir_with(o) {
+        if d.fs_type.is_none() && !(allow_wsl2 && is_relative) {
+            warning!("[WARNING] distro: {} loaded from current dir is WSL2, ignore it", &d.name);
         } else {
-            println!("distro: {} loaded from current dir: {}", &d.name, std::env::current_dir().unwrap().display());
-            return Some(d);
+            info!("distro: {} loaded from current dir: {}", &d.name, std::env::current_dir().unwrap().display());
+            return Ok(Some(d));
         }
     }
 
     // try load default WSL distro in registry
     if let Some(d) = distro::try_load_from_reg_default() {
         if d.fs_type.is_none() {
-            println!("[WARNING] distro: {} loaded from default WSL distro in registry is WSL2, ignore it", &d.name);
+            warning!("[WARNING] distro: {} loaded from default WSL distro in registry is WSL2, ignore it", &d.name);
+        } else {
+            info!("distro: {} loaded from default WSL distro in registry", &d.name);
+            return Ok(Some(d));
+        }
+    }
+
+    info!("no distro loaded");
+    return Ok(None);
+}
+
+/// files under `path` matching any of `rules`, files without wslfs nor lxfs metadata are not reported
+fn audit(path: &Path, distro_name: Option<&String>, rules: &posix::AuditRules, case_collisions: bool, args_recursive: &ArgsRecursive, json: bool) -> Result<(), ()> {
+    let distro = try_load_distro(distro_name, Some(path), true).map_err(|err| err.report(json))?;
+    if let Some(d) = distro.as_ref().filter(|d| is_wsl2(Some(d))) {
+        WslAttrError::DistroWsl2 { name: d.name.clone() }.report(json);
+        return Err(());
+    }
+    let real_path = get_real_path(path, distro.as_ref()).map_err(|err| err.report(json))?;
+
+    let (mut audited, mut reported) = (0, 0);
+    let mut failures = Failures::new();
+    let mut out = std::io::stdout().lock();
+    walk_files(&real_path, args_recursive, &mut failures, |path, wsl_file| {
+        if case_collisions && wsl_file.is_dir() {
+            let names = match std::fs::read_dir(real_path.join(path)) {
+                Ok(entries) => entries.filter_map(Result::ok).map(|e| e.file_name()).collect::<Vec<_>>(),
+                Err(ex) => {
+                    error!("[ERROR] cannot list directory {}, error: {ex:?}", path.display());
+                    return Err(());
+                },
+            };
+            let groups = path_utils::case_collisions(&names);
+            if !groups.is_empty() {
+                reported += 1;
+                let case_sensitive = ntfs_io::query_case_sensitive(wsl_file.file_handle).ok();
+                print_case_collisions(&mut out, path, &groups, case_sensitive, json);
+            }
+        }
+        if !rules.any() {
+            return Ok(());
+        }
+
+        let ea_buffer = match wsl_file.read_ea() {
+            Ok(ea_buffer) => ea_buffer,
+            Err(ex) => {
+                error!("[ERROR] cannot read EAs of {}, error: {ex:?}", path.display());
+                return Err(());
+            },
+        };
+        let ea_parsed = ea_buffer.as_ref().map(|ea_buffer| ea_parse::parse_ea(ea_buffer));
+        let wslfs = WslfsParsed::load(&wsl_file, &ea_parsed);
+        let lxfs = LxfsParsed::load(&wsl_file, &ea_parsed);
+        let wsl_attrs: &dyn WslFileAttributes = match select_fs_type(None, distro.as_ref(), &wslfs, &lxfs) {
+            Ok(FsType::Wslfs) => &wslfs,
+            Ok(FsType::Lxfs) => &lxfs,
+            Err(_) => return Ok(()),
+        };
+        audited += 1;
+
+        let (mode, uid, gid) = (wsl_attrs.get_mode(), wsl_attrs.get_uid(), wsl_attrs.get_gid());
+        let findings = rules.findings(mode, uid);
+        if findings.is_empty() {
+            return Ok(());
+        }
+        reported += 1;
+        if json {
+            let json_u32 = |v: Option<u32>| v.map_or("null".to_owned(), |v| v.to_string());
+            let mut json_out = String::from("{\"path\":\"");
+            escape_utils::escape_json_str(&path.to_string_lossy(), &mut json_out).unwrap();
+            json_out += &format!("\",\"findings\":[{}],\"mode\":{},\"uid\":{},\"gid\":{}}}",
+                findings.iter().map(|f| format!("\"{}\"", f)).collect::<Vec<_>>().join(","),
+                json_u32(mode.map(u32::from)), json_u32(uid), json_u32(gid));
+            writeln!(out, "{}", json::to_output(&json_out)).unwrap();
         } else {
-            println!("distro: {} loaded from default WSL distro in registry", &d.name);
-            return Some(d);
+            let display = |v: Option<String>| v.unwrap_or_else(|| "none".to_owned());
+            writeln!(out, "{}: {} (mode: {}, uid: {}, gid: {})", path.display(), findings.join(", "),
+                display(mode.map(|m| format!("{:06o}", m.0))), display(uid.map(|v| v.to_string())), display(gid.map(|v| v.to_string()))).unwrap();
+        }
+        Ok(())
+    });
+    info!("audit: {} files with metadata audited, {} reported", audited, reported);
+    failures.finish("audit")
+}
+
+/// each group of names colliding in directory `path`, as a json object per line `{"path","case_collisions":[[]],"case_sensitive"}`
+fn print_case_collisions(out: &mut dyn std::io::Write, path: &Path, groups: &[Vec<String>], case_sensitive: Option<bool>, json: bool) {
+    if json {
+        let mut json_out = String::from("{\"path\":\"");
+        escape_utils::escape_json_str(&path.to_string_lossy(), &mut json_out).unwrap();
+        json_out += "\",\"case_collisions\":[";
+        for (i, group) in groups.iter().enumerate() {
+            if i > 0 {
+                json_out.push(',');
+            }
+            json_out.push('[');
+            for (j, name) in group.iter().enumerate() {
+                if j > 0 {
+                    json_out.push(',');
+                }
+                json_out.push('"');
+                escape_utils::escape_json_str(name, &mut json_out).unwrap();
+                json_out.push('"');
+            }
+            json_out.push(']');
+        }
+        json_out += &format!("],\"case_sensitive\":{}}}", case_sensitive.map_or("null".to_owned(), |v| v.to_string()));
+        writeln!(out, "{}", json::to_output(&json_out)).unwrap();
+    } else {
+        let case_sensitive = match case_sensitive {
+            Some(true) => "case sensitive",
+            Some(false) => "NOT case sensitive",
+            None => "case sensitivity unknown",
+        };
+        for group in groups {
+            writeln!(out, "{}: case collision: {} ({})", path.display(), group.join(", "), case_sensitive).unwrap();
         }
     }
+}
 
-    println!("no distro loaded");
-    return None;
+/// windows path of a unix path or a WSL UNC path
+fn get_real_path(in_path: &Path, distro: Option<&Distro>) -> Result<PathBuf, WslAttrError> {
+    get_real_path_with(in_path, distro, NO_DISTRO.load(Ordering::Relaxed))
 }
 
-fn load_wsl_file(in_path: &Path, distro: Option<&Distro>) -> Option<WslFile> {
+/// `get_real_path` with `--no-distro`, which tells why no distro is loaded
+fn get_real_path_with(in_path: &Path, distro: Option<&Distro>, no_distro: bool) -> Result<PathBuf, WslAttrError> {
     let real_path;
 
     if is_unix_absolute(in_path) {
         // unix path with root like r"/usr/bin"
-        println!("unix path: {}", in_path.display());
+        info!("unix path: {}", in_path.display());
 
-        let d = distro.expect("argument --distro is needed for unix path");
+        let d = distro.ok_or_else(|| if no_distro {
+            WslAttrError::DistroDisabled { path: in_path.to_path_buf() }
+        } else {
+            WslAttrError::UnixPathWithoutDistro { path: in_path.to_path_buf() }
+        })?;
 
-        let mut unix_path_comps = in_path.components();
-        unix_path_comps.next(); // skip RootDir
-        real_path = d.base_path.join("rootfs").join(unix_path_comps);
+        // "/" is the rootfs itself, "/.." stays in it
+        let mut root_path = if is_wsl2(Some(d)) {
+            // no rootfs for WSL2, its files are served by 9P
+            PathBuf::from(format!(r"\\wsl$\{}\", &d.name))
+        } else {
+            d.rootfs_path()
+        };
+        root_path.extend(path_utils::names_below_root(in_path));
+        real_path = root_path;
     } else {
-        let abs_path = absolute(in_path).expect(&format!("invalid path: {:?}", in_path));
+        let abs_path = absolute(in_path).map_err(|source| WslAttrError::InvalidPath { path: in_path.to_path_buf(), source })?;
         let path_prefix = try_get_abs_path_prefix(&abs_path);
         if let Some(distro_name_from_path) = path_prefix.as_ref().and_then(try_get_distro_from_unc_prefix) {
             // wsl UNC path like r"\\wsl$\Arch\file"
-            println!("UNC path : {}", &abs_path.display());
+            info!("UNC path : {}", &abs_path.display());
 
-            let distro = distro.unwrap_or_else(|| {
-                panic!("no distro loaded for a WSL UNC path: {}", abs_path.display());
-            });
-            if distro_name_from_path != distro.name.as_str() {
-                panic!("distro: {} loaded does not match the WSL UNC path: {}", &distro.name, abs_path.display());
+            let Some(distro) = distro else {
+                if no_distro {
+                    return Err(WslAttrError::DistroDisabled { path: abs_path });
+                }
+                return Err(WslAttrError::UncPathWithoutDistro { path: abs_path });
+            };
+            // names of distros are case insensitive as UNC share names
+            if !distro_name_from_path.eq_ignore_ascii_case(&distro.name) {
+                return Err(WslAttrError::DistroMismatch { path: abs_path, distro: distro.name.clone() });
+            }
+            if is_wsl2(Some(distro)) {
+                info!("real path: {}", &abs_path.display());
+                return Ok(abs_path);
             }
 
-            let mut abs_path_comps = abs_path.components();
-            abs_path_comps.next(); // skip Prefix
-            abs_path_comps.next(); // sklp RootDir
-            real_path = distro.base_path.join("rootfs").join(abs_path_comps); // .skip(2)
+            // skip Prefix and RootDir, r"\\wsl$\Arch\" is the rootfs itself
+            let mut rootfs_path = distro.rootfs_path();
+            rootfs_path.extend(path_utils::names_below_root(&abs_path));
+            real_path = rootfs_path;
         } else if is_path_prefix_disk(&path_prefix) {
             // normal path like r"D:\file"
             real_path = abs_path;
         } else {
             // unsupported path like r"\\remote\share\"
-            panic!("unsupported path {}", abs_path.display());
+            return Err(WslAttrError::UnsupportedPath { path: abs_path });
         }
     }
 
-    println!("real path: {}", &real_path.display());
+    info!("real path: {}", &real_path.display());
+    Ok(real_path)
+}
 
-    unsafe {
-        let wsl_file = wsl_file::open_handle(&real_path, false).expect("failed to open file");
-        return Some(wsl_file);
+/// distro, fs type and unix path of a windows path, `Err` if the path is not inside any registered distro
+fn which(path: &Path) -> Result<(), ()> {
+    if NO_DISTRO.load(Ordering::Relaxed) {
+        error!("[ERROR] which looks up the registered distros, which --no-distro disables");
+        return Err(());
     }
+    let abs_path = absolute(path).map_err(|source| WslAttrError::InvalidPath { path: path.to_path_buf(), source }.report(false))?;
+    let Some(distro) = distro::try_load_from_absolute_path(&abs_path) else {
+        println!("not inside any registered distro: {}", abs_path.display());
+        return Err(());
+    };
+
+    let unix_path = if distro.source == DistroSource::FilePathUNC {
+        // r"\wsl$\Arch"
+        let root: PathBuf = abs_path.components().take(2).collect();
+        path_utils::unix_path_in(&abs_path, &root)
+    } else {
+        normalize_path(&abs_path).ok()
+        .and_then(|p| path_utils::unix_path_in(&p, &distro.rootfs_path()))
+    };
+
+    let opts = FmtOptions::default();
+    println!("{}{}", opts.label("Distro:"), &distro.name);
+    println!("{}{}", opts.label("Fs Type:"), distro.fs_type.map_or("WSL2".to_owned(), |t| format!("{:?}", t)));
+    println!("{}{}", opts.label("Base Path:"), distro.base_path.display());
+    println!("{}{}", opts.label("Flags:"), distro.flags.map_or("none, not in registry".to_owned(), distro::distro_flags_display));
+    println!("{}{}", opts.label("Unix Path:"), unix_path.as_deref().unwrap_or("none, not inside rootfs"));
+    Ok(())
 }
 
-fn downgrade_distro(distro: &mut Distro) {
-    for entry in walkdir::WalkDir::new(&distro.base_path) {
-        if let Ok(entry) = entry {
-            if let Ok(_) = downgrade_path(&entry.path().join("rootfs")) {
-                println!("downgrade success: {}", entry.path().display());
-            } else {
-                println!("downgrade failed: {}", entry.path().display());
+fn load_wsl_file(in_path: &Path, distro: Option<&Distro>) -> Result<WslFile, WslAttrError> {
+    let real_path = get_real_path(in_path, distro)?;
+
+    unsafe { wsl_file::open_handle(&real_path, false) }
+        .map_err(|source| WslAttrError::Open { path: in_path.to_path_buf(), source })
+}
+
+/// prompt `y/N`, non-interactive stdin cannot confirm, `--yes` is required then
+fn confirm(prompt: &str) -> bool {
+    use std::io::{stdin, stdout, IsTerminal, Write};
+
+    if !stdin().is_terminal() {
+        error!("[ERROR] {}, stdin is not interactive, use --yes to confirm", prompt);
+        return false;
+    }
+
+    print!("{}, continue? [y/N] ", prompt);
+    let _ = stdout().flush();
+    let mut answer = String::new();
+    if stdin().read_line(&mut answer).is_err() {
+        return false;
+    }
+    matches!(answer.trim(), "y" | "Y" | "yes" | "Yes" | "YES")
+}
+
+/// convert one file from its wslfs or lxfs metadata to `to`, metadata of the source fs type is removed
+fn convert(path: &Path, to: FsType, create: bool, distro_name: Option<&String>) -> Result<(), ()> {
+    use wslfs::WslfsReparseTag;
+
+    let distro = try_load_distro(distro_name, Some(path), false).map_err(|err| err.report(false))?;
+    if let Some(d) = distro.as_ref().filter(|d| d.fs_type.is_some_and(|t| t != to)) {
+        warning!("[NOTE] distro: {} is {:?}, WSL reads files as the registry Version says, not as the metadata written", &d.name, d.fs_type.unwrap());
+    }
+    let mut wsl_file = load_wsl_file(path, distro.as_ref()).map_err(|err| err.report(false))?;
+    let ea_buffer = wsl_file.read_ea().map_err(|source| WslAttrError::ReadEa { path: path.to_path_buf(), source }.report(false))?;
+    let ea_parsed = ea_buffer.as_ref().map(|ea_buffer| ea_parse::parse_ea(&ea_buffer));
+    let wslfs = WslfsParsed::load(&wsl_file, &ea_parsed);
+    let mut lxfs = LxfsParsed::load(&wsl_file, &ea_parsed);
+
+    let from = match (wslfs.maybe(), lxfs.maybe()) {
+        (true, true) => {
+            error!("[ERROR] cannot convert {}, cause both wslfs and lxfs metadata exist", path.display());
+            return Err(());
+        },
+        (true, false) => Some(FsType::Wslfs),
+        (false, true) => Some(FsType::Lxfs),
+        (false, false) if create => None,
+        (false, false) => {
+            error!("[ERROR] cannot convert {}, cause no wslfs nor lxfs metadata exists, use --create for default metadata", path.display());
+            return Err(());
+        },
+    };
+    if from == Some(to) {
+        info!("convert: {} is {:?} already", path.display(), to);
+        return Ok(());
+    }
+
+    if let Err(ex) = wsl_file.reopen_to_write() {
+        error!("[ERROR] cannot open file to write, error: {ex:?}");
+        return Err(());
+    }
+    let result = match to {
+        FsType::Lxfs => (|| -> std::io::Result<()> { unsafe {
+            let mut ea_out = EaOut::default();
+            // entries from wslfs have no trailing byte to trim
+            LxfsParsed::from(&wslfs).save_to(&mut ea_out, false)?;
+            // no lxfs EA's exist, remove $LX* and only LX.* carried to LXXATTR
+            for name in [wslfs::LXUID, wslfs::LXGID, wslfs::LXMOD, wslfs::LXDEV] {
+                if ea_parsed.iter().flatten().any(|ea| ea.name == name.as_bytes()) {
+                    ea_out.remove(name.as_bytes());
+                }
+            }
+            for dot_ea in wslfs.lx_dot_ea.iter().filter(|dot_ea| dot_ea.value_unprefixed().is_some()) {
+                ea_out.remove(dot_ea.name_ea());
             }
+            ntfs_io::write_ea(wsl_file.file_handle, &ea_out.buffer)?;
+            if wslfs.reparse_tag.is_some() {
+                wslfs::delete_wslfs_reparse_point(&mut wsl_file)?;
+            }
+            if let Some(symlink) = &wslfs.symlink {
+                write_data(wsl_file.file_handle, symlink.as_bytes())?;
+            }
+            Ok(())
+        } })(),
+        FsType::Wslfs => (|| -> std::io::Result<()> { unsafe {
+            if from.is_none() {
+                lxfs.init_default();
+            }
+            let mut wslfs_new = WslfsParsed::try_from(&lxfs)?;
+            wslfs_new.save(&mut wsl_file)?;
+            let mut ea_out = EaOut::default();
+            for name in [lxfs::LXATTRB, lxfs::LXXATTR] {
+                if ea_parsed.iter().flatten().any(|ea| ea.name == name.as_bytes()) {
+                    ea_out.remove(name.as_bytes());
+                }
+            }
+            if ea_out.count() > 0 {
+                ntfs_io::write_ea(wsl_file.file_handle, &ea_out.buffer)?;
+            }
+            if let Some(file_type) = wslfs_new.reparse_tag.filter(|t| t.tag_id() != 0) {
+                if file_type == StModeType::LNK {
+                    // the symlink target moves from the file content to the reparse point
+                    ntfs_io::truncate_data(wsl_file.file_handle)?;
+                }
+                wslfs::set_wslfs_reparse_point(&mut wsl_file, file_type, wslfs_new.symlink.as_deref())?;
+            }
+            Ok(())
+        } })(),
+    };
+    if let Err(ex) = result {
+        error!("[ERROR] convert {} to {:?}, error: {ex:?}", path.display(), to);
+        return Err(());
+    }
+    if let Err(ex) = wsl_file.restore_file_times() {
+        warning!("[WARNING] cannot restore file times, error: {ex:?}");
+    }
+    match from {
+        Some(from) => info!("convert: {} {:?} --> {:?}", path.display(), from, to),
+        None => info!("convert: {} none --> {:?} with default metadata", path.display(), to),
+    }
+    Ok(())
+}
+
+/// all files in the rootfs of the distro, walked once from the rootfs itself
+fn distro_files(distro: &Distro) -> impl Iterator<Item = PathBuf> {
+    walkdir::WalkDir::new(distro.rootfs_path()).into_iter()
+    .filter_map(Result::ok)
+    .map(walkdir::DirEntry::into_path)
+}
+
+/// the registry Version is not changed if any file failed, stopped by `--fail-fast` or skipped by `--skip-errors`,
+/// cause the distro is half downgraded
+fn downgrade_distro(distro: &mut Distro, check_object_id: bool) -> Result<(), ()> {
+    let mut failures = Failures::new();
+    for path in distro_files(distro) {
+        match downgrade_path(&path, check_object_id) {
+            Ok(()) => {
+                info!("downgrade success: {}", path.display());
+                emit_event(&path, "downgrade", "ok");
+            },
+            Err(ex) => {
+                error!("[ERROR] downgrade {}, error: {ex:?}", path.display());
+                emit_event(&path, "downgrade", "failed");
+                if failures.fail() {
+                    break;
+                }
+            },
         }
     }
-    match distro.set_fs_type(Some(FsType::Lxfs)) {
-        Ok(_) => println!("downgrade success, set {} fs_type(Version) to 1", &distro.name),
-        Err(_) => println!("downgrade fail, set {} fs_type(Version) failed", &distro.name),
-    };
+    if failures.count > 0 {
+        warning!("[NOTE] fs_type(Version) of {} is not changed, run downgrade again after fixing the failed files", &distro.name);
+        return failures.finish("downgrade");
+    }
+    if let Err(msg) = distro.set_fs_type(Some(FsType::Lxfs)) {
+        error!("[ERROR] downgrade fail, set {} fs_type(Version) failed, {}", &distro.name, msg);
+        let _ = failures.finish("downgrade");
+        return Err(());
+    }
+    info!("downgrade success, set {} fs_type(Version) to 1", &distro.name);
+    failures.finish("downgrade")
 }
 
-fn downgrade_path(real_path: &Path) -> std::io::Result<()> {
+fn downgrade_path(real_path: &Path, check_object_id: bool) -> std::io::Result<()> {
     let mut wsl_file = unsafe { wsl_file::open_handle(&real_path, false)? };
-    let ea_buffer = wsl_file.read_ea().unwrap_or(None);
+    let ea_buffer = wsl_file.read_ea()?;
     
     let ea_parsed = ea_buffer.as_ref()
     .map(|ea_buffer| {
@@ -520,16 +3536,17 @@ fn downgrade_path(real_path: &Path) -> std::io::Result<()> {
     let wslfs = wslfs::WslfsParsed::load(&wsl_file, &ea_parsed);
     let lxfs = lxfs::LxfsParsed::load(&wsl_file, &ea_parsed);
 
-    downgrade(&mut wsl_file, &wslfs, &lxfs);
-
-    Ok(())
+    downgrade(&mut wsl_file, &wslfs, &lxfs, check_object_id)
 }
 
-fn downgrade(wsl_file: &mut WslFile,  wslfs: &WslfsParsed, lxfs: &LxfsParsed) {
+/// wslfs to lxfs: writes LXATTRB and LXXATTR, removes $LX* and LX.* EA's and the WSL reparse point,
+/// writes the symlink target as file content; file data, NTFS times, object id and ACL are not touched
+fn downgrade(wsl_file: &mut WslFile,  wslfs: &WslfsParsed, lxfs: &LxfsParsed, check_object_id: bool) -> std::io::Result<()> {
     if lxfs.maybe() {
-        println!("{} maybe lxfs already", unsafe { wsl_file.full_path.Buffer.display() });
-        return;
+        info!("{} maybe lxfs already", unsafe { wsl_file.full_path.Buffer.display() });
+        return Ok(());
     }
+    let object_id = check_object_id.then(|| unsafe { ntfs_io::query_object_id(wsl_file.file_handle) });
     let mut ea_to_remove = vec![
         wslfs::LXUID.as_bytes(),        
         wslfs::LXGID.as_bytes(),
@@ -537,42 +3554,24 @@ fn downgrade(wsl_file: &mut WslFile,  wslfs: &WslfsParsed, lxfs: &LxfsParsed) {
         wslfs::LXDEV.as_bytes()
     ];
 
-    let mut ea_out = EaOut::default();
-
     // 1. for all files, set LXATTRB
-    let mut lxattrb = EaLxattrbV1::new(&wsl_file.basic_file_info);
-
-    lxattrb.st_uid = wslfs.get_uid().unwrap_or(0);
-    lxattrb.st_gid = wslfs.get_gid().unwrap_or(0);
-    lxattrb.st_mode = wslfs.get_mode().unwrap_or(0);
-
-    let dev_major = wslfs.get_dev_major().unwrap_or(0);
-    let dev_minor = wslfs.get_dev_minor().unwrap_or(0);
-    lxattrb.st_rdev = lxfs::make_dev(dev_major, dev_minor);
-
-    let lxattrb_bytes = unsafe {
-		std::slice::from_raw_parts(
-			&lxattrb as *const _ as *const u8,
-			std::mem::size_of_val(&lxattrb)
-		)
-	};
-    ea_out.add(LXATTRB.as_bytes(), lxattrb_bytes);
-
     // 2. for all files, set LXXATTR, from LX.*
-    let mut lxxattr_out = LxxattrOut::default();
-    for dot_ea in &wslfs.lx_dot_ea {
+    let mut ea_out = EaOut::default();
+    let mut lxfs_new = LxfsParsed::from(wslfs);
+    // entries from wslfs have no trailing byte to trim
+    lxfs_new.save_to(&mut ea_out, false)?;
+    // only LX.* carried to LXXATTR
+    for dot_ea in wslfs.lx_dot_ea.iter().filter(|dot_ea| dot_ea.value_unprefixed().is_some()) {
         ea_to_remove.push(&dot_ea.name_ea());
-        lxxattr_out.add(&dot_ea.name(), &dot_ea.value());
     }
-    ea_out.add(LXXATTR.as_bytes(), &lxxattr_out.buffer);
 
     // write EA
     for ea in ea_to_remove {
-        ea_out.add(ea,"".as_bytes());
+        ea_out.remove(ea);
     }
     unsafe {
-        let _ = wsl_file.reopen_to_write();
-        let _ = ntfs_io::write_ea(wsl_file.file_handle, &ea_out.buffer);
+        wsl_file.reopen_to_write()?;
+        ntfs_io::write_ea(wsl_file.file_handle, &ea_out.buffer)?;
     }
 
     // 3. special files, remove sparse point
@@ -580,7 +3579,7 @@ fn downgrade(wsl_file: &mut WslFile,  wslfs: &WslfsParsed, lxfs: &LxfsParsed) {
         if  t != StModeType::UNKNOWN {
             use wslfs::WslfsReparseTag;
             unsafe {
-                let _ = delete_reparse_point(wsl_file.file_handle, t.tag_id());
+                delete_reparse_point(wsl_file.file_handle, t.tag_id())?;
             }
         }
     }
@@ -588,22 +3587,672 @@ fn downgrade(wsl_file: &mut WslFile,  wslfs: &WslfsParsed, lxfs: &LxfsParsed) {
     // 4. symlink files, write file content
     if let Some(ref symlink) = wslfs.symlink {
         unsafe {
-            let _ = write_data(wsl_file.file_handle, symlink.as_bytes());
+            write_data(wsl_file.file_handle, symlink.as_bytes())?;
+        }
+    }
+
+    // 5. the object id is kept by NTFS, make sure of it
+    match object_id {
+        Some(Ok(before)) => match unsafe { ntfs_io::query_object_id(wsl_file.file_handle) } {
+            Ok(after) if after == before => {},
+            Ok(after) => warning!("[WARNING] object id of {} changed by downgrade: {:02x?} --> {:02x?}",
+                unsafe { wsl_file.full_path.Buffer.display() }, before, after),
+            Err(ex) => warning!("[WARNING] cannot read object id of {} after downgrade, error: {ex:?}", unsafe { wsl_file.full_path.Buffer.display() }),
+        },
+        Some(Err(ex)) => warning!("[WARNING] cannot read object id of {} before downgrade, error: {ex:?}", unsafe { wsl_file.full_path.Buffer.display() }),
+        None => {},
+    }
+    Ok(())
+}
+
+/// only wslfs, fields of lxfs are in LXATTRB and cannot be deleted
+fn clear_attr(path: &Path, fields: &[wslfs::WslfsField], distro_name: Option<&String>) -> Result<(), ()> {
+    let distro = try_load_distro(distro_name, Some(path), false).map_err(|err| err.report(false))?;
+    let mut wsl_file = load_wsl_file(path, distro.as_ref()).map_err(|err| err.report(false))?;
+    let ea_buffer = wsl_file.read_ea().map_err(|source| WslAttrError::ReadEa { path: path.to_path_buf(), source }.report(false))?;
+    let ea_parsed = ea_buffer.as_ref().map(|ea_buffer| ea_parse::parse_ea(&ea_buffer));
+    let mut wslfs = WslfsParsed::load(&wsl_file, &ea_parsed);
+
+    let cleared: Vec<_> = fields.iter().filter(|field| wslfs.clear(**field)).collect();
+    if cleared.is_empty() {
+        info!("clear_attr: no change");
+        return Ok(());
+    }
+
+    if let Err(ex) = wsl_file.reopen_to_write() {
+        error!("[ERROR] cannot open file to write, error: {ex:?}");
+        return Err(());
+    }
+    if let Err(ex) = wslfs.save(&mut wsl_file) {
+        error!("[ERROR] clear_attr, error: {ex:?}");
+        return Err(());
+    }
+    if let Err(ex) = wsl_file.restore_file_times() {
+        warning!("[WARNING] cannot restore file times, error: {ex:?}");
+    }
+    for field in cleared {
+        info!("clear_attr: {} deleted", field.ea_name());
+    }
+    Ok(())
+}
+
+/// EA's of wslfs or lxfs metadata, and wslfs extended attributes
+fn is_wsl_ea_name(name: &[u8]) -> bool {
+    [wslfs::LXUID, wslfs::LXGID, wslfs::LXMOD, wslfs::LXDEV, lxfs::LXATTRB, lxfs::LXXATTR].iter().any(|n| n.as_bytes() == name)
+    || name.starts_with(wslfs::LX_DOT.as_bytes())
+}
+
+fn strip(path: &Path, distro_name: Option<&String>, yes: bool) -> Result<(), ()> {
+    use wslfs::WslfsReparseTag;
+
+    let distro = try_load_distro(distro_name, Some(path), false).map_err(|err| err.report(false))?;
+    let mut wsl_file = load_wsl_file(path, distro.as_ref()).map_err(|err| err.report(false))?;
+    let ea_buffer = wsl_file.read_ea().map_err(|source| WslAttrError::ReadEa { path: path.to_path_buf(), source }.report(false))?;
+    let ea_parsed = ea_buffer.as_ref().map(|ea_buffer| ea_parse::parse_ea(&ea_buffer)).unwrap_or_default();
+    let ea_to_remove: Vec<&[u8]> = ea_parsed.iter().map(|ea| ea.name).filter(|name| is_wsl_ea_name(name)).collect();
+    let reparse_tag = wsl_file.reparse_tag.filter(|t| StModeType::from_tag_id(*t) != StModeType::UNKNOWN);
+
+    if ea_to_remove.is_empty() && reparse_tag.is_none() {
+        info!("strip: no WSL metadata");
+        return Ok(());
+    }
+    for name in &ea_to_remove {
+        info!("  EA {}", String::from_utf8_lossy(name));
+    }
+    if let Some(t) = reparse_tag {
+        info!("  reparse point {:#010x} / {}", t, StModeType::from_tag_id(t).name().0);
+        if t == wslfs::IO_REPARSE_TAG_LX_SYMLINK {
+            warning!("[WARNING] the symlink target is dropped with the reparse point");
+        }
+    }
+    if !yes && !confirm(&format!("strip {} EAs{} of {}", ea_to_remove.len(), if reparse_tag.is_some() { " and the reparse point" } else { "" }, path.display())) {
+        info!("strip canceled");
+        return Ok(());
+    }
+
+    if let Err(ex) = wsl_file.reopen_to_write() {
+        error!("[ERROR] cannot open file to write, error: {ex:?}");
+        return Err(());
+    }
+    let mut ea_out = EaOut::default();
+    for name in &ea_to_remove {
+        ea_out.remove(name);
+    }
+    if !ea_to_remove.is_empty() {
+        if let Err(ex) = unsafe { ntfs_io::write_ea(wsl_file.file_handle, &ea_out.buffer) } {
+            error!("[ERROR] strip EAs, error: {ex:?}");
+            return Err(());
+        }
+    }
+    if reparse_tag.is_some() {
+        if let Err(ex) = unsafe { wslfs::delete_wslfs_reparse_point(&mut wsl_file) } {
+            error!("[ERROR] strip reparse point, error: {ex:?}");
+            return Err(());
         }
     }
+    if let Err(ex) = wsl_file.restore_file_times() {
+        warning!("[WARNING] cannot restore file times, error: {ex:?}");
+    }
+    info!("strip: {} EAs{} removed", ea_to_remove.len(), if reparse_tag.is_some() { " and the reparse point" } else { "" });
+    Ok(())
+}
+
+fn set_case_sensitive(path: &Path, case_sensitive: bool, distro_name: Option<&String>) -> Result<(), ()> {
+    let distro = try_load_distro(distro_name, Some(path), false).map_err(|err| err.report(false))?;
+    let mut wsl_file = load_wsl_file(path, distro.as_ref()).map_err(|err| err.report(false))?;
+    if !wsl_file.is_dir() {
+        error!("[ERROR] case sensitivity applies to directories only: {}", path.display());
+        return Err(());
+    }
+    let state = if case_sensitive { "on" } else { "off" };
+    if ntfs_io::query_case_sensitive(wsl_file.file_handle).ok() == Some(case_sensitive) {
+        info!("case sensitive: already {}", state);
+        return Ok(());
+    }
+    if let Err(ex) = wsl_file.reopen_to_write() {
+        error!("[ERROR] cannot open file to write, error: {ex:?}");
+        return Err(());
+    }
+    if let Err(ex) = ntfs_io::set_case_sensitive(wsl_file.file_handle, case_sensitive) {
+        error!("[ERROR] cannot set case sensitive {}, turning off fails if names differ only in case, error: {ex:?}", state);
+        return Err(());
+    }
+    if let Err(ex) = wsl_file.restore_file_times() {
+        warning!("[WARNING] cannot restore file times, error: {ex:?}");
+    }
+    info!("case sensitive: {}", state);
+    Ok(())
+}
+
+fn win_attr(path: &Path, changes: &[String], distro_name: Option<&String>) -> Result<(), ()> {
+    let (set, clear) = win_attr::parse_changes(changes).map_err(|msg| error!("[ERROR] {}", msg))?;
+    let distro = try_load_distro(distro_name, Some(path), true).map_err(|err| err.report(false))?;
+    let real_path = get_real_path(path, distro.as_ref()).map_err(|err| err.report(false))?;
+    match win_attr::set_file_attributes(&real_path, set, clear) {
+        Ok((old, new)) if old == new => println!("Windows Attributes: {}", win_attr::fmt_file_attributes(new)),
+        Ok((old, new)) => println!("Windows Attributes: {} --> {}", win_attr::fmt_file_attributes(old), win_attr::fmt_file_attributes(new)),
+        Err(ex) => {
+            error!("[ERROR] cannot change Windows attributes of {}, error: {ex:?}", path.display());
+            return Err(());
+        },
+    }
+    Ok(())
+}
+
+/// NTFS attributes for `view`, a readonly file cannot be written by WSL whatever its mode is
+fn print_win_attributes(f: &mut dyn std::io::Write, wsl_file: &WslFile, opts: &FmtOptions) -> std::io::Result<()> {
+    let Some(fbi) = wsl_file.basic_file_info.filter(|_| !opts.only_times) else {
+        return Ok(());
+    };
+    writeln!(f, "{}{}", opts.label("Windows Attributes:"), win_attr::fmt_file_attributes(fbi.FileAttributes))
+}
+
+/// case sensitivity flag of a directory for `view`, nothing for other files
+fn print_case_sensitive(f: &mut dyn std::io::Write, wsl_file: &WslFile, opts: &FmtOptions) -> std::io::Result<()> {
+    if !wsl_file.is_dir() || opts.only_times {
+        return Ok(());
+    }
+    match ntfs_io::query_case_sensitive(wsl_file.file_handle) {
+        Ok(case_sensitive) => writeln!(f, "{}{}", opts.label("Case Sensitive:"), if case_sensitive { "yes" } else { "no" }),
+        Err(ex) => writeln!(f, "[ERROR] cannot query case sensitivity, error: {ex:?}"),
+    }
+}
+
+/// `--compact` or `--width`, otherwise compact when stdout is a console narrower than 80 columns,
+/// colors of `--color auto` need a console accepting ANSI escape codes
+fn fmt_options(args: &ArgsView) -> FmtOptions {
+    let width = if args.compact {
+        FmtOptions::COMPACT.width
+    } else if let Some(width) = args.width {
+        width
+    } else if args.output.is_none() && console_width().is_some_and(|w| w < 80) {
+        FmtOptions::COMPACT.width
+    } else {
+        FmtOptions::default().width
+    };
+    let max_value_len = if args.full { 0 } else { args.max_value_len };
+    let color = match args.color {
+        ColorWhen::Always => {
+            console::enable_virtual_terminal();
+            true
+        },
+        ColorWhen::Never => false,
+        ColorWhen::Auto => args.output.is_none() && console::enable_virtual_terminal(),
+    };
+    FmtOptions { width, max_value_len, hide_atime: args.hide_atime, only_times: args.only_times, color, name_filter: args.name_filter.clone(), epoch_time: args.epoch_time }
+}
+
+/// columns of the console window, `None` if stdout is not a console
+fn console_width() -> Option<usize> {
+    use windows::Win32::System::Console::{GetConsoleScreenBufferInfo, GetStdHandle, CONSOLE_SCREEN_BUFFER_INFO, STD_OUTPUT_HANDLE};
+    unsafe {
+        let handle = GetStdHandle(STD_OUTPUT_HANDLE).ok()?;
+        let mut info = CONSOLE_SCREEN_BUFFER_INFO::default();
+        GetConsoleScreenBufferInfo(handle, &mut info).ok()?;
+        Some((info.srWindow.Right - info.srWindow.Left + 1) as usize)
+    }
 }
 
-fn print_file_time(wsl_file: &WslFile) {
+fn print_file_time(f: &mut dyn std::io::Write, wsl_file: &WslFile, opts: &FmtOptions) -> std::io::Result<()> {
     if let Some(fbi) = wsl_file.basic_file_info {
         let creation_time: LxfsTime = (fbi.CreationTime as u64).into();
-        println!("{:28}{}", "CreationTime:", creation_time);
-        let last_access_time: LxfsTime = (fbi.LastAccessTime as u64).into();
-        println!("{:28}{}", "LastAccessTime:", last_access_time);
+        writeln!(f, "{}{}", opts.label("CreationTime:"), opts.time(creation_time, "NTFS"))?;
+        if !opts.hide_atime {
+            let last_access_time: LxfsTime = (fbi.LastAccessTime as u64).into();
+            writeln!(f, "{}{}", opts.label("LastAccessTime:"), opts.time(last_access_time, "NTFS"))?;
+        }
         let last_write_time: LxfsTime = (fbi.LastWriteTime as u64).into();
-        println!("{:28}{}", "LastWriteTime:", last_write_time);
+        writeln!(f, "{}{}", opts.label("LastWriteTime:"), opts.time(last_write_time, "NTFS"))?;
         let change_time: LxfsTime = (fbi.ChangeTime as u64).into();
-        println!("{:28}{}", "ChangeTime:", change_time);
+        writeln!(f, "{}{}", opts.label("ChangeTime:"), opts.time(change_time, "NTFS"))?;
     } else {
-        println!("[ERROR] cannot query file times")
+        writeln!(f, "[ERROR] cannot query file times")?;
+    }
+    Ok(())
+}
+
+#[test]
+fn test_select_fs_type() {
+    use ea_parse::parse_ea;
+
+    let load = |ea_store: &MemEaStore| {
+        let ea_buffer = ea_store.read_ea().unwrap();
+        let ea_parsed = ea_buffer.as_ref().map(|ea_buffer| parse_ea(ea_buffer));
+        let wslfs = WslfsParsed::load(ea_store, &ea_parsed);
+        let lxfs = LxfsParsed::load(ea_store, &ea_parsed);
+        select_fs_type(None, None, &wslfs, &lxfs)
+    };
+
+    let mut ea_store = MemEaStore::default();
+    assert!(load(&ea_store).is_err());
+
+    let mut ea_out = EaOut::default();
+    ea_out.add(wslfs::LXUID.as_bytes(), &1000u32.to_le_bytes());
+    ea_store.write_ea(&ea_out.buffer).unwrap();
+    assert_eq!(load(&ea_store), Ok(FsType::Wslfs));
+
+    let mut lxfs = LxfsParsed::default();
+    lxfs.set_uid(1000);
+    lxfs.save(&mut ea_store).unwrap();
+    assert!(load(&ea_store).is_err());
+
+    let mut ea_out = EaOut::default();
+    ea_out.remove(wslfs::LXUID.as_bytes());
+    ea_store.write_ea(&ea_out.buffer).unwrap();
+    assert_eq!(load(&ea_store), Ok(FsType::Lxfs));
+
+    let wslfs = WslfsParsed::default();
+    assert_eq!(select_fs_type(Some(FsType::Wslfs), None, &wslfs, &LxfsParsed::default()), Ok(FsType::Wslfs));
+}
+
+#[test]
+fn test_load_nonexistent_file_json_error() {
+    let path = std::env::temp_dir().join("wslattr-nonexistent").join("file");
+    let err = load_wsl_file(&path, None).err().expect("nonexistent file must fail");
+    assert_eq!(err.kind(), "open");
+    let json = err.to_json();
+    assert!(json.starts_with("{\"error\":{\"kind\":\"open\",\"message\":\"cannot open file: "));
+    let mut path_json = String::new();
+    escape_utils::escape_json_str(&path.to_string_lossy(), &mut path_json).unwrap();
+    assert!(json.ends_with(&format!("\"path\":\"{}\"}}}}", path_json)));
+}
+
+#[test]
+fn test_is_wsl_ea_name() {
+    for name in ["$LXUID", "$LXGID", "$LXMOD", "$LXDEV", "LXATTRB", "LXXATTR", "LX.USER.TEST"] {
+        assert!(is_wsl_ea_name(name.as_bytes()), "{}", name);
+    }
+    for name in ["$KERNEL.PURGE.ESBCACHE", "USER.TEST", "LXATTRB2", "LX"] {
+        assert!(!is_wsl_ea_name(name.as_bytes()), "{}", name);
+    }
+}
+
+#[test]
+fn test_get_real_path_errors() {
+    let kind = |path: &str, distro: Option<&Distro>| get_real_path(Path::new(path), distro).err().map(|err| err.kind());
+    let d = Distro {
+        name: "Arch".to_owned(),
+        base_path: PathBuf::from(r"C:\wsl\Arch"),
+        fs_type: Some(FsType::Lxfs),
+        flags: None,
+        reg_key: None,
+        rootfs: None,
+        source: DistroSource::Arg,
+        users: None,
+        groups: None,
+    };
+
+    assert_eq!(kind("/usr/bin", None), Some("unix_path_without_distro"));
+    assert_eq!(get_real_path(Path::new("/usr/bin"), Some(&d)).ok(), Some(PathBuf::from(r"C:\wsl\Arch\rootfs\usr\bin")));
+    assert_eq!(kind(r"\\wsl$\Arch\usr", None), Some("unc_path_without_distro"));
+    assert_eq!(kind(r"\\wsl$\Ubuntu\usr", Some(&d)), Some("distro_mismatch"));
+    assert_eq!(kind(r"\\remote\share\file", None), Some("unsupported_path"));
+    assert_eq!(kind("", None), Some("invalid_path"));
+}
+
+#[test]
+fn test_get_real_path_distro_root() {
+    let d = Distro {
+        name: "Arch".to_owned(),
+        base_path: PathBuf::from(r"C:\wsl\Arch"),
+        fs_type: Some(FsType::Lxfs),
+        flags: None,
+        reg_key: None,
+        rootfs: None,
+        source: DistroSource::Arg,
+        users: None,
+        groups: None,
+    };
+    let real_path = |path: &str| get_real_path(Path::new(path), Some(&d)).ok();
+    let rootfs = Some(PathBuf::from(r"C:\wsl\Arch\rootfs"));
+
+    assert_eq!(real_path("/"), rootfs);
+    assert_eq!(real_path("/."), rootfs);
+    assert_eq!(real_path("/.."), rootfs);
+    assert_eq!(real_path(r"\\wsl$\Arch\"), rootfs);
+    assert_eq!(real_path(r"\\wsl$\Arch"), rootfs);
+    assert_eq!(real_path(r"\\wsl.localhost\Arch\."), rootfs);
+    assert_eq!(real_path("/usr/../etc"), Some(PathBuf::from(r"C:\wsl\Arch\rootfs\etc")));
+}
+
+#[test]
+fn test_try_load_distro_from_unc_path() {
+    let base_path = std::env::temp_dir().join("wslattr-test-try-load-distro-unc");
+    let o = distro::parse_distro_override(&format!("fixture={},Wslfs", base_path.display())).unwrap();
+    let o = Some(&o);
+
+    let d = try_load_distro_with(None::<&str>, Some(r"\\wsl$\fixture\etc\passwd"), false, false, o).unwrap().unwrap();
+    assert_eq!((d.name.as_str(), d.source), ("fixture", DistroSource::FilePathUNC));
+    let d = try_load_distro_with(None::<&str>, Some(r"\\wsl.localhost\FIXTURE\etc"), false, false, o).unwrap().unwrap();
+    assert_eq!(d.name, "fixture");
+    assert_eq!(get_real_path(Path::new(r"\\wsl$\FIXTURE\etc"), Some(&d)).ok(), Some(base_path.join("rootfs").join("etc")));
+
+    let d = try_load_distro_with(Some("fixture"), Some(r"\\wsl$\fixture\etc"), false, false, o).unwrap().unwrap();
+    assert_eq!(d.source, DistroSource::Arg);
+    assert!(get_real_path(Path::new(r"\\wsl$\other\etc"), Some(&d)).is_err_and(|err| err.kind() == "distro_mismatch"));
+
+    // no fallback to the distro of current dir or the default one
+    assert!(try_load_distro_with(None::<&str>, Some(r"\\wsl$\no-such-distro\etc"), false, false, o).unwrap().is_none());
+    // a typo in `--distro` is an error, not a panic
+    let err = try_load_distro_with(Some("no-such-distro"), None::<&str>, false, false, o).unwrap_err();
+    assert_eq!(err.kind(), "distro_not_found");
+}
+
+#[test]
+fn test_no_distro() {
+    let kind = |path: &str| get_real_path_with(Path::new(path), None, true).err().map(|err| err.kind());
+    assert_eq!(kind("/usr/bin"), Some("distro_disabled"));
+    assert_eq!(kind(r"\\wsl$\Arch\usr"), Some("distro_disabled"));
+
+    // not even the override is looked up
+    let base_path = std::env::temp_dir().join("wslattr_test_no_distro");
+    let o = distro::parse_distro_override(&format!("fixture={},Wslfs", base_path.display())).unwrap();
+    assert!(try_load_distro_with(None::<&str>, Some(r"\\wsl$\fixture\etc"), false, true, Some(&o)).unwrap().is_none());
+    assert!(try_load_distro_with(None::<&str>, Some("/etc"), true, true, Some(&o)).unwrap().is_none());
+
+    // --preserve-root guesses the rootfs by etc/passwd
+    let rootfs = base_path.join("rootfs");
+    std::fs::create_dir_all(rootfs.join("etc")).unwrap();
+    std::fs::write(rootfs.join("etc").join("passwd"), "root:x:0:0:root:/root:/bin/sh\n").unwrap();
+    let guessed = (looks_like_rootfs(&rootfs), looks_like_rootfs(&rootfs.join("etc")), looks_like_rootfs(&base_path));
+    std::fs::remove_dir_all(&base_path).unwrap();
+    assert_eq!(guessed, (true, false, false));
+}
+
+#[test]
+fn test_open_directory_root() {
+    // like a rootfs, and the disk root r"C:\"
+    let root = std::env::temp_dir().join("wslattr_test_open_directory_root");
+    std::fs::create_dir_all(&root).unwrap();
+    let disk_root: PathBuf = root.components().take(2).collect();
+    for path in [&root, &disk_root] {
+        let wsl_file = unsafe { open_handle(path, false) }.unwrap();
+        assert!(wsl_file.is_dir(), "{}", path.display());
+    }
+    std::fs::remove_dir_all(&root).unwrap();
+}
+
+#[test]
+fn test_decode_ea_dump() {
+    use base64::Engine;
+
+    let mut ea_out = EaOut::default();
+    ea_out.add(b"$LXUID", &1000u32.to_le_bytes());
+    let raw = ea_out.buffer;
+    let hex: String = raw.iter().map(|b| format!("{:02x}", b)).collect();
+    let wrapped = format!("0X{}\n{}\n", &hex[..8], &hex[8..]);
+    assert_eq!(decode_ea_dump(&raw, ValueFormat::Text), Some(raw.clone()));
+    assert_eq!(decode_ea_dump(hex.as_bytes(), ValueFormat::Hex), Some(raw.clone()));
+    assert_eq!(decode_ea_dump(wrapped.as_bytes(), ValueFormat::Hex), Some(raw.clone()));
+    assert_eq!(decode_ea_dump(b"zz", ValueFormat::Hex), None);
+
+    let b64 = base64::engine::general_purpose::STANDARD.encode(&raw);
+    assert_eq!(decode_ea_dump(b64.as_bytes(), ValueFormat::Base64), Some(raw.clone()));
+    assert_eq!(decode_ea_dump(format!("0s{}\r\n", b64).as_bytes(), ValueFormat::Base64), Some(raw.clone()));
+
+    let ea_store = MemEaStore::from_ea_buffer(&raw);
+    let ea_buffer = ea_store.read_ea().unwrap();
+    let ea_parsed = ea_buffer.as_ref().map(|ea_buffer| ea_parse::parse_ea(ea_buffer));
+    assert_eq!(WslfsParsed::load(&ea_store, &ea_parsed).get_uid(), Some(1000));
+}
+
+#[test]
+fn test_time_drift() {
+    let t = |sec: u64, nsec: u32| LxfsTime::new(sec, nsec);
+    let ntfs = |lt: LxfsTime| time_utils::lxfs_time_to_u64(lt) as i64;
+    let fbi = FILE_BASIC_INFORMATION {
+        LastAccessTime: ntfs(t(1700000000, 0)),
+        LastWriteTime: ntfs(t(1700000100, 500)),
+        ChangeTime: ntfs(t(1700000200, 0)),
+        ..Default::default()
+    };
+    // nanoseconds below the 100ns of NTFS are no drift
+    assert!(time_drift(&fbi, (t(1700000000, 0), t(1700000100, 523), t(1700000200, 0)), false).is_empty());
+
+    let drift = time_drift(&fbi, (t(1600000000, 0), t(1700000100, 500), t(1700000300, 0)), false);
+    assert_eq!(drift, vec![
+        ("atime", t(1700000000, 0), t(1600000000, 0)),
+        ("ctime", t(1700000200, 0), t(1700000300, 0)),
+    ]);
+    assert_eq!(time_drift(&fbi, (t(1600000000, 0), t(1700000100, 500), t(1700000200, 0)), true), vec![]);
+}
+
+#[test]
+fn test_capabilities_json() {
+    let json = capabilities_json();
+    assert!(json.starts_with(&format!(r#"{{"version":"{}","fs_types":["lxfs","wslfs"],"reparse_tags":[{{"tag":"0x80000024","type":"FIFO"}},"#, env!("CARGO_PKG_VERSION"))), "{}", json);
+    assert!(json.contains(r#"{"tag":"0xa000001d","type":"SYMLINK"}"#));
+    assert!(json.contains(r#""commands":["view","#));
+    assert!(json::document(&json).starts_with(r#"{"schema_version":1,"version":"#));
+}
+
+#[test]
+fn test_event_json() {
+    assert_eq!(event_json(Path::new(r"usr\bin"), "chmod", Change::Written.event_result()),
+        r#"{"path":"usr\\bin","action":"chmod","result":"ok"}"#);
+}
+
+#[test]
+fn test_distro_files() {
+    let base_path = std::env::temp_dir().join("wslattr_test_distro_files");
+    let _ = std::fs::remove_dir_all(&base_path);
+    std::fs::create_dir_all(base_path.join("rootfs").join("etc")).unwrap();
+    std::fs::write(base_path.join("rootfs").join("etc").join("passwd"), b"").unwrap();
+    std::fs::write(base_path.join("ext4.vhdx"), b"").unwrap();
+    let distro = Distro {
+        name: "test".to_owned(),
+        base_path: base_path.clone(),
+        fs_type: Some(FsType::Wslfs),
+        flags: None,
+        reg_key: None,
+        rootfs: None,
+        source: DistroSource::Unknown,
+        users: None,
+        groups: None,
+    };
+
+    let rootfs = base_path.join("rootfs");
+    let files: Vec<PathBuf> = distro_files(&distro).collect();
+    assert_eq!(files, vec![rootfs.clone(), rootfs.join("etc"), rootfs.join("etc").join("passwd")]);
+
+    std::fs::remove_dir_all(&base_path).unwrap();
+}
+
+#[test]
+fn test_side_by_side() {
+    let out = side_by_side(("wslfs:", "$LXUID: 1000\n$LXMOD: \x1b[01;34mdrwxr-xr-x\x1b[0m\n"), ("lxfs:", ""));
+    assert_eq!(out, concat!(
+        "wslfs:             | lxfs:\n",
+        "$LXUID: 1000       | none\n",
+        "$LXMOD: \x1b[01;34mdrwxr-xr-x\x1b[0m |\n",
+    ));
+}
+
+#[test]
+fn test_failures_fail_fast_and_skip_errors() {
+    // 2 of 5 files fail, the walk order is not sorted
+    let root = std::env::temp_dir().join("wslattr_test_failures_walk");
+    std::fs::create_dir_all(&root).unwrap();
+    for name in ["a", "b", "c", "d", "e"] {
+        std::fs::write(root.join(name), name).unwrap();
+    }
+    let is_failing = |path: &Path| path == Path::new("c") || path == Path::new("e");
+    let args = ArgsRecursive { recursive: true, max_depth: None, cross_boundaries: false };
+    let run = |fail_fast: bool| {
+        let mut failures = Failures { fail_fast, count: 0 };
+        let mut visited = Vec::new();
+        walk_files(&root, &args, &mut failures, |path, _wsl_file| {
+            visited.push(path.to_path_buf());
+            if is_failing(path) { Err(()) } else { Ok(()) }
+        });
+        (visited, failures)
+    };
+
+    // stopped right at the first failed file
+    let (visited, failures) = run(true);
+    assert!(visited.last().is_some_and(|p| is_failing(p)));
+    assert_eq!(visited.iter().filter(|p| is_failing(p)).count(), 1);
+    assert_eq!((failures.count, failures.stopped(), failures.finish("test")), (1, true, Err(())));
+
+    // the root "." and all 5 files
+    let (visited, failures) = run(false);
+    assert_eq!(visited.len(), 6);
+    assert_eq!((failures.count, failures.stopped(), failures.finish("test")), (2, false, Err(())));
+
+    let failures = Failures::default();
+    assert!(!failures.stopped());
+    assert_eq!(failures.finish("test"), Ok(()));
+    std::fs::remove_dir_all(&root).unwrap();
+}
+
+#[test]
+fn test_walk_files_stops_at_junction() {
+    let root = std::env::temp_dir().join("wslattr_test_walk_junction");
+    let outside = std::env::temp_dir().join("wslattr_test_walk_junction_target");
+    let _ = std::fs::remove_dir_all(&root);
+    std::fs::create_dir_all(&root).unwrap();
+    std::fs::create_dir_all(&outside).unwrap();
+    std::fs::write(root.join("a"), b"").unwrap();
+    std::fs::write(outside.join("b"), b"").unwrap();
+    let status = std::process::Command::new("cmd")
+        .args(["/C", "mklink", "/J"]).arg(root.join("j")).arg(&outside)
+        .stdout(std::process::Stdio::null())
+        .status().unwrap();
+    assert!(status.success());
+
+    let args = ArgsRecursive { recursive: true, max_depth: None, cross_boundaries: false };
+    let mut failures = Failures::default();
+    let mut visited = Vec::new();
+    walk_files(&root, &args, &mut failures, |path, _wsl_file| {
+        visited.push(path.to_path_buf());
+        Ok(())
+    });
+    let counted = count_files(&root, &args);
+    // the junction only, its target is left
+    std::fs::remove_dir(root.join("j")).unwrap();
+    std::fs::remove_dir_all(&root).unwrap();
+    std::fs::remove_dir_all(&outside).unwrap();
+
+    visited.sort();
+    assert_eq!(visited, vec![PathBuf::from("."), PathBuf::from("a")]);
+    assert_eq!(counted, 2);
+}
+
+#[test]
+fn test_porcelain_lines() {
+    let mut lxfs = LxfsParsed::default();
+    lxfs.set_mode(Mode(0o100644));
+    lxfs.set_attr("user.removed", b"old");
+    let before = porcelain_fields(&lxfs);
+
+    lxfs.set_mode(Mode(0o100755));
+    lxfs.rm_attr("user.removed");
+    lxfs.set_attr("user.tab", b"a\tb");
+    let lines = porcelain_lines("chmod", Path::new("dir/a b"), FsType::Lxfs, &before, &porcelain_fields(&lxfs));
+    assert_eq!(lines, vec![
+        "chmod\tdir/a b\tlxfs\tmode\t0100644\t0100755",
+        "chmod\tdir/a b\tlxfs\tattr:user.tab\t-\t\"a\\011b\"",
+        "chmod\tdir/a b\tlxfs\tattr:user.removed\t\"old\"\t-",
+    ]);
+
+    assert!(porcelain_lines("chown", Path::new("a"), FsType::Lxfs, &before, &before).is_empty());
+}
+
+#[test]
+fn test_fmt_raw_ea_single_name() {
+    let mut ea_out = EaOut::default();
+    ea_out.add(b"LXATTRB", &[1; 56]);
+    ea_out.add(b"LXATTRB2", &[2; 4]);
+    ea_out.add(b"LXXATTR", b"x");
+    ea_out.add(b"$LXUID", &[0; 4]);
+    let ea_parsed = ea_parse::parse_ea(&ea_out.buffer);
+
+    let mut out = vec![];
+    fmt_raw_ea(&mut out, None, &ea_parsed, ValueFormat::Hex, &FmtOptions { name_filter: Some("LXATTRB".to_owned()), ..FmtOptions::COMPACT }).unwrap();
+    let out = String::from_utf8(out).unwrap();
+    assert!(out.contains("  LXATTRB "), "{}", out);
+    assert!(!out.contains("LXATTRB2"), "{}", out);
+    assert!(!out.contains("LXXATTR"), "{}", out);
+    assert!(!out.contains("$LXUID"), "{}", out);
+}
+
+#[test]
+fn test_confirm_answer_and_changed_fields() {
+    assert_eq!(ConfirmAnswer::parse("y\n"), Some(ConfirmAnswer::Yes));
+    assert_eq!(ConfirmAnswer::parse(" N\r\n"), Some(ConfirmAnswer::No));
+    assert_eq!(ConfirmAnswer::parse("all"), Some(ConfirmAnswer::All));
+    assert_eq!(ConfirmAnswer::parse("q"), Some(ConfirmAnswer::Quit));
+    assert_eq!(ConfirmAnswer::parse(""), None);
+    assert_eq!(ConfirmAnswer::parse("x"), None);
+
+    let field = |f: &str, v: &str| (f.to_owned(), v.to_owned());
+    let before = [field("mode", "0100644"), field("uid", "0"), field("attr:user.a", "\"x\"")];
+    let after = [field("mode", "0100755"), field("uid", "0"), field("attr:user.b", "\"y\"")];
+    let change = |f: &str, old: &str, new: &str| (f.to_owned(), old.to_owned(), new.to_owned());
+    assert_eq!(changed_fields(&before, &after), vec![
+        change("mode", "0100644", "0100755"),
+        change("attr:user.b", "-", "\"y\""),
+        change("attr:user.a", "\"x\"", "-"),
+    ]);
+}
+
+#[test]
+fn test_chmod_keeps_file_type() {
+    use ea_parse::parse_ea;
+
+    // a tree of wslfs files as recursive chmod walks it, each file keeps its own type
+    let root = std::env::temp_dir().join("wslattr_test_chmod_keeps_file_type");
+    let _ = std::fs::remove_dir_all(&root);
+    std::fs::create_dir_all(root.join("dir")).unwrap();
+    std::fs::write(root.join(r"dir\reg"), b"").unwrap();
+    for (name, file_type, symlink) in [(r"dir\lnk", StModeType::LNK, Some("reg")), (r"dir\chr", StModeType::CHR, None)] {
+        std::fs::write(root.join(name), b"").unwrap();
+        let mut wsl_file = unsafe { open_handle(&root.join(name), true) }.unwrap();
+        unsafe { wslfs::set_wslfs_reparse_point(&mut wsl_file, file_type, symlink) }.unwrap();
     }
+
+    let args = Args::try_parse_from(["wslattr", "chmod", "-R", "-y", "--fs-type", "wslfs", "755", root.to_str().unwrap()]).unwrap();
+    let Some(Command::Chmod { args_change, modes }) = args.command else {
+        unreachable!()
+    };
+    chmod(args_change, modes);
+
+    let mode_of = |name: &str| {
+        let wsl_file = unsafe { open_handle(&root.join(name), false) }.unwrap();
+        let ea_buffer = wsl_file.read_ea().unwrap();
+        let ea_parsed = ea_buffer.as_ref().map(|ea_buffer| parse_ea(ea_buffer));
+        WslfsParsed::load(&wsl_file, &ea_parsed).get_mode()
+    };
+    let modes = ["dir", r"dir\reg", r"dir\lnk", r"dir\chr"].map(mode_of);
+    std::fs::remove_dir_all(&root).unwrap();
+    assert_eq!(modes, [Some(Mode(0o040755)), Some(Mode(0o100755)), Some(Mode(0o120755)), Some(Mode(0o020755))]);
+}
+
+#[test]
+fn test_dump_manifest_json() {
+    let fbi = FILE_BASIC_INFORMATION { CreationTime: 1, LastAccessTime: 2, LastWriteTime: 3, ChangeTime: 4, FileAttributes: 0x20 };
+    assert_eq!(dump_manifest_json(Path::new(r#"C:\a "b""#), Some(FsType::Lxfs), Some(wslfs::IO_REPARSE_TAG_LX_SYMLINK), Some(&fbi), 100, Some(13)), concat!(
+        r#"{"path":"C:\\a \"b\"","fs_type":"lxfs","reparse_tag":"0xa000001d","ea_len":100,"reparse_len":13,"#,
+        r#""ntfs_times":{"creation_time":1,"last_access_time":2,"last_write_time":3,"change_time":4},"file_attributes":32}"#,
+    ));
+    assert_eq!(dump_manifest_json(Path::new("f"), None, None, None, 0, None),
+        r#"{"path":"f","fs_type":null,"reparse_tag":null,"ea_len":0,"reparse_len":null,"ntfs_times":null,"file_attributes":null}"#);
+}
+
+#[test]
+fn test_times_json() {
+    // 2024-10-24 03:45:25.0034801 UTC as FILETIME
+    let fbi = FILE_BASIC_INFORMATION { CreationTime: 133742151250034801, LastAccessTime: 133742151250034801, LastWriteTime: 133742151250034801, ChangeTime: 133742151250034801, FileAttributes: 0 };
+    let lxfs_times = (LxfsTime::new(1729741525, 1), LxfsTime::new(1729741525, 123456789), LxfsTime::new(1729741525, 3480100));
+    let mut out = String::new();
+    times_json(Some(&fbi), Some(lxfs_times), &mut out);
+    assert_eq!(out, concat!(
+        r#"{"ntfs":{"creation_time":"1729741525.003480100","last_access_time":"1729741525.003480100","#,
+        r#""last_write_time":"1729741525.003480100","change_time":"1729741525.003480100"},"#,
+        r#""lxattrb":{"atime":"1729741525.000000001","mtime":"1729741525.123456789","ctime":"1729741525.003480100"}}"#,
+    ));
+
+    let mut out = String::new();
+    times_json(None, None, &mut out);
+    assert_eq!(out, r#"{"ntfs":null,"lxattrb":null}"#);
 }
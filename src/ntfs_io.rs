@@ -1,60 +1,108 @@
 use std::ffi::c_void;
 use std::mem::{offset_of, transmute};
 use std::io::{Error, Result};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::ptr::{addr_of, null_mut};
 
 use windows::core::{PCSTR, PWSTR};
-use windows::Win32::Foundation::{LocalFree, ERROR_MORE_DATA, HANDLE, HLOCAL, MAX_PATH, WIN32_ERROR};
-use windows::Wdk::Storage::FileSystem::{FileBasicInformation, FileEaInformation, NtQueryEaFile, NtQueryInformationFile, NtSetEaFile, FILE_BASIC_INFORMATION, FILE_EA_INFORMATION, REPARSE_DATA_BUFFER};
+use windows::Win32::Foundation::NTSTATUS;
+use windows::Win32::Foundation::{LocalFree, ERROR_ACCESS_DENIED, ERROR_FILE_NOT_FOUND, ERROR_MORE_DATA, ERROR_PRIVILEGE_NOT_HELD, HANDLE, HLOCAL, MAX_PATH, STATUS_ACCESS_DENIED, STATUS_EA_TOO_LARGE, STATUS_NO_EAS_ON_FILE, STATUS_PRIVILEGE_NOT_HELD, WIN32_ERROR};
+use windows::Wdk::Storage::FileSystem::{FileBasicInformation, FileCaseSensitiveInformation, FileEaInformation, NtQueryEaFile, NtQueryInformationFile, NtSetEaFile, NtSetInformationFile, FILE_BASIC_INFORMATION, FILE_EA_INFORMATION, REPARSE_DATA_BUFFER};
 use windows::Win32::System::IO::{DeviceIoControl, IO_STATUS_BLOCK};
-use windows::Win32::Storage::FileSystem::{ReadFile, WriteFile, REPARSE_GUID_DATA_BUFFER};
-use windows::Win32::System::Ioctl::{FSCTL_DELETE_REPARSE_POINT, FSCTL_GET_REPARSE_POINT, FSCTL_SET_REPARSE_POINT};
+use windows::Win32::Storage::FileSystem::{FileCaseSensitiveInfo, GetFileInformationByHandleEx, ReadFile, SetEndOfFile, WriteFile, REPARSE_GUID_DATA_BUFFER};
+use windows::Win32::System::WindowsProgramming::FILE_CASE_SENSITIVE_INFO;
+use windows::Win32::System::Ioctl::{FILE_OBJECTID_BUFFER, FSCTL_DELETE_REPARSE_POINT, FSCTL_GET_OBJECT_ID, FSCTL_GET_REPARSE_POINT, FSCTL_SET_REPARSE_POINT};
 use windows::Win32::Foundation::GetLastError;
 
 /// `NtQueryEaFile` can read known EA's, but there are 'LX.LINUX.ATTR.*', so we'd read all.
+/// `Ok(None)` means the file has no EA's, `Err` means the query failed.
 pub unsafe fn read_ea_all(file_handle: HANDLE) -> Result<Option<Vec<u8>>> {
-    let mut isb = IO_STATUS_BLOCK::default();
-    let mut ea_info = FILE_EA_INFORMATION::default();
-  
-    // Query the Extended Attribute length
-    let nt_status = NtQueryInformationFile(
-        file_handle, 
-        transmute(&mut isb), 
-        transmute(&mut ea_info), 
-        size_of::<FILE_EA_INFORMATION>() as u32, 
-        FileEaInformation
-    );    
+    read_ea_with(
+        || {
+            let mut isb = IO_STATUS_BLOCK::default();
+            let mut ea_info = FILE_EA_INFORMATION::default();
+            // Query the Extended Attribute length
+            let nt_status = NtQueryInformationFile(
+                file_handle, 
+                transmute(&mut isb), 
+                transmute(&mut ea_info), 
+                size_of::<FILE_EA_INFORMATION>() as u32, 
+                FileEaInformation
+            );
+            (nt_status, ea_info.EaSize)
+        },
+        |buf| {
+            let mut isb = IO_STATUS_BLOCK::default();
+            NtQueryEaFile(
+                file_handle,
+                &mut isb,
+                transmute(buf.as_mut_ptr()),
+                buf.len() as u32,
+                false, // read all ea entries to buffer
+                None,
+                0,
+                None,
+                true,
+            )
+        },
+    )
+}
+
+/// outcomes of `read_ea_all` from the results of the two queries, split to be tested without a file
+fn read_ea_with(query_size: impl FnOnce() -> (NTSTATUS, u32), query_ea: impl FnOnce(&mut [u8]) -> NTSTATUS) -> Result<Option<Vec<u8>>> {
+    let (nt_status, ea_size) = query_size();
     if nt_status.is_err() {
-        println!("[ERROR] NtQueryInformationFile: {:#x}", nt_status.0);
+        eprintln!("[ERROR] NtQueryInformationFile: {:#x}", nt_status.0);
         return Err(Error::from_raw_os_error(nt_status.0));
     }
-    if ea_info.EaSize == 0 {
+    if ea_size == 0 {
         return Ok(None);
     }
 
-    let mut buf = vec![0u8; ea_info.EaSize as usize];
+    let mut buf = vec![0u8; ea_size as usize];
 
-    let nt_status = NtQueryEaFile(
-        file_handle,
-        &mut isb,
-        transmute(buf.as_mut_ptr()),
-        ea_info.EaSize,
-        false, // read all ea entries to buffer
-        None,
-        0,
-        None,
-        true,
-    );
+    let nt_status = query_ea(&mut buf);
+    if nt_status == STATUS_NO_EAS_ON_FILE {
+        return Ok(None);
+    }
     if nt_status.is_err() {
-        println!("[ERROR] NtQueryEaFile: {:#x}", nt_status.0);
+        eprintln!("[ERROR] NtQueryEaFile: {:#x}", nt_status.0);
         return Err(Error::from_raw_os_error(nt_status.0));
     }
 
     return Ok(Some(buf));
 }
 
+/// set by the first failure for lack of privileges, to exit with a distinct code
+pub static PERMISSION_DENIED: AtomicBool = AtomicBool::new(false);
+
+/// `STATUS_ACCESS_DENIED` or `STATUS_PRIVILEGE_NOT_HELD` from Nt* functions, or the win32 error of them from `DeviceIoControl`
+pub fn is_permission_denied(raw_os_error: i32) -> bool {
+    [STATUS_ACCESS_DENIED.0, STATUS_PRIVILEGE_NOT_HELD.0, ERROR_ACCESS_DENIED.0 as i32, ERROR_PRIVILEGE_NOT_HELD.0 as i32].contains(&raw_os_error)
+}
+
+/// the error as is, the advice is printed for the first failure for lack of privileges,
+/// `reparse_point` for the IOCTLs of reparse points
+pub fn check_permission(err: Error, reparse_point: bool) -> Error {
+    if err.raw_os_error().is_some_and(is_permission_denied) && !PERMISSION_DENIED.swap(true, Ordering::Relaxed) {
+        eprintln!("[ERROR] permission denied, try running from an elevated prompt or check file ACLs");
+        if reparse_point {
+            warning!("[NOTE] setting or deleting a reparse point may need SeRestorePrivilege, reading it may need SeBackupPrivilege");
+        }
+    }
+    err
+}
+
 /// It's safe to save only changed EA's.
 pub unsafe fn write_ea(file_handle: HANDLE, buf: &[u8]) -> Result<()> {
+    use crate::ea_parse::{ea_size_after_write, ea_too_large, need_ea_after_write, EA_SIZE_MAX};
+
+    let current = read_ea_all(file_handle)?;
+    let size = ea_size_after_write(current.as_deref(), buf);
+    if size > EA_SIZE_MAX {
+        return Err(ea_too_large(size));
+    }
+
     let mut isb = IO_STATUS_BLOCK::default();
     let nt_status = NtSetEaFile(
         file_handle,
@@ -62,9 +110,16 @@ pub unsafe fn write_ea(file_handle: HANDLE, buf: &[u8]) -> Result<()> {
         transmute(buf.as_ptr()),
         buf.len() as u32,
     );
+    if nt_status == STATUS_EA_TOO_LARGE {
+        return Err(ea_too_large(size));
+    }
     if nt_status.is_err() {
-        println!("[ERROR] NtSetEaFile: {:#x}", nt_status.0);
-        return Err(Error::from_raw_os_error(nt_status.0));
+        eprintln!("[ERROR] NtSetEaFile: {:#x}", nt_status.0);
+        return Err(check_permission(Error::from_raw_os_error(nt_status.0), false));
+    }
+    let need_ea = need_ea_after_write(current.as_deref(), buf);
+    if !need_ea.is_empty() {
+        warning!("[NOTE] EA: {} flagged FILE_NEED_EA, programs unaware of EAs cannot open the file", need_ea.join(", "));
     }
     Ok(())
 }
@@ -90,31 +145,49 @@ unsafe fn read_reparse_point_inner(file_handle: HANDLE, buf: &mut Vec<u8>) -> Op
     return Some(err);
 }
 
+/// documented max size of a reparse point, `MAXIMUM_REPARSE_DATA_BUFFER_SIZE`
+const REPARSE_BUFFER_MAX: usize = 16 * 1024;
+
+/// size to retry with after `ERROR_MORE_DATA`, `None` if `buf` is of the max size already,
+/// `ReparseDataLength` follows a header of 8 bytes for a Microsoft tag, or of 24 bytes with a GUID for others,
+/// the size is doubled if the header gives no larger one
+fn next_reparse_buffer_size(buf: &[u8]) -> Option<usize> {
+    if buf.len() >= REPARSE_BUFFER_MAX {
+        return None;
+    }
+    let size_from_header = buf.get(0..6).map(|header| {
+        let tag = u32::from_le_bytes(header[0..4].try_into().unwrap());
+        let data_len = u16::from_le_bytes(header[4..6].try_into().unwrap()) as usize;
+        // IsReparseTagMicrosoft
+        let header_len = if tag & 0x8000_0000 != 0 {
+            offset_of!(REPARSE_DATA_BUFFER, Anonymous)
+        } else {
+            offset_of!(REPARSE_GUID_DATA_BUFFER, GenericReparseBuffer)
+        };
+        header_len + data_len
+    });
+    let size = size_from_header.filter(|size| *size > buf.len()).unwrap_or(buf.len() * 2);
+    Some(size.min(REPARSE_BUFFER_MAX))
+}
+
 pub unsafe fn read_reparse_point(file_handle: HANDLE) -> Result<Vec<u8>> {
     // a reasonable init buf size 64
-    let buf_size = size_of::<REPARSE_GUID_DATA_BUFFER>() + 36;
-    let mut buf = vec![0; buf_size];
-    match read_reparse_point_inner(file_handle, &mut buf) {
-        None => return Ok(buf),
-        Some(ERROR_MORE_DATA) => {
-            // retry with new buf
-            let reparse_buf = buf.as_ptr() as *const REPARSE_GUID_DATA_BUFFER;
-            // larger in most case
-            let reparse_data_len = (*reparse_buf).ReparseDataLength as usize;
-            let buf_size = size_of::<REPARSE_GUID_DATA_BUFFER>() + reparse_data_len;
-            let mut buf = vec![0; buf_size];
-            match read_reparse_point_inner(file_handle, &mut buf) {
-                None => return Ok(buf),
-                Some(err) => {
-                    println!("[ERROR] DeviceIoControl, Cannot read symlink from reparse_point data");
-                    return Err(Error::from_raw_os_error(err.0 as i32));
-                }
-            }
-        },
-        Some(err) => {
-            println!("[ERROR] DeviceIoControl, Cannot read symlink from reparse_point data");
-            return Err(Error::from_raw_os_error(err.0 as i32));
-        },
+    let mut buf = vec![0; size_of::<REPARSE_GUID_DATA_BUFFER>() + 36];
+    loop {
+        let err = match read_reparse_point_inner(file_handle, &mut buf) {
+            None => return Ok(buf),
+            Some(ERROR_MORE_DATA) => match next_reparse_buffer_size(&buf) {
+                Some(buf_size) => {
+                    // retry with new buf, larger in most case
+                    buf = vec![0; buf_size];
+                    continue;
+                },
+                None => ERROR_MORE_DATA,
+            },
+            Some(err) => err,
+        };
+        eprintln!("[ERROR] DeviceIoControl, Cannot read symlink from reparse_point data");
+        return Err(check_permission(Error::from_raw_os_error(err.0 as i32), true));
     }
 }
 
@@ -134,7 +207,7 @@ pub unsafe fn write_reparse_point(file_handle: HANDLE, buf: &[u8]) -> Result<()>
     }
     let err = GetLastError();
     //dbg!(err.raw_os_error());
-    return Err(Error::from_raw_os_error(err.0 as i32));
+    return Err(check_permission(Error::from_raw_os_error(err.0 as i32), true));
 }
 
 pub unsafe fn delete_reparse_point(file_handle: HANDLE, tag: u32) -> Result<()> {
@@ -156,7 +229,7 @@ pub unsafe fn delete_reparse_point(file_handle: HANDLE, tag: u32) -> Result<()>
     }
     let err = GetLastError();
     //dbg!(err.raw_os_error());
-    return Err(Error::from_raw_os_error(err.0 as i32));
+    return Err(check_permission(Error::from_raw_os_error(err.0 as i32), true));
 }
 
 pub unsafe fn read_data(file_handle: HANDLE) -> Result<Vec<u8>> {
@@ -168,7 +241,7 @@ pub unsafe fn read_data(file_handle: HANDLE) -> Result<Vec<u8>> {
         Some(&mut read_size),
         None,        
     ) {
-        println!("[ERROR] ReadFile: {}, Cannot read symlink from file content\n", &err);
+        eprintln!("[ERROR] ReadFile: {}, Cannot read symlink from file content\n", &err);
         return Err(err.into());
     }
     buf.shrink_to_fit();
@@ -183,12 +256,44 @@ pub unsafe fn write_data(file_handle: HANDLE, buf: &[u8]) -> Result<()> {
         Some(&mut write_size),
         None,        
     ) {
-        println!("[ERROR] WriteFile: {}, Cannot write symlink from file content\n", &err);
+        eprintln!("[ERROR] WriteFile: {}, Cannot write symlink from file content\n", &err);
         return Err(err.into());
     }
     return Ok(());
 }
 
+/// NTFS object id of the file, `Ok(None)` if it has none, never creates one
+pub unsafe fn query_object_id(file_handle: HANDLE) -> Result<Option<[u8; 16]>> {
+    let mut buf = FILE_OBJECTID_BUFFER::default();
+    let mut bytes_returned: u32 = 0;
+    if DeviceIoControl(
+        file_handle,
+        FSCTL_GET_OBJECT_ID,
+        None,
+        0,
+        Some(&mut buf as *mut _ as *mut c_void),
+        size_of_val(&buf) as u32,
+        Some(&mut bytes_returned),
+        None,
+    ).is_ok() {
+        return Ok(Some(buf.ObjectId));
+    }
+    let err = GetLastError();
+    if err == ERROR_FILE_NOT_FOUND {
+        return Ok(None);
+    }
+    return Err(Error::from_raw_os_error(err.0 as i32));
+}
+
+/// cut the file content at the file pointer, at 0 for a handle just opened
+pub unsafe fn truncate_data(file_handle: HANDLE) -> Result<()> {
+    if let Err(err) = SetEndOfFile(file_handle) {
+        eprintln!("[ERROR] SetEndOfFile: {}", &err);
+        return Err(err.into());
+    }
+    Ok(())
+}
+
 pub fn query_file_basic_infomation(file_handle: HANDLE) -> Result<FILE_BASIC_INFORMATION> {
     let mut isb = IO_STATUS_BLOCK::default();
     let mut fbi = FILE_BASIC_INFORMATION::default();
@@ -200,12 +305,64 @@ pub fn query_file_basic_infomation(file_handle: HANDLE) -> Result<FILE_BASIC_INF
         FileBasicInformation,
     ) };
     if nt_status.is_err() {
-        println!("[ERROR] NtQueryInformationFile: {:#x}", nt_status.0);
+        eprintln!("[ERROR] NtQueryInformationFile: {:#x}", nt_status.0);
         return Err(Error::from_raw_os_error(nt_status.0));
     }
     Ok(fbi)
 }
 
+/// Zero in a time field or `FileAttributes` means that field is not changed.
+pub fn set_file_basic_infomation(file_handle: HANDLE, fbi: &FILE_BASIC_INFORMATION) -> Result<()> {
+    let mut isb = IO_STATUS_BLOCK::default();
+    let nt_status = unsafe { NtSetInformationFile(
+        file_handle,
+        &mut isb,
+        fbi as *const _ as *const c_void,
+        size_of_val(fbi) as u32,
+        FileBasicInformation,
+    ) };
+    if nt_status.is_err() {
+        eprintln!("[ERROR] NtSetInformationFile: {:#x}", nt_status.0);
+        return Err(Error::from_raw_os_error(nt_status.0));
+    }
+    Ok(())
+}
+
+/// `FILE_CS_FLAG_CASE_SENSITIVE_DIR`, set by WSL on directories it creates
+const FILE_CS_FLAG_CASE_SENSITIVE_DIR: u32 = 0x1;
+
+/// the per-directory case sensitivity flag, it is not an EA
+pub fn query_case_sensitive(file_handle: HANDLE) -> Result<bool> {
+    let mut info = FILE_CASE_SENSITIVE_INFO::default();
+    if let Err(err) = unsafe { GetFileInformationByHandleEx(
+        file_handle,
+        FileCaseSensitiveInfo,
+        &mut info as *mut _ as *mut c_void,
+        size_of_val(&info) as u32,
+    ) } {
+        return Err(err.into());
+    }
+    Ok(info.Flags & FILE_CS_FLAG_CASE_SENSITIVE_DIR != 0)
+}
+
+/// it fails on a file, or to turn off a directory with names differing only in case
+pub fn set_case_sensitive(file_handle: HANDLE, case_sensitive: bool) -> Result<()> {
+    let mut isb = IO_STATUS_BLOCK::default();
+    let info = FILE_CASE_SENSITIVE_INFO { Flags: if case_sensitive { FILE_CS_FLAG_CASE_SENSITIVE_DIR } else { 0 } };
+    let nt_status = unsafe { NtSetInformationFile(
+        file_handle,
+        &mut isb,
+        &info as *const _ as *const c_void,
+        size_of_val(&info) as u32,
+        FileCaseSensitiveInformation,
+    ) };
+    if nt_status.is_err() {
+        eprintln!("[ERROR] NtSetInformationFile: {:#x}", nt_status.0);
+        return Err(Error::from_raw_os_error(nt_status.0));
+    }
+    Ok(())
+}
+
 pub fn error_msg_ntdll(msgid: u32) -> windows::core::Result<String> {
     use windows::Win32::System::Diagnostics::Debug::*;
     use windows::core::Error;
@@ -249,3 +406,61 @@ pub fn error_msg_ntdll(msgid: u32) -> windows::core::Result<String> {
         }
     }
 }
+
+#[test]
+fn test_next_reparse_buffer_size() {
+    let header = |tag: u32, data_len: u16, buf_len: usize| {
+        let mut buf = vec![0u8; buf_len];
+        buf[0..4].copy_from_slice(&tag.to_le_bytes());
+        buf[4..6].copy_from_slice(&data_len.to_le_bytes());
+        buf
+    };
+    // IO_REPARSE_TAG_LX_SYMLINK, a Microsoft tag without GUID
+    assert_eq!(next_reparse_buffer_size(&header(0xA000001D, 1000, 64)), Some(8 + 1000));
+    // a third party tag with GUID
+    assert_eq!(next_reparse_buffer_size(&header(0x00001234, 1000, 64)), Some(24 + 1000));
+    // a length not larger than the buffer is doubled
+    assert_eq!(next_reparse_buffer_size(&header(0xA000001D, 16, 64)), Some(128));
+    // oversized, capped at the max
+    assert_eq!(next_reparse_buffer_size(&header(0x00001234, u16::MAX, 64)), Some(REPARSE_BUFFER_MAX));
+    assert_eq!(next_reparse_buffer_size(&header(0x00001234, 100, 12 * 1024)), Some(REPARSE_BUFFER_MAX));
+    assert_eq!(next_reparse_buffer_size(&header(0x00001234, u16::MAX, REPARSE_BUFFER_MAX)), None);
+}
+
+#[test]
+fn test_is_permission_denied() {
+    use windows::Win32::Foundation::{ERROR_SHARING_VIOLATION, STATUS_SHARING_VIOLATION};
+
+    assert!(is_permission_denied(STATUS_ACCESS_DENIED.0));
+    assert!(is_permission_denied(STATUS_PRIVILEGE_NOT_HELD.0));
+    assert!(is_permission_denied(ERROR_ACCESS_DENIED.0 as i32));
+    assert!(is_permission_denied(ERROR_PRIVILEGE_NOT_HELD.0 as i32));
+    assert!(!is_permission_denied(STATUS_SHARING_VIOLATION.0));
+    assert!(!is_permission_denied(ERROR_SHARING_VIOLATION.0 as i32));
+    assert!(!is_permission_denied(0));
+}
+
+#[test]
+fn test_read_ea_outcomes() {
+    use windows::Win32::Foundation::{STATUS_INVALID_HANDLE, STATUS_SUCCESS};
+
+    // EaSize 0, the EA's are not queried
+    let r = read_ea_with(|| (STATUS_SUCCESS, 0), |_| unreachable!());
+    assert_eq!(r.unwrap(), None);
+
+    // EaSize stale, EA's removed meanwhile
+    let r = read_ea_with(|| (STATUS_SUCCESS, 12), |_| STATUS_NO_EAS_ON_FILE);
+    assert_eq!(r.unwrap(), None);
+
+    let r = read_ea_with(|| (STATUS_SUCCESS, 4), |buf| {
+        buf.copy_from_slice(b"abcd");
+        STATUS_SUCCESS
+    });
+    assert_eq!(r.unwrap(), Some(b"abcd".to_vec()));
+
+    let r = read_ea_with(|| (STATUS_INVALID_HANDLE, 0), |_| unreachable!());
+    assert_eq!(r.unwrap_err().raw_os_error(), Some(STATUS_INVALID_HANDLE.0));
+
+    let r = read_ea_with(|| (STATUS_SUCCESS, 12), |_| STATUS_ACCESS_DENIED);
+    assert_eq!(r.unwrap_err().raw_os_error(), Some(STATUS_ACCESS_DENIED.0));
+}
@@ -53,3 +53,107 @@ pub fn try_get_distro_from_unc_path(abs_path: &Path) -> Option<OsString> {
         .and_then(try_get_distro_from_unc_prefix)
         .map(|s| s.to_owned())
 }
+
+/// unix path of `path` inside `root`, like r"C:\distro\rootfs\usr\bin" in r"C:\distro\rootfs" is "/usr/bin"
+pub fn unix_path_in(path: &Path, root: &Path) -> Option<String> {
+    let rel = path.strip_prefix(root).ok()?;
+    let names: Vec<_> = rel.components().map(|c| match c {
+        Component::Normal(name) => Some(name.to_string_lossy()),
+        _ => None,
+    }).collect::<Option<_>>()?;
+    Some(format!("/{}", names.join("/")))
+}
+
+/// names of `path` below its root, `.` is skipped and `..` never goes above the root like in unix,
+/// empty for the root itself like "/" or r"\\wsl$\Arch\"
+pub fn names_below_root(path: &Path) -> Vec<&OsStr> {
+    let mut names = vec![];
+    for comp in path.components() {
+        match comp {
+            Component::Normal(name) => names.push(name),
+            Component::ParentDir => {
+                names.pop();
+            },
+            Component::Prefix(_) | Component::RootDir | Component::CurDir => {},
+        }
+    }
+    names
+}
+
+/// windows path of the symlink `target` of `link` inside `root`, an absolute target is below `root`,
+/// a relative one is below the directory of `link`, `..` never goes above `root`,
+/// `None` if `link` is not inside `root`
+pub fn resolve_symlink_target(link: &Path, root: &Path, target: &str) -> Option<PathBuf> {
+    let link_unix = unix_path_in(link, root)?;
+    let unix_target = if is_unix_absolute(target) {
+        target.to_owned()
+    } else {
+        let dir = link_unix.rsplit_once('/').map_or("", |(dir, _)| dir);
+        format!("{}/{}", dir, target)
+    };
+    let mut resolved = root.to_path_buf();
+    resolved.extend(names_below_root(Path::new(&unix_target)));
+    Some(resolved)
+}
+
+/// groups of names differing only by case, like "Makefile" and "makefile", which collide in a directory not case sensitive,
+/// names in a group and the groups are sorted
+pub fn case_collisions<S: AsRef<OsStr>>(names: &[S]) -> Vec<Vec<String>> {
+    let mut names: Vec<(String, String)> = names.iter()
+        .map(|n| n.as_ref().to_string_lossy().into_owned())
+        .map(|n| (n.to_lowercase(), n))
+        .collect();
+    names.sort();
+    let mut groups: Vec<Vec<String>> = vec![];
+    for (i, (folded, name)) in names.iter().enumerate() {
+        if i > 0 && names[i - 1].0 == *folded {
+            groups.last_mut().unwrap().push(name.clone());
+        } else if names.get(i + 1).is_some_and(|(next, _)| next == folded) {
+            groups.push(vec![name.clone()]);
+        }
+    }
+    groups
+}
+
+#[test]
+fn test_unix_path_in() {
+    let root = Path::new(r"C:\distro\rootfs");
+    assert_eq!(unix_path_in(Path::new(r"C:\distro\rootfs\usr\bin"), root).as_deref(), Some("/usr/bin"));
+    assert_eq!(unix_path_in(root, root).as_deref(), Some("/"));
+    assert_eq!(unix_path_in(Path::new(r"C:\distro\ext4.vhdx"), root), None);
+    assert_eq!(unix_path_in(Path::new(r"\\wsl$\Arch\etc\passwd"), Path::new(r"\\wsl$\Arch\")).as_deref(), Some("/etc/passwd"));
+}
+
+#[test]
+fn test_names_below_root() {
+    let names = |path: &str| names_below_root(Path::new(path)).iter().map(|s| s.to_string_lossy().into_owned()).collect::<Vec<_>>();
+    assert!(names("/").is_empty());
+    assert!(names("/..").is_empty());
+    assert_eq!(names("/usr/./bin/../lib"), ["usr", "lib"]);
+    assert!(names(r"\\wsl$\Arch\").is_empty());
+    assert!(names(r"\\wsl$\Arch").is_empty());
+    assert!(names(r"\\?\UNC\wsl$\Arch").is_empty());
+    assert_eq!(names(r"\\wsl.localhost\Arch\etc\..\..\usr"), ["usr"]);
+}
+
+#[test]
+fn test_resolve_symlink_target() {
+    let root = Path::new("/distro/rootfs");
+    let link = Path::new("/distro/rootfs/usr/bin/vi");
+    assert_eq!(resolve_symlink_target(link, root, "vim.basic"), Some(root.join("usr").join("bin").join("vim.basic")));
+    assert_eq!(resolve_symlink_target(link, root, "../lib/./vim"), Some(root.join("usr").join("lib").join("vim")));
+    assert_eq!(resolve_symlink_target(link, root, "/etc/alternatives/vi"), Some(root.join("etc").join("alternatives").join("vi")));
+    assert_eq!(resolve_symlink_target(link, root, "../../../../etc"), Some(root.join("etc")));
+    assert_eq!(resolve_symlink_target(Path::new("/distro/rootfs/vi"), root, "."), Some(root.to_path_buf()));
+    assert_eq!(resolve_symlink_target(Path::new("/other/vi"), root, "vim"), None);
+}
+
+#[test]
+fn test_case_collisions() {
+    assert!(case_collisions(&["a", "b", "c"]).is_empty());
+    assert_eq!(case_collisions(&["makefile", "src", "Makefile", "README", "ReadMe", "readme.md", "Ä", "ä"]), [
+        vec!["Makefile", "makefile"],
+        vec!["README", "ReadMe"],
+        vec!["Ä", "ä"],
+    ]);
+}
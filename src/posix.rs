@@ -1,7 +1,10 @@
+use std::fmt::{Display, Octal};
 use std::fs::File; 
 use std::io::{BufRead, BufReader};
 use std::path::Path;
 
+use clap::ValueEnum;
+
 pub const ST_MODE_TYPE_FIFO: u32 = 0o_0010000;
 pub const ST_MODE_TYPE_CHR:  u32 = 0o_0020000;
 pub const ST_MODE_TYPE_DIR:  u32 = 0o_0040000;
@@ -12,10 +15,11 @@ pub const ST_MODE_TYPE_SOCK: u32 = 0o_0140000;
 /// type of file mask for st_mode 
 pub const ST_MODE_TYPE_MASK: u32 = 0o_0170000;
 
-pub const DEFAULT_MODE: u32 = 0o_0100644;
+pub const DEFAULT_MODE: Mode = Mode(0o_0100644);
+pub const DEFAULT_DIR_MODE: Mode = Mode(0o_0040755);
 
 #[repr(u32)]
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, ValueEnum)]
 #[derive(PartialEq)]
 pub enum StModeType {
     /// named pipe (fifo)
@@ -33,6 +37,7 @@ pub enum StModeType {
     /// socket
     SOCK = ST_MODE_TYPE_SOCK,
 
+    #[value(skip)]
     UNKNOWN = ST_MODE_TYPE_MASK,
 }
 
@@ -85,9 +90,94 @@ const S_IROTH: u32 = 0o_0000004;			/* R for other */
 const S_IWOTH: u32 = 0o_0000002;			/* W for other */
 const S_IXOTH: u32 = 0o_0000001;			/* X for other */
 
+/// permission, setid and sticky bits of st_mode
+const ST_MODE_PERMS_MASK: u32 = 0o_0007777;
+
+/// st_mode with type of file and permission bits
+#[repr(transparent)]
+#[derive(Debug, Clone, Copy)]
+#[derive(PartialEq, Eq)]
+pub struct Mode(pub u32);
+
+impl Mode {
+    pub fn file_type(&self) -> StModeType {
+        StModeType::from_mode(self.0)
+    }
+
+    /// permission, setid and sticky bits
+    pub fn perms(&self) -> u16 {
+        (self.0 & ST_MODE_PERMS_MASK) as u16
+    }
+
+    pub fn with_type(self, file_type: StModeType) -> Self {
+        Mode((self.0 & !ST_MODE_TYPE_MASK) | (file_type as u32 & ST_MODE_TYPE_MASK))
+    }
+
+    pub fn with_perms(self, perms: u16) -> Self {
+        Mode((self.0 & !ST_MODE_PERMS_MASK) | (perms as u32 & ST_MODE_PERMS_MASK))
+    }
+
+    pub fn suid(&self) -> bool {
+        (self.0 & S_ISUID) != 0
+    }
+
+    pub fn sgid(&self) -> bool {
+        (self.0 & S_ISGID) != 0
+    }
+
+    pub fn sticky(&self) -> bool {
+        (self.0 & S_ISTXT) != 0
+    }
+
+    pub fn set_suid(&mut self, on: bool) {
+        self.set_bits(S_ISUID, on);
+    }
+
+    pub fn set_sgid(&mut self, on: bool) {
+        self.set_bits(S_ISGID, on);
+    }
+
+    pub fn set_sticky(&mut self, on: bool) {
+        self.set_bits(S_ISTXT, on);
+    }
+
+    fn set_bits(&mut self, mask: u32, on: bool) {
+        if on {
+            self.0 |= mask;
+        } else {
+            self.0 &= !mask;
+        }
+    }
+}
+
+impl From<u32> for Mode {
+    fn from(mode: u32) -> Self {
+        Mode(mode)
+    }
+}
+
+impl From<Mode> for u32 {
+    fn from(mode: Mode) -> Self {
+        mode.0
+    }
+}
+
+/// "ls -l" type perms field
+impl Display for Mode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&lsperms(self.0))
+    }
+}
+
+impl Octal for Mode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        Octal::fmt(&self.0, f)
+    }
+}
+
 const RWX: [&'static str; 8] = [ "---", "--x", "-w-", "-wx", "r--", "r-x", "rw-", "rwx" ];
 
-/* Convert a mode field into "ls -l" type perms field. */
+/// "ls -l" type perms field of st_mode, like `-rwxr-xr-x`, the first char is type of file
 pub fn lsperms(mode: u32) -> String {
     let mut bits = ['-' as u8; 10];
 
@@ -115,17 +205,53 @@ pub fn lsperms(mode: u32) -> String {
     return String::from_utf8_lossy(&bits).into_owned();
 }
 
-pub fn chmod_all(mut mode: u32, mode_strs: &str) -> Result<u32, ()> {
-    if let Ok(newmode) = u32::from_str_radix(mode_strs, 8) {
-        if mode_strs.len() <= 4 {
-            return Ok((mode & ST_MODE_TYPE_MASK) | (newmode & !ST_MODE_TYPE_MASK));
-        } else {
-            return Err(());
+/// SGR parameters of the `ls --color` defaults, setid bits first, `None` for a plain file
+fn ls_color(mode: u32) -> Option<&'static str> {
+    if (mode & S_ISUID) != 0 {
+        return Some("37;41");
+    }
+    if (mode & S_ISGID) != 0 {
+        return Some("30;43");
+    }
+    match StModeType::from_mode(mode) {
+        StModeType::DIR => Some("01;34"),
+        StModeType::LNK => Some("01;36"),
+        StModeType::FIFO => Some("40;33"),
+        StModeType::SOCK => Some("01;35"),
+        StModeType::CHR | StModeType::BLK => Some("40;33;01"),
+        StModeType::REG if (mode & (S_IXUSR | S_IXGRP | S_IXOTH)) != 0 => Some("01;32"),
+        _ => None,
+    }
+}
+
+/// `text` wrapped in the ANSI escape codes `ls --color` uses for a file of `mode`
+pub fn ansi_colored(text: &str, mode: u32) -> String {
+    match ls_color(mode) {
+        Some(color) => format!("\x1b[{}m{}\x1b[0m", color, text),
+        None => text.to_owned(),
+    }
+}
+
+/// only permission, setid and sticky bits are changed, type of file is kept
+pub fn chmod_all(mode: Mode, mode_strs: &str) -> Result<Mode, ()> {
+    let newmode = chmod_all_inner(mode, mode_strs)?;
+    // changing type of file by chmod will corrupt the file
+    assert_eq!(newmode.0 & ST_MODE_TYPE_MASK, mode.0 & ST_MODE_TYPE_MASK);
+    return Ok(newmode);
+}
+
+/// an octal part "0644" is allowed only as the first, then symbolic parts adjust it in order, "644,u+x"
+fn chmod_all_inner(mut mode: Mode, mode_strs: &str) -> Result<Mode, ()> {
+    let mut mode_parts = mode_strs.split(',').map(str::trim).peekable();
+    if let Some(first) = mode_parts.next_if(|s| !s.is_empty() && s.bytes().all(|b| b.is_ascii_digit())) {
+        match u16::from_str_radix(first, 8) {
+            Ok(newmode) if first.len() <= 4 => mode = mode.with_perms(newmode),
+            _ => return Err(()),
         }
     }
 
-    for mode_str in mode_strs.split(',') {
-        mode = chmod_part(mode, mode_str.trim())?;
+    for mode_str in mode_parts {
+        mode = chmod_part(mode, mode_str)?;
     }
     return Ok(mode);
 }
@@ -134,23 +260,22 @@ pub fn chmod_all(mut mode: u32, mode_strs: &str) -> Result<u32, ()> {
 /// ug +- s
 /// o +- t
 /// +- t
-pub fn chmod_part(mut mode: u32, mode_str: &str) -> Result<u32, ()> {
+/// any combination of who is allowed as "ug+rw", an empty who is "a",
+/// umask is not applied as the umask of the distro is unknown
+pub fn chmod_part(mut mode: Mode, mode_str: &str) -> Result<Mode, ()> {
     use regex::Regex;
     use std::sync::LazyLock;
 
     static MODE_PATTERN: LazyLock<Regex> = LazyLock::new(|| 
-        Regex::new(r"^([ugoa]?)([+-])([rwxst]+)$").unwrap()
+        Regex::new(r"^([ugoa]*)([+-])([rwxst]+)$").unwrap()
     );
 
     if let Some(c) = MODE_PATTERN.captures(mode_str) {
         let found: (&str, [&str; 3]) = c.extract();
-        let whoes = found.1[0];
+        let whoes = if found.1[0].is_empty() { "a" } else { found.1[0] };
         let act = found.1[1].chars().nth(0).unwrap();
         let whats = found.1[2];
-        if whoes == "" && whats == "t" {
-            mode = chmod_bit(mode, 'o', act, 't');
-        } else {
-            let who = whoes.chars().nth(0).unwrap();
+        for who in whoes.chars() {
             for what in whats.chars() {
                 mode = chmod_bit(mode, who, act, what);
             }
@@ -162,7 +287,7 @@ pub fn chmod_part(mut mode: u32, mode_str: &str) -> Result<u32, ()> {
     return Ok(mode);
 }
 
-pub fn chmod_bit(mut mode: u32, who: char, act: char, what: char) -> u32 {
+pub fn chmod_bit(mut mode: Mode, who: char, act: char, what: char) -> Mode {
     if who == 'a' {
         mode = chmod_bit(mode, 'u', act, what);
         mode = chmod_bit(mode, 'g', act, what);
@@ -187,16 +312,134 @@ pub fn chmod_bit(mut mode: u32, who: char, act: char, what: char) -> u32 {
         ('g', 's') => S_ISGID,
         ('o', 't') => S_ISTXT,
         _ => 0,
-    };
+    } & ST_MODE_PERMS_MASK;
     if act == '+' {
-        mode |= mask;
+        mode.set_bits(mask, true);
     }
     if act == '-' {
-        mode &= !mask;
+        mode.set_bits(mask, false);
     }
     return mode;
 }
 
+/// permission, setid and sticky bits from octal "0755" or symbolic "u=rwx,g=rx,o=rx",
+/// symbolic parts are applied from 0 in order, each is "who=what", "who+what" or "who-what" as `chmod_part`,
+/// "who=" clears all bits of who, `None` if any part is invalid
+pub fn parse_mode(mode_str: &str) -> Option<u32> {
+    let mode_str = mode_str.trim();
+    if let Ok(perms) = u32::from_str_radix(mode_str, 8) {
+        return (mode_str.len() <= 4).then_some(perms);
+    }
+
+    let mut mode = Mode(0);
+    for part in mode_str.split(',') {
+        let part = part.trim();
+        if let Some((whoes, whats)) = part.split_once('=') {
+            if whoes.is_empty() || !whoes.chars().all(|c| "ugoa".contains(c)) || !whats.chars().all(|c| "rwxst".contains(c)) {
+                return None;
+            }
+            for who in whoes.chars() {
+                for what in "rwxst".chars() {
+                    mode = chmod_bit(mode, who, '-', what);
+                }
+                for what in whats.chars() {
+                    mode = chmod_bit(mode, who, '+', what);
+                }
+            }
+        } else {
+            mode = chmod_part(mode, part).ok()?;
+        }
+    }
+    Some(mode.0 & ST_MODE_PERMS_MASK)
+}
+
+/// permission, setid and sticky bits as "u=rwxs,g=rx,o=rxt", the inverse of `parse_mode`
+pub fn format_mode_symbolic(mode: u32) -> String {
+    let part = |who: char, shift: u32, special: u32, special_char: char| {
+        let mut s = String::with_capacity(6);
+        s.push(who);
+        s.push('=');
+        let rwx = RWX[((mode >> shift) & 7) as usize];
+        s.extend(rwx.chars().filter(|c| *c != '-'));
+        if mode & special != 0 {
+            s.push(special_char);
+        }
+        s
+    };
+    format!("{},{},{}", part('u', 6, S_ISUID, 's'), part('g', 3, S_ISGID, 's'), part('o', 0, S_ISTXT, 't'))
+}
+
+/// a device node to create, a line of a makedev table
+#[derive(Debug, PartialEq)]
+pub struct DevSpec {
+    pub path: String,
+    pub mode: Mode,
+    pub major: u32,
+    pub minor: u32,
+}
+
+/// device type from "c" or "b" as `mknod`
+pub fn parse_dev_type(type_str: &str) -> Result<StModeType, String> {
+    match type_str {
+        "c" | "u" => Ok(StModeType::CHR),
+        "b" => Ok(StModeType::BLK),
+        _ => Err(format!("invalid device type: {}, must be c or b", type_str)),
+    }
+}
+
+/// "path type major minor mode", `Ok(None)` for an empty line or a comment starting with '#'
+pub fn parse_dev_spec(line: &str) -> Result<Option<DevSpec>, String> {
+    let line = line.trim();
+    if line.is_empty() || line.starts_with('#') {
+        return Ok(None);
+    }
+    let fields: Vec<&str> = line.split_whitespace().collect();
+    let [path, type_str, major, minor, mode_str] = fields[..] else {
+        return Err(format!("expect 5 fields: path type major minor mode, got {}", fields.len()));
+    };
+    let file_type = parse_dev_type(type_str)?;
+    let major = major.parse::<u32>().map_err(|_| format!("invalid major: {}", major))?;
+    let minor = minor.parse::<u32>().map_err(|_| format!("invalid minor: {}", minor))?;
+    let perms = parse_mode(mode_str).ok_or_else(|| format!("invalid mode: {}", mode_str))?;
+    Ok(Some(DevSpec {
+        path: path.to_owned(),
+        mode: Mode(perms).with_type(file_type),
+        major,
+        minor,
+    }))
+}
+
+/// uid and gid mappings of `remap-owners`, old id to new id
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct OwnerMap {
+    pub uids: Vec<(u32, u32)>,
+    pub gids: Vec<(u32, u32)>,
+}
+
+/// "olduid:newuid" or "u:olduid:newuid" and "g:oldgid:newgid" lines, empty lines and comments starting with '#' are ignored
+pub fn parse_owner_map(content: &str) -> Result<OwnerMap, String> {
+    let mut map = OwnerMap::default();
+    for (i, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let fields: Vec<&str> = line.split(':').map(str::trim).collect();
+        let (ids, kind, old, new) = match fields[..] {
+            [old, new] | ["u", old, new] => (&mut map.uids, "uid", old, new),
+            ["g", old, new] => (&mut map.gids, "gid", old, new),
+            _ => return Err(format!("line {}: expect olduid:newuid or g:oldgid:newgid, got: {}", i + 1, line)),
+        };
+        let parse = |id: &str| id.parse::<u32>().map_err(|_| format!("line {}: invalid {}: {}", i + 1, kind, id));
+        let (old, new) = (parse(old)?, parse(new)?);
+        if ids.iter().any(|(o, _)| *o == old) {
+            return Err(format!("line {}: {} {} is mapped twice", i + 1, kind, old));
+        }
+        ids.push((old, new));
+    }
+    Ok(map)
+}
+
 fn line_parse(line: &str) -> Result<(String, u32), ()> {
     let mut tokens = line.split(':').fuse();
     let name = tokens.next().ok_or(())?;
@@ -205,42 +448,275 @@ fn line_parse(line: &str) -> Result<(String, u32), ()> {
     Ok((name.to_string(), uid))
 }
 
-// name:x:uid:gid
+// name:x:uid:gid:gecos:home:shell
 #[derive(Debug)]
 pub struct User {
     pub name: String,
     pub uid: u32,
+    /// primary group
+    pub gid: u32,
+}
+
+fn user_line_parse(line: &str) -> Result<User, ()> {
+    let (name, uid) = line_parse(line)?;
+    let gid: u32 = line.split(':').nth(3).ok_or(())?.parse().map_err(|_e| { () })?;
+    Ok(User { name, uid, gid })
 }
 
 pub fn load_users(rootfs: &Path) -> Option<Vec<User>> {
-    let file = File::open(rootfs.join("etc/passwd")).ok()?;
+    load_users_from(&rootfs.join("etc/passwd"))
+}
+
+/// users of a passwd file, like one given by `--passwd`
+pub fn load_users_from(passwd: &Path) -> Option<Vec<User>> {
+    let file = File::open(passwd).ok()?;
     let reader = BufReader::new(file);
 
     let users = reader.lines()
     .filter_map(|l| l.ok())
-    .filter_map(|l| line_parse(&l).ok())
-    .map(|(name, uid)| User { name, uid } )
+    .filter_map(|l| user_line_parse(&l).ok())
     .collect();
 
     Some(users)
 }
 
-// name:x:gid
+// name:x:gid:user1,user2
 #[derive(Debug)]
 pub struct Group {
     pub name: String,
     pub gid: u32,
+    /// supplementary members
+    pub members: Vec<String>,
+}
+
+fn group_line_parse(line: &str) -> Result<Group, ()> {
+    let (name, gid) = line_parse(line)?;
+    let members = line.split(':').nth(3)
+    .map(|m| m.split(',').map(str::trim).filter(|m| !m.is_empty()).map(str::to_string).collect())
+    .unwrap_or_default();
+    Ok(Group { name, gid, members })
 }
 
 pub fn load_groups(rootfs: &Path) -> Option<Vec<Group>> {
-    let file = File::open(rootfs.join("etc/group")).ok()?;
+    load_groups_from(&rootfs.join("etc/group"))
+}
+
+/// groups of a group file, like one given by `--group`
+pub fn load_groups_from(group: &Path) -> Option<Vec<Group>> {
+    let file = File::open(group).ok()?;
     let reader = BufReader::new(file);
 
     let groups = reader.lines()
     .filter_map(|l| l.ok())
-    .filter_map(|l| line_parse(&l).ok())
-    .map(|(name, gid)| Group { name, gid } )
+    .filter_map(|l| group_line_parse(&l).ok())
     .collect();
 
     Some(groups)
 }
+
+/// rules of `audit`, a file matching any of them is reported
+#[derive(Debug, Default, Clone, Copy)]
+pub struct AuditRules {
+    /// writable by others, except symlinks which are always 777 and sticky directories like /tmp
+    pub world_writable: bool,
+    /// setuid or setgid
+    pub setuid: bool,
+    pub owned_by: Option<u32>,
+}
+
+impl AuditRules {
+    /// `false` if only `--case-collisions` is audited, no metadata is read then
+    pub fn any(&self) -> bool {
+        self.world_writable || self.setuid || self.owned_by.is_some()
+    }
+
+    /// names of the rules the file matches
+    pub fn findings(&self, mode: Option<Mode>, uid: Option<u32>) -> Vec<&'static str> {
+        let mut findings = vec![];
+        if let Some(mode) = mode {
+            if self.world_writable && (mode.0 & S_IWOTH) != 0 && mode.file_type() != StModeType::LNK
+            && !(mode.file_type() == StModeType::DIR && mode.sticky()) {
+                findings.push("world-writable");
+            }
+            if self.setuid && mode.suid() {
+                findings.push("setuid");
+            }
+            if self.setuid && mode.sgid() {
+                findings.push("setgid");
+            }
+        }
+        if self.owned_by.is_some_and(|owner| uid == Some(owner)) {
+            findings.push("owned-by");
+        }
+        findings
+    }
+}
+
+#[test]
+fn test_user_line_parse() {
+    let u = user_line_parse("alice:x:1000:1001:Alice,,,:/home/alice:/bin/bash").unwrap();
+    assert_eq!(u.name, "alice");
+    assert_eq!(u.uid, 1000);
+    assert_eq!(u.gid, 1001);
+
+    assert!(user_line_parse("broken:x:1000").is_err());
+    assert!(user_line_parse("broken:x:abc:1000").is_err());
+}
+
+#[test]
+fn test_group_line_parse() {
+    let g = group_line_parse("sudo:x:27:alice,bob").unwrap();
+    assert_eq!(g.name, "sudo");
+    assert_eq!(g.gid, 27);
+    assert_eq!(g.members, vec!["alice", "bob"]);
+
+    let g = group_line_parse("alice:x:1000:").unwrap();
+    assert!(g.members.is_empty());
+
+    let g = group_line_parse("nogroup:x:65534").unwrap();
+    assert_eq!(g.gid, 65534);
+    assert!(g.members.is_empty());
+}
+
+#[test]
+fn test_mode() {
+    let mut mode = Mode(0o100644);
+    assert_eq!(mode.file_type(), StModeType::REG);
+    assert_eq!(mode.perms(), 0o644);
+    assert_eq!(mode.to_string(), "-rw-r--r--");
+    assert_eq!(format!("{:06o}", mode), "100644");
+
+    mode.set_suid(true);
+    mode.set_sticky(true);
+    assert_eq!(mode.to_string(), "-rwSr--r-T");
+    mode.set_suid(false);
+    assert!(!mode.suid() && mode.sticky() && !mode.sgid());
+
+    let mode = mode.with_type(StModeType::DIR).with_perms(0o755);
+    assert_eq!(u32::from(mode), 0o040755);
+    assert_eq!(Mode::from(0o040755), mode);
+}
+
+#[test]
+fn test_chmod_keep_type() {
+    let types = [
+        StModeType::FIFO, StModeType::CHR, StModeType::DIR, StModeType::BLK,
+        StModeType::REG, StModeType::LNK, StModeType::SOCK,
+    ];
+    let modes_strs = [
+        "0", "7777", "0644", "755", "1777",
+        "a+rwxst", "a-rwxst", "u+s", "g-s", "o+t", "+t", "-t",
+        "u+rwx,g-w,o-rwx", "ug+x", "go-r", "a+st,u-s",
+    ];
+    for t in types {
+        for perms in [0o0000, 0o0644, 0o7777] {
+            let mode = Mode(t as u32 | perms);
+            for modes_str in modes_strs {
+                if let Ok(newmode) = chmod_all(mode, modes_str) {
+                    assert_eq!(newmode.file_type(), t, "{} {:06o}", modes_str, mode);
+                }
+            }
+        }
+    }
+
+    assert_eq!(chmod_all(Mode(0o100644), "0755"), Ok(Mode(0o100755)));
+    assert_eq!(chmod_all(Mode(0o040755), "o+t"), Ok(Mode(0o041755)));
+    assert_eq!(chmod_all(Mode(0o100644), "17777"), Err(()));
+}
+
+#[test]
+fn test_chmod_multiple_who() {
+    assert_eq!(chmod_all(Mode(0o100400), "ug+rw"), Ok(Mode(0o100660)));
+    assert_eq!(chmod_all(Mode(0o100755), "go-rx"), Ok(Mode(0o100700)));
+    assert_eq!(chmod_all(Mode(0o100644), "uo+x"), Ok(Mode(0o100745)));
+    assert_eq!(chmod_all(Mode(0o100644), "ua-r"), Ok(Mode(0o100200)));
+    assert_eq!(chmod_all(Mode(0o100644), "+x"), Ok(Mode(0o100755)));
+    assert_eq!(chmod_all(Mode(0o100644), "-r"), Ok(Mode(0o100200)));
+    assert_eq!(chmod_all(Mode(0o040755), "+t"), Ok(Mode(0o041755)));
+    assert_eq!(chmod_all(Mode(0o100755), "ug+s"), Ok(Mode(0o106755)));
+    assert_eq!(chmod_all(Mode(0o100644), "ugx+r"), Err(()));
+}
+
+#[test]
+fn test_chmod_octal_then_symbolic() {
+    assert_eq!(chmod_all(Mode(0o100600), "644,u+x"), Ok(Mode(0o100744)));
+    assert_eq!(chmod_all(Mode(0o040000), "0755,g-w,o-rwx"), Ok(Mode(0o040750)));
+    assert_eq!(chmod_all(Mode(0o100644), "0755, o-x"), Ok(Mode(0o100754)));
+    assert_eq!(chmod_all(Mode(0o100644), "u+x,644"), Err(()));
+    assert_eq!(chmod_all(Mode(0o100644), "644,755"), Err(()));
+    assert_eq!(chmod_all(Mode(0o100644), "17777,u+x"), Err(()));
+    assert_eq!(chmod_all(Mode(0o100644), "0758,u+x"), Err(()));
+}
+
+#[test]
+fn test_parse_format_mode() {
+    assert_eq!(parse_mode("0755"), Some(0o755));
+    assert_eq!(parse_mode("u=rwx,g=rx,o=rx"), Some(0o755));
+    assert_eq!(parse_mode("a=r,u+w"), Some(0o644));
+    assert_eq!(parse_mode("u=rwxs,g=,o=t"), Some(0o5700));
+    assert_eq!(parse_mode("17777"), None);
+    assert_eq!(parse_mode("u=q"), None);
+    assert_eq!(parse_mode("=rwx"), None);
+
+    assert_eq!(format_mode_symbolic(0o755), "u=rwx,g=rx,o=rx");
+    assert_eq!(format_mode_symbolic(0o100644), "u=rw,g=r,o=r");
+    assert_eq!(format_mode_symbolic(0o7000), "u=s,g=s,o=t");
+    assert_eq!(lsperms(0o040755), "drwxr-xr-x");
+
+    // every permission, setid and sticky combination, with and without type of file
+    for perms in 0..=ST_MODE_PERMS_MASK {
+        for t in [0, ST_MODE_TYPE_REG, ST_MODE_TYPE_DIR] {
+            let s = format_mode_symbolic(t | perms);
+            assert_eq!(parse_mode(&s), Some(perms), "{}", s);
+        }
+        assert_eq!(parse_mode(&format!("{:04o}", perms)), Some(perms));
+    }
+}
+
+#[test]
+fn test_parse_dev_spec() {
+    assert_eq!(parse_dev_spec("  # comment"), Ok(None));
+    assert_eq!(parse_dev_spec(""), Ok(None));
+    assert_eq!(parse_dev_spec("/dev/null c 1 3 0666"), Ok(Some(DevSpec {
+        path: "/dev/null".to_owned(),
+        mode: Mode(0o020666),
+        major: 1,
+        minor: 3,
+    })));
+    assert_eq!(parse_dev_spec("/dev/sda\tb 8 0 u=rw,g=rw").unwrap().unwrap().mode, Mode(0o060660));
+    assert!(parse_dev_spec("/dev/null p 1 3 0666").is_err());
+    assert!(parse_dev_spec("/dev/null c 1 3").is_err());
+    assert!(parse_dev_spec("/dev/null c x 3 0666").is_err());
+    assert!(parse_dev_spec("/dev/null c 1 3 0999").is_err());
+}
+
+#[test]
+fn test_parse_owner_map() {
+    let map = parse_owner_map("# old to new\n1000:1001\n\nu:0:0\ng: 1000 : 1001\n").unwrap();
+    assert_eq!(map, OwnerMap { uids: vec![(1000, 1001), (0, 0)], gids: vec![(1000, 1001)] });
+    assert!(parse_owner_map("1000").is_err());
+    assert!(parse_owner_map("x:1:2").is_err());
+    assert!(parse_owner_map("g:a:2").is_err());
+    assert!(parse_owner_map("1000:1001\nu:1000:1002").is_err());
+}
+
+#[test]
+fn test_audit_rules() {
+    let rules = AuditRules { world_writable: true, setuid: true, owned_by: Some(0) };
+    assert_eq!(rules.findings(Some(Mode(0o104755)), Some(0)), vec!["setuid", "owned-by"]);
+    assert_eq!(rules.findings(Some(Mode(0o102757)), Some(1000)), vec!["world-writable", "setgid"]);
+    assert!(rules.findings(Some(Mode(0o120777)), Some(1000)).is_empty());
+    assert!(rules.findings(Some(Mode(0o041777)), Some(1000)).is_empty());
+    assert_eq!(rules.findings(Some(Mode(0o040777)), Some(1000)), vec!["world-writable"]);
+    assert!(rules.findings(None, None).is_empty());
+    assert!(AuditRules::default().findings(Some(Mode(0o106777)), Some(0)).is_empty());
+}
+
+#[test]
+fn test_ansi_colored() {
+    assert_eq!(ansi_colored("drwxr-xr-x", 0o040755), "\x1b[01;34mdrwxr-xr-x\x1b[0m");
+    assert_eq!(ansi_colored("-rwsr-xr-x", 0o104755), "\x1b[37;41m-rwsr-xr-x\x1b[0m");
+    assert_eq!(ansi_colored("-rwxr-xr-x", 0o100755), "\x1b[01;32m-rwxr-xr-x\x1b[0m");
+    assert_eq!(ansi_colored("-rw-r--r--", 0o100644), "-rw-r--r--");
+    assert_eq!(ansi_colored("SYMLINK", ST_MODE_TYPE_LNK), "\x1b[01;36mSYMLINK\x1b[0m");
+}
@@ -22,14 +22,24 @@ impl LxfsTime {
     pub fn new(tv_sec: u64, tv_nsec: u32) -> Self {
         Self { tv_sec, tv_nsec }
     }
+
+    /// `<seconds>.<nanoseconds>` since the Unix epoch, exact and without a timezone, read back by `parse_time` with a `@` prefix
+    pub fn to_epoch_string(&self) -> String {
+        format!("{}.{:09}", self.tv_sec, self.tv_nsec)
+    }
 }
 
+/// a corrupt time out of the range of `OffsetDateTime` is shown as `<invalid time: tv_sec: S, tv_nsec: N>`,
+/// an error here would abort the whole `view` output
 impl Display for LxfsTime {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let odt = OffsetDateTime::from_unix_timestamp(self.tv_sec as i64).map_err(|_| std::fmt::Error)?;
-        let odt = odt + Duration::nanoseconds(self.tv_nsec as i64);
-        f.write_str(&odt.format(&FILE_TIME_FORMAT).map_err(|_| std::fmt::Error)?)?;
-        Ok(())
+        let formatted = OffsetDateTime::from_unix_timestamp(self.tv_sec as i64).ok()
+            .and_then(|odt| odt.checked_add(Duration::nanoseconds(self.tv_nsec as i64)))
+            .and_then(|odt| odt.format(&FILE_TIME_FORMAT).ok());
+        match formatted {
+            Some(s) => f.write_str(&s),
+            None => write!(f, "<invalid time: tv_sec: {}, tv_nsec: {}>", self.tv_sec, self.tv_nsec),
+        }
     }
 }
 
@@ -106,11 +116,21 @@ pub fn filetime_to_lxfs_time(ft: FILETIME) -> LxfsTime {
     u64_to_lxfs_time(t64)
 }
 
+/// the current time
+pub fn now() -> LxfsTime {
+    let odt = OffsetDateTime::now_utc();
+    LxfsTime::new(odt.unix_timestamp() as u64, odt.nanosecond())
+}
+
 pub fn lxfs_time_to_u64(lt: LxfsTime) -> u64 {
     (lt.tv_sec * 10000000u64) + 116444736000000000u64 + (lt.tv_nsec as u64/100)
 }
 
+/// FILETIME before 1970 saturates to the unix epoch, `LxfsTime` cannot hold it
 pub fn u64_to_lxfs_time(t64: u64) -> LxfsTime {
+    if t64 < 116444736000000000u64 {
+        return LxfsTime::new(0, 0);
+    }
     let (sec, ns100) = ( t64 / 10000000u64, t64 % 10000000u64);
     LxfsTime {
         tv_sec: sec - 11644473600u64,
@@ -124,6 +144,52 @@ pub static FILE_TIME_FORMAT: LazyLock<Vec<format_description::FormatItem<'static
     format_description::parse(FILE_TIME_FORMAT_STR).unwrap()
 });
 
+/// `@SECS[.NSEC]`, the fraction is up to 9 digits
+fn parse_unix_time(s: &str) -> Result<LxfsTime, String> {
+    let (secs, frac) = s.split_once('.').unwrap_or((s, "0"));
+    let tv_sec = secs.parse::<u64>().map_err(|_| format!("invalid unix seconds: {}", s))?;
+    if frac.is_empty() || frac.len() > 9 || !frac.bytes().all(|b| b.is_ascii_digit()) {
+        return Err(format!("invalid fraction of unix seconds, at most 9 digits: {}", s));
+    }
+    // "5" is 500000000 ns
+    let tv_nsec = format!("{:0<9}", frac).parse::<u32>().unwrap();
+    Ok(LxfsTime::new(tv_sec, tv_nsec))
+}
+
+/// unix seconds, `@SECS[.NSEC]`, `YYYY-MM-DD`, `YYYY-MM-DD HH:MM:SS[.FRACTION][ UTC]` in UTC as `view` displays,
+/// or RFC 3339, fractions are kept to nanoseconds
+pub fn parse_time(s: &str) -> Result<LxfsTime, String> {
+    use time::{Date, PrimitiveDateTime, Time};
+    use time::format_description::well_known::Rfc3339;
+
+    let s = s.trim();
+    if let Ok(tv_sec) = s.parse::<u64>() {
+        return Ok(LxfsTime::new(tv_sec, 0));
+    }
+    if let Some(unix_time) = s.strip_prefix('@') {
+        return parse_unix_time(unix_time);
+    }
+
+    // runtime-parsed format descriptions do not support `[optional]`, try each form instead
+    let local = s.strip_suffix(" UTC").unwrap_or(s);
+    let odt = if let Ok(odt) = OffsetDateTime::parse(s, &Rfc3339) {
+        odt
+    } else if let Ok(pdt) = PrimitiveDateTime::parse(local, &format_description::parse(
+        "[year]-[month]-[day] [hour]:[minute]:[second].[subsecond]").unwrap()) {
+        pdt.assume_utc()
+    } else if let Ok(pdt) = PrimitiveDateTime::parse(local, &format_description::parse(
+        "[year]-[month]-[day] [hour]:[minute]:[second]").unwrap()) {
+        pdt.assume_utc()
+    } else if let Ok(date) = Date::parse(s, &format_description::parse("[year]-[month]-[day]").unwrap()) {
+        PrimitiveDateTime::new(date, Time::MIDNIGHT).assume_utc()
+    } else {
+        return Err(format!("invalid time: {}", s));
+    };
+
+    let tv_sec = u64::try_from(odt.unix_timestamp()).map_err(|_| format!("time before 1970: {}", s))?;
+    Ok(LxfsTime::new(tv_sec, odt.nanosecond()))
+}
+
 #[test]
 fn test_convert() {
     let tv = LxfsTime {
@@ -146,3 +212,56 @@ fn test_display() {
 
     assert_eq!("2024-10-24 03:45:25.0034801 UTC", odt.format(&FILE_TIME_FORMAT).unwrap());
 }
+
+#[test]
+fn test_display_invalid_time() {
+    assert_eq!(LxfsTime::new(1729741525, 3480100).to_string(), "2024-10-24 03:45:25.0034801 UTC");
+    assert_eq!(LxfsTime::new(i64::MAX as u64, 0).to_string(), format!("<invalid time: tv_sec: {}, tv_nsec: 0>", i64::MAX));
+    assert_eq!(LxfsTime::new(1 << 62, 5).to_string(), format!("<invalid time: tv_sec: {}, tv_nsec: 5>", 1u64 << 62));
+}
+
+#[test]
+fn test_parse_time() {
+    assert_eq!(parse_time("1729741525"), Ok(LxfsTime::new(1729741525, 0)));
+    assert_eq!(parse_time("2024-10-24"), Ok(LxfsTime::new(1729728000, 0)));
+    assert_eq!(parse_time("2024-10-24 03:45:25"), Ok(LxfsTime::new(1729741525, 0)));
+    assert_eq!(parse_time("2024-10-24T11:45:25.5+08:00"), Ok(LxfsTime::new(1729741525, 500000000)));
+    assert!(parse_time("1969-12-31").is_err());
+    assert!(parse_time("yesterday").is_err());
+}
+
+#[test]
+fn test_parse_time_formats() {
+    let tv = LxfsTime::new(1729741525, 3480100);
+    assert_eq!(parse_time("2024-10-24T03:45:25.0034801Z"), Ok(tv));
+    assert_eq!(parse_time("@1729741525"), Ok(LxfsTime::new(1729741525, 0)));
+    assert_eq!(parse_time("@1729741525.0034801"), Ok(tv));
+    assert_eq!(parse_time("@1729741525.123456789"), Ok(LxfsTime::new(1729741525, 123456789)));
+    assert!(parse_time("@1729741525.1234567890").is_err());
+    assert!(parse_time("@1729741525.").is_err());
+    assert!(parse_time("@-1").is_err());
+    assert!(parse_time("@1.2e3").is_err());
+
+    // the display format of `view` round trips
+    assert_eq!(parse_time(&tv.to_string()), Ok(tv));
+    assert_eq!(parse_time("2024-10-24 03:45:25.0034801"), Ok(tv));
+    assert_eq!(parse_time("2024-10-24 03:45:25 UTC"), Ok(LxfsTime::new(1729741525, 0)));
+    assert!(parse_time("2024-10-24 03:45:25 CST").is_err());
+}
+
+#[test]
+fn test_epoch_string() {
+    assert_eq!(LxfsTime::new(1729741525, 123456789).to_epoch_string(), "1729741525.123456789");
+    assert_eq!(LxfsTime::new(1729741525, 5).to_epoch_string(), "1729741525.000000005");
+    // nanoseconds below 100ns survive the round trip, a FILETIME cannot hold them
+    let t = LxfsTime::new(1729741525, 123456789);
+    assert_eq!(parse_time(&format!("@{}", t.to_epoch_string())), Ok(t));
+    assert_eq!(u64_to_lxfs_time(lxfs_time_to_u64(t)), LxfsTime::new(1729741525, 123456700));
+}
+
+#[test]
+fn test_filetime_before_1970() {
+    assert_eq!(u64_to_lxfs_time(0), LxfsTime::new(0, 0));
+    assert_eq!(u64_to_lxfs_time(116444736000000000u64 - 1), LxfsTime::new(0, 0));
+    assert_eq!(u64_to_lxfs_time(116444736000000000u64 + 10000001), LxfsTime::new(1, 100));
+}
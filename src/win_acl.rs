@@ -0,0 +1,127 @@
+use std::ffi::c_void;
+use std::io::{Error, Result, Write};
+use std::ptr::null_mut;
+
+use windows::core::{PCWSTR, PWSTR};
+use windows::Win32::Foundation::{LocalFree, HANDLE, HLOCAL};
+use windows::Win32::Security::Authorization::{ConvertSidToStringSidW, GetSecurityInfo, SE_FILE_OBJECT};
+use windows::Win32::Security::{GetAce, LookupAccountSidW, ACCESS_ALLOWED_ACE, ACE_HEADER, ACL, DACL_SECURITY_INFORMATION, GROUP_SECURITY_INFORMATION, INHERITED_ACE, OWNER_SECURITY_INFORMATION, PSECURITY_DESCRIPTOR, PSID, SID_NAME_USE};
+
+use crate::wsl_file::FmtOptions;
+
+const ACCESS_ALLOWED_ACE_TYPE: u8 = 0;
+const ACCESS_DENIED_ACE_TYPE: u8 = 1;
+
+/// masks shown by `icacls`, from the widest
+const ACCESS_MASKS: &[(u32, &str)] = &[
+    (0x1F01FF, "F"),
+    (0x1301BF, "M"),
+    (0x1201BF, "RXW"),
+    (0x1200A9, "RX"),
+    (0x120089, "R"),
+    (0x100116, "W"),
+];
+
+fn access_mask_display(mask: u32) -> String {
+    if let Some((_, name)) = ACCESS_MASKS.iter().find(|(m, _)| *m == mask) {
+        name.to_string()
+    } else {
+        format!("{:#010x}", mask)
+    }
+}
+
+/// `DOMAIN\name` of the SID, or the string SID if it cannot be resolved
+unsafe fn sid_display(sid: PSID) -> String {
+    let mut name = [0u16; 256];
+    let mut name_len = name.len() as u32;
+    let mut domain = [0u16; 256];
+    let mut domain_len = domain.len() as u32;
+    let mut sid_name_use = SID_NAME_USE::default();
+    if LookupAccountSidW(
+        PCWSTR::null(),
+        sid,
+        PWSTR(name.as_mut_ptr()),
+        &mut name_len,
+        PWSTR(domain.as_mut_ptr()),
+        &mut domain_len,
+        &mut sid_name_use,
+    ).is_ok() {
+        let name = String::from_utf16_lossy(&name[..name_len as usize]);
+        let domain = String::from_utf16_lossy(&domain[..domain_len as usize]);
+        return if domain.is_empty() { name } else { format!("{}\\{}", domain, name) };
+    }
+
+    let mut sid_str = PWSTR(null_mut());
+    if ConvertSidToStringSidW(sid, &mut sid_str).is_ok() {
+        let s = sid_str.to_string().unwrap_or_default();
+        let _ = LocalFree(HLOCAL(sid_str.as_ptr() as _));
+        return s;
+    }
+    "INVALID_SID".to_owned()
+}
+
+/// owner, group and DACL of the file, the handle must be opened with `READ_CONTROL`
+pub fn fmt_win_acl(f: &mut dyn Write, file_handle: HANDLE, opts: &FmtOptions) -> Result<()> {
+    let mut owner = PSID::default();
+    let mut group = PSID::default();
+    let mut dacl: *mut ACL = null_mut();
+    let mut sd = PSECURITY_DESCRIPTOR::default();
+
+    unsafe {
+        let err = GetSecurityInfo(
+            file_handle,
+            SE_FILE_OBJECT,
+            OWNER_SECURITY_INFORMATION | GROUP_SECURITY_INFORMATION | DACL_SECURITY_INFORMATION,
+            Some(&mut owner),
+            Some(&mut group),
+            Some(&mut dacl),
+            None,
+            Some(&mut sd),
+        );
+        if err.is_err() {
+            eprintln!("[ERROR] GetSecurityInfo: {:#x}", err.0);
+            return Err(Error::from_raw_os_error(err.0 as i32));
+        }
+
+        let result = (|| {
+            writeln!(f, "{}{}", opts.label("Windows Owner:"), sid_display(owner))?;
+            writeln!(f, "{}{}", opts.label("Windows Group:"), sid_display(group))?;
+            if dacl.is_null() {
+                writeln!(f, "{}{}", opts.label("Windows DACL:"), "NULL, everyone has full access")?;
+                return Ok(());
+            }
+            writeln!(f, "Windows DACL:")?;
+            for i in 0..(*dacl).AceCount as u32 {
+                let mut ace: *mut c_void = null_mut();
+                if GetAce(dacl, i, &mut ace).is_err() {
+                    continue;
+                }
+                let header = &*(ace as *const ACE_HEADER);
+                let kind = match header.AceType {
+                    ACCESS_ALLOWED_ACE_TYPE => "allow",
+                    ACCESS_DENIED_ACE_TYPE => "deny",
+                    other => {
+                        writeln!(f, "{}type {}", opts.label("  UNSUPPORTED_ACE"), other)?;
+                        continue;
+                    },
+                };
+                // allowed and denied ACE have the same layout
+                let ace = &*(ace as *const ACCESS_ALLOWED_ACE);
+                let sid = PSID(&ace.SidStart as *const u32 as *mut c_void);
+                let inherited = if header.AceFlags as u32 & INHERITED_ACE.0 != 0 { " inherited" } else { "" };
+                writeln!(f, "{}{} ({}){}", opts.label(&format!("  {}", sid_display(sid))), kind, access_mask_display(ace.Mask), inherited)?;
+            }
+            Ok(())
+        })();
+
+        let _ = LocalFree(HLOCAL(sd.0));
+        result
+    }
+}
+
+#[test]
+fn test_access_mask_display() {
+    assert_eq!(access_mask_display(0x1F01FF), "F");
+    assert_eq!(access_mask_display(0x1200A9), "RX");
+    assert_eq!(access_mask_display(0x1), "0x00000001");
+}
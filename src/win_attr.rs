@@ -0,0 +1,154 @@
+use std::io::{Error, ErrorKind, Result};
+use std::os::windows::fs::OpenOptionsExt;
+use std::os::windows::io::AsRawHandle;
+use std::path::Path;
+
+use windows::Win32::Foundation::HANDLE;
+use windows::Win32::Storage::FileSystem::{FILE_FLAG_BACKUP_SEMANTICS, FILE_FLAG_OPEN_REPARSE_POINT, FILE_READ_ATTRIBUTES, FILE_WRITE_ATTRIBUTES};
+
+use crate::ntfs_io::{query_file_basic_infomation, set_file_basic_infomation};
+
+pub const FILE_ATTRIBUTE_READONLY: u32 = 0x1;
+pub const FILE_ATTRIBUTE_HIDDEN: u32 = 0x2;
+pub const FILE_ATTRIBUTE_SYSTEM: u32 = 0x4;
+pub const FILE_ATTRIBUTE_ARCHIVE: u32 = 0x20;
+/// set alone, means no other attribute, a zero `FileAttributes` means not changed to `NtSetInformationFile`
+pub const FILE_ATTRIBUTE_NORMAL: u32 = 0x80;
+
+/// the attributes `win-attr` changes, as `attrib` letters
+const SETTABLE: &[(char, u32)] = &[
+    ('r', FILE_ATTRIBUTE_READONLY),
+    ('h', FILE_ATTRIBUTE_HIDDEN),
+    ('s', FILE_ATTRIBUTE_SYSTEM),
+    ('a', FILE_ATTRIBUTE_ARCHIVE),
+];
+
+/// names shown by `view`, from the lowest bit
+const NAMES: &[(u32, &str)] = &[
+    (FILE_ATTRIBUTE_READONLY, "READONLY"),
+    (FILE_ATTRIBUTE_HIDDEN, "HIDDEN"),
+    (FILE_ATTRIBUTE_SYSTEM, "SYSTEM"),
+    (0x10, "DIRECTORY"),
+    (FILE_ATTRIBUTE_ARCHIVE, "ARCHIVE"),
+    (FILE_ATTRIBUTE_NORMAL, "NORMAL"),
+    (0x100, "TEMPORARY"),
+    (0x200, "SPARSE_FILE"),
+    (0x400, "REPARSE_POINT"),
+    (0x800, "COMPRESSED"),
+    (0x1000, "OFFLINE"),
+    (0x2000, "NOT_CONTENT_INDEXED"),
+    (0x4000, "ENCRYPTED"),
+];
+
+/// like "READONLY ARCHIVE (0x21)", unknown bits are left in the hex value only
+pub fn fmt_file_attributes(attributes: u32) -> String {
+    let names: Vec<&str> = NAMES.iter().filter(|(bit, _)| attributes & bit != 0).map(|(_, name)| *name).collect();
+    if names.is_empty() {
+        format!("none ({:#x})", attributes)
+    } else {
+        format!("{} ({:#x})", names.join(" "), attributes)
+    }
+}
+
+/// `(set, clear)` masks of changes like "+h", "-r" or "+hs" as `attrib`
+pub fn parse_changes<S: AsRef<str>>(changes: &[S]) -> std::result::Result<(u32, u32), String> {
+    let (mut set, mut clear) = (0, 0);
+    for change in changes {
+        let change = change.as_ref();
+        let (mask, letters) = if let Some(letters) = change.strip_prefix('+') {
+            (&mut set, letters)
+        } else if let Some(letters) = change.strip_prefix('-') {
+            (&mut clear, letters)
+        } else {
+            return Err(format!("invalid change: {:?}, expect +ATTRS or -ATTRS", change));
+        };
+        if letters.is_empty() {
+            return Err(format!("invalid change: {:?}, no attribute", change));
+        }
+        for letter in letters.chars() {
+            let Some((_, bit)) = SETTABLE.iter().find(|(c, _)| *c == letter.to_ascii_lowercase()) else {
+                return Err(format!("invalid attribute: {:?} in {:?}, expect r(eadonly), h(idden), s(ystem) or a(rchive)", letter, change));
+            };
+            *mask |= bit;
+        }
+    }
+    if set & clear != 0 {
+        return Err(format!("attributes both set and cleared: {}", fmt_file_attributes(set & clear)));
+    }
+    Ok((set, clear))
+}
+
+/// `attributes` after the changes, `FILE_ATTRIBUTE_NORMAL` only stands alone
+pub fn apply_changes(attributes: u32, set: u32, clear: u32) -> u32 {
+    let attributes = (attributes & !FILE_ATTRIBUTE_NORMAL & !clear) | set;
+    if attributes == 0 { FILE_ATTRIBUTE_NORMAL } else { attributes }
+}
+
+/// `(old, new)` attributes, set by the basic information with the file times queried, so no time changes;
+/// the file is opened for attributes only, a readonly file refuses to be opened to write
+pub fn set_file_attributes(real_path: &Path, set: u32, clear: u32) -> Result<(u32, u32)> {
+    let file = std::fs::OpenOptions::new()
+        .access_mode(FILE_READ_ATTRIBUTES.0 | FILE_WRITE_ATTRIBUTES.0)
+        .custom_flags(FILE_FLAG_BACKUP_SEMANTICS.0 | FILE_FLAG_OPEN_REPARSE_POINT.0)
+        .open(real_path)?;
+    let handle = HANDLE(file.as_raw_handle());
+    let mut fbi = query_file_basic_infomation(handle)?;
+    let old = fbi.FileAttributes;
+    let new = apply_changes(old, set, clear);
+    if new == old {
+        return Ok((old, new));
+    }
+    fbi.FileAttributes = new;
+    set_file_basic_infomation(handle, &fbi)?;
+    let fbi = query_file_basic_infomation(handle)?;
+    if fbi.FileAttributes != new {
+        return Err(Error::new(ErrorKind::Other, format!("attributes are {} after set", fmt_file_attributes(fbi.FileAttributes))));
+    }
+    Ok((old, new))
+}
+
+#[test]
+fn test_parse_and_apply_changes() {
+    assert_eq!(parse_changes(&["+h", "-r"]), Ok((FILE_ATTRIBUTE_HIDDEN, FILE_ATTRIBUTE_READONLY)));
+    assert_eq!(parse_changes(&["+HS"]), Ok((FILE_ATTRIBUTE_HIDDEN | FILE_ATTRIBUTE_SYSTEM, 0)));
+    assert!(parse_changes(&["h"]).is_err());
+    assert!(parse_changes(&["+"]).is_err());
+    assert!(parse_changes(&["+x"]).is_err());
+    assert!(parse_changes(&["+a", "-a"]).is_err());
+
+    assert_eq!(apply_changes(FILE_ATTRIBUTE_NORMAL, FILE_ATTRIBUTE_ARCHIVE, 0), FILE_ATTRIBUTE_ARCHIVE);
+    assert_eq!(apply_changes(FILE_ATTRIBUTE_ARCHIVE, 0, FILE_ATTRIBUTE_ARCHIVE), FILE_ATTRIBUTE_NORMAL);
+    assert_eq!(apply_changes(0x10 | FILE_ATTRIBUTE_ARCHIVE, 0, FILE_ATTRIBUTE_ARCHIVE), 0x10);
+
+    assert_eq!(fmt_file_attributes(0x21), "READONLY ARCHIVE (0x21)");
+    assert_eq!(fmt_file_attributes(0), "none (0x0)");
+}
+
+#[test]
+fn test_toggle_archive() {
+    let path = std::env::temp_dir().join("wslattr_test_toggle_archive");
+    std::fs::write(&path, b"").unwrap();
+    let times = |path: &Path| {
+        let file = std::fs::File::open(path).unwrap();
+        let fbi = query_file_basic_infomation(HANDLE(file.as_raw_handle())).unwrap();
+        (fbi.LastWriteTime, fbi.ChangeTime)
+    };
+
+    let (_, with_archive) = set_file_attributes(&path, FILE_ATTRIBUTE_ARCHIVE, 0).unwrap();
+    assert_ne!(with_archive & FILE_ATTRIBUTE_ARCHIVE, 0);
+    let before = times(&path);
+
+    let (old, new) = set_file_attributes(&path, 0, FILE_ATTRIBUTE_ARCHIVE).unwrap();
+    assert_eq!(old, with_archive);
+    assert_eq!(new & FILE_ATTRIBUTE_ARCHIVE, 0);
+    assert_eq!(times(&path), before);
+
+    let (old, new) = set_file_attributes(&path, FILE_ATTRIBUTE_ARCHIVE, 0).unwrap();
+    assert_eq!((old, new), (apply_changes(with_archive, 0, FILE_ATTRIBUTE_ARCHIVE), with_archive));
+    // readonly still allows to change attributes
+    set_file_attributes(&path, FILE_ATTRIBUTE_READONLY, 0).unwrap();
+    let (_, new) = set_file_attributes(&path, 0, FILE_ATTRIBUTE_READONLY | FILE_ATTRIBUTE_ARCHIVE).unwrap();
+    assert_eq!(new & (FILE_ATTRIBUTE_READONLY | FILE_ATTRIBUTE_ARCHIVE), 0);
+
+    std::fs::remove_file(&path).unwrap();
+}
@@ -4,44 +4,207 @@ use std::mem::transmute;
 use std::os::windows::ffi::OsStrExt;
 use std::path::Path;
 use std::ptr::null_mut;
+use std::sync::atomic::{AtomicU32, Ordering};
 
 use windows::core::PWSTR;
 use windows::Wdk::Storage::FileSystem::{NtOpenFile, FILE_BASIC_INFORMATION, FILE_OPEN_REPARSE_POINT, FILE_SYNCHRONOUS_IO_NONALERT};
-use windows::Win32::Foundation::{HANDLE, NTSTATUS, STATUS_IO_REPARSE_TAG_NOT_HANDLED, STATUS_REPARSE_POINT_ENCOUNTERED, UNICODE_STRING};
+use windows::Win32::Foundation::{HANDLE, NTSTATUS, STATUS_IO_REPARSE_TAG_NOT_HANDLED, STATUS_REPARSE_POINT_ENCOUNTERED, STATUS_SHARING_VIOLATION, UNICODE_STRING};
 use windows::Win32::System::WindowsProgramming::RtlFreeUnicodeString;
 use windows::Win32::System::IO::IO_STATUS_BLOCK;
 use windows::Wdk::Foundation::{NtClose, OBJECT_ATTRIBUTES};
 
 use windows::Win32::System::Kernel::{OBJ_CASE_INSENSITIVE, OBJ_IGNORE_IMPERSONATED_DEVICEMAP};
 
-use windows::Win32::Storage::FileSystem::{FileAttributeTagInfo, GetFileInformationByHandleEx, FILE_ATTRIBUTE_TAG_INFO, FILE_GENERIC_READ, FILE_GENERIC_WRITE, FILE_SHARE_READ, FILE_SHARE_WRITE};
+use windows::Win32::Storage::FileSystem::{FileAttributeTagInfo, GetFileInformationByHandleEx, FILE_ATTRIBUTE_DIRECTORY, FILE_ATTRIBUTE_TAG_INFO, FILE_GENERIC_READ, FILE_GENERIC_WRITE, FILE_SHARE_READ, FILE_SHARE_WRITE};
 
 use crate::distro::FsType;
-use crate::ntfs_io::{query_file_basic_infomation, read_ea_all};
+use crate::posix::{Mode, StModeType, DEFAULT_DIR_MODE, DEFAULT_MODE};
+use crate::time_utils::LxfsTime;
+use crate::ntfs_io::{query_file_basic_infomation, read_data, read_ea_all, read_reparse_point, set_file_basic_infomation, write_ea};
 
 pub trait WslFileAttributes<'a> {
     fn fs_type(&self) -> FsType;
 
-    fn fmt(&self, f: &mut dyn std::io::Write, distro: Option<&crate::distro::Distro>) -> std::io::Result<()>;
+    fn fmt(&self, f: &mut dyn std::io::Write, distro: Option<&crate::distro::Distro>, opts: &FmtOptions) -> std::io::Result<()>;
 
     fn maybe(&self) -> bool;
 
     fn get_uid(&self) -> Option<u32>;
     fn get_gid(&self) -> Option<u32>;
-    fn get_mode(&self) -> Option<u32>;
+    fn get_mode(&self) -> Option<Mode>;
     fn get_dev_major(&self) -> Option<u32>;
     fn get_dev_minor(&self) -> Option<u32>;
 
     fn set_uid(&mut self, uid: u32);
     fn set_gid(&mut self, gid: u32);
-    fn set_mode(&mut self, mode: u32);
+    fn set_mode(&mut self, mode: Mode);
     fn set_dev_major(&mut self, dev_major: u32);
     fn set_dev_minor(&mut self, dev_minor: u32);
 
+    /// atime, mtime and ctime with nanoseconds, wslfs keeps none of them and uses NTFS times
+    fn get_times(&self) -> Option<(LxfsTime, LxfsTime, LxfsTime)>;
+    fn set_times(&mut self, atime: Option<LxfsTime>, mtime: Option<LxfsTime>, ctime: Option<LxfsTime>);
+
+    /// name and value of all extended attributes
+    fn list_attrs(&self) -> Vec<(String, &[u8])>;
+    fn get_attr(&self, name: &str) -> Option<&[u8]>;
+    /// an empty value is kept as an empty attribute, it is never written as a zero-length EA value,
+    /// which is how `NtSetEaFile` deletes an EA
     fn set_attr(&mut self, name: &str, value: &[u8]);
-    fn rm_attr(&mut self, name: &str);
+    /// `false` if there is no such attribute, a wslfs value with a broken 'lxea' prefix is removed too
+    fn rm_attr(&mut self, name: &str) -> bool;
+
+    /// create the main metadata with defaults if missing, `false` if nothing created
+    fn init_default(&mut self) -> bool;
+
+    /// fix known corruptions, `accept` is asked with the description of each fix,
+    /// returns the descriptions of fixes applied
+    fn repair(&mut self, accept: &mut dyn FnMut(&str) -> bool) -> Vec<String>;
+
+    fn save(&mut self, ea_store: &mut dyn EaStore) -> std::io::Result<()> ;
+}
+
+/// namespace of a linux xattr name, the part before the first '.', names are the same for both fs types:
+/// - wslfs: EA `LX.<name>`, NTFS keeps EA names in upper case, so names are read back in lower case
+/// - lxfs: entry `<name>` of the `LXXATTR` EA, case kept
+///
+/// `user.*` are xattrs of programs, the others mean something to the kernel or to WSL,
+/// `set-attr` and `rm-attr` change them only with `--allow-namespace`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum XattrNamespace {
+    /// `user.*`, e.g. `user.xdg.origin.url`
+    User,
+    /// `trusted.*`, visible only to processes with CAP_SYS_ADMIN
+    Trusted,
+    /// `security.*`, e.g. `security.capability` grants privileges to the program
+    Security,
+    /// `system.*`, e.g. `system.posix_acl_access`, made by the kernel from other metadata
+    System,
+}
+
+impl XattrNamespace {
+    pub fn prefix(&self) -> &'static str {
+        match self {
+            XattrNamespace::User => "user.",
+            XattrNamespace::Trusted => "trusted.",
+            XattrNamespace::Security => "security.",
+            XattrNamespace::System => "system.",
+        }
+    }
+
+    /// `None` if the name is in no namespace, or empty after it
+    pub fn of_name(name: &str) -> Option<XattrNamespace> {
+        use XattrNamespace::*;
+        [User, Trusted, Security, System].into_iter()
+            .find(|ns| name.strip_prefix(ns.prefix()).is_some_and(|rest| !rest.is_empty()))
+    }
+}
+
+/// the linux name of an xattr, also from a wslfs EA name like `LX.USER.FOO`
+pub fn xattr_name(name: &str) -> String {
+    let lx_dot = crate::wslfs::LX_DOT;
+    match name.get(..lx_dot.len()) {
+        Some(prefix) if prefix.eq_ignore_ascii_case(lx_dot) => name[lx_dot.len()..].to_ascii_lowercase(),
+        _ => name.to_owned(),
+    }
+}
+
+/// `*` matches any chars, `?` one char, ASCII case insensitive as wslfs EA names are upper case
+pub fn glob_match(pattern: &str, name: &str) -> bool {
+    let (pattern, name): (Vec<char>, Vec<char>) = (pattern.chars().collect(), name.chars().collect());
+    let (mut p, mut n) = (0, 0);
+    // position of the last `*` and of the name it matched up to, to backtrack
+    let mut star: Option<(usize, usize)> = None;
+    while n < name.len() {
+        if p < pattern.len() && pattern[p] == '*' {
+            star = Some((p, n));
+            p += 1;
+        } else if p < pattern.len() && (pattern[p] == '?' || pattern[p].eq_ignore_ascii_case(&name[n])) {
+            p += 1;
+            n += 1;
+        } else if let Some((star_p, star_n)) = star {
+            p = star_p + 1;
+            n = star_n + 1;
+            star = Some((star_p, star_n + 1));
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|c| *c == '*')
+}
 
-    fn save(&mut self, wsl_file: &mut WslFile) -> std::io::Result<()> ;
+/// layout of the human readable output of `view` and `stat`
+#[derive(Clone, Debug)]
+pub struct FmtOptions {
+    /// width of the label column, including at least one space after the label
+    pub width: usize,
+    /// attribute values longer are truncated, 0 means no truncation
+    pub max_value_len: usize,
+    /// skip access times, stale on a noatime mount
+    pub hide_atime: bool,
+    /// only the times, of NTFS and of lxfs
+    pub only_times: bool,
+    /// ANSI colors for the access string and the type of file
+    pub color: bool,
+    /// glob of attribute and EA names shown, `None` shows all
+    pub name_filter: Option<String>,
+    /// times as `<seconds>.<nanoseconds>` since the Unix epoch, with the source of each
+    pub epoch_time: bool,
+}
+
+/// default of `--max-value-len`
+pub const DEFAULT_MAX_VALUE_LEN: usize = 256;
+
+impl Default for FmtOptions {
+    fn default() -> Self {
+        FmtOptions { width: 28, ..FmtOptions::COMPACT }
+    }
+}
+
+impl FmtOptions {
+    /// labels followed by a single space
+    pub const COMPACT: FmtOptions = FmtOptions { width: 0, max_value_len: DEFAULT_MAX_VALUE_LEN, hide_atime: false, only_times: false, color: false, name_filter: None, epoch_time: false };
+
+    /// `label` padded to the label column
+    pub fn label(&self, label: &str) -> String {
+        format!("{:w$} ", label, w = self.width.saturating_sub(1))
+    }
+
+    /// the attribute or EA is shown by `--name-filter`
+    pub fn shows_name(&self, name: &str) -> bool {
+        self.name_filter.as_deref().map_or(true, |pattern| glob_match(pattern, name))
+    }
+
+    /// a time formatted, or in epoch form followed by `source`, "NTFS" of 100ns or "LXATTRB" of 1ns
+    pub fn time(&self, time: LxfsTime, source: &str) -> String {
+        if self.epoch_time {
+            format!("{} ({})", time.to_epoch_string(), source)
+        } else {
+            time.to_string()
+        }
+    }
+
+    /// `Access:` of the mode, colored as `ls --color` if `color`
+    pub fn access(&self, mode: Mode) -> String {
+        if self.color {
+            crate::posix::ansi_colored(&mode.to_string(), mode.0)
+        } else {
+            mode.to_string()
+        }
+    }
+}
+
+/// EA's and other file data the attributes are loaded from and saved to
+pub trait EaStore {
+    /// `Ok(None)` means no EA's
+    fn read_ea(&self) -> Result<Option<Vec<u8>>>;
+    /// empty value means delete, other EA's are not changed
+    fn write_ea(&mut self, buf: &[u8]) -> Result<()>;
+
+    fn reparse_tag(&self) -> Option<u32>;
+    fn basic_file_info(&self) -> Option<FILE_BASIC_INFORMATION>;
+    fn read_reparse_point(&self) -> Result<Vec<u8>>;
+    fn read_data(&self) -> Result<Vec<u8>>;
 }
 
 #[derive(Default)]
@@ -54,6 +217,9 @@ pub struct WslFile {
     pub reparse_tag: Option<u32>,
 
     pub basic_file_info: Option<FILE_BASIC_INFORMATION>,
+
+    /// writes are dropped and reported as done, to preview a change for `--confirm-each`
+    pub preview: bool,
 }
 
 impl WslFile {
@@ -61,7 +227,7 @@ impl WslFile {
         if !self.file_handle.is_invalid() {
             let nt_status = unsafe { NtClose(self.file_handle) };
             if nt_status.is_err() {
-                println!("[ERROR] NtClose: {:#x}", nt_status.0);
+                eprintln!("[ERROR] NtClose: {:#x}", nt_status.0);
             }
             self.file_handle = HANDLE::default();
         }
@@ -71,12 +237,131 @@ impl WslFile {
         assert!(!self.writable);
         self.close();
         unsafe { open_file_inner(self, true)? };
+        self.writable = true;
         return Ok(());
     }
 
+    pub fn is_dir(&self) -> bool {
+        is_dir(&self.basic_file_info)
+    }
+
+    /// mode for a file without any mode metadata, of the type of its WSL reparse tag if any
+    pub fn default_mode(&self) -> Mode {
+        default_mode_with_tag(&self.basic_file_info, self.reparse_tag)
+    }
+
     pub fn read_ea(&self) -> Result<Option<Vec<u8>>> {
         unsafe { read_ea_all(self.file_handle) }
     }
+
+    /// write back file times queried at open, as NTFS updates them when EA's changed
+    pub fn restore_file_times(&self) -> Result<()> {
+        assert!(self.writable);
+        if self.preview {
+            return Ok(());
+        }
+        if let Some(mut fbi) = self.basic_file_info {
+            fbi.FileAttributes = 0; // keep attributes unchanged
+            set_file_basic_infomation(self.file_handle, &fbi)?;
+        }
+        Ok(())
+    }
+}
+
+impl EaStore for WslFile {
+    fn read_ea(&self) -> Result<Option<Vec<u8>>> {
+        unsafe { read_ea_all(self.file_handle) }
+    }
+
+    fn write_ea(&mut self, buf: &[u8]) -> Result<()> {
+        if self.preview {
+            return Ok(());
+        }
+        unsafe { write_ea(self.file_handle, buf) }
+    }
+
+    fn reparse_tag(&self) -> Option<u32> {
+        self.reparse_tag
+    }
+
+    fn basic_file_info(&self) -> Option<FILE_BASIC_INFORMATION> {
+        self.basic_file_info
+    }
+
+    fn read_reparse_point(&self) -> Result<Vec<u8>> {
+        unsafe { read_reparse_point(self.file_handle) }
+    }
+
+    fn read_data(&self) -> Result<Vec<u8>> {
+        unsafe { read_data(self.file_handle) }
+    }
+}
+
+/// in memory `EaStore` for tests and EA dump files, EA names are upper cased as NTFS does
+#[derive(Default)]
+pub struct MemEaStore {
+    pub eas: Vec<(Vec<u8>, Vec<u8>)>,
+    pub reparse_tag: Option<u32>,
+    pub basic_file_info: Option<FILE_BASIC_INFORMATION>,
+    pub reparse_data: Vec<u8>,
+    pub data: Vec<u8>,
+}
+
+impl MemEaStore {
+    /// EA's of a `FILE_FULL_EA_INFORMATION` buffer, which must be checked by `check_ea_buffer`,
+    /// names are uppercased as NTFS stores them and `write_ea` matches them
+    pub fn from_ea_buffer(buf: &[u8]) -> Self {
+        let eas = crate::ea_parse::parse_ea_to_iter(buf)
+            .map(|ea| (ea.name.to_ascii_uppercase(), ea.value.to_owned()))
+            .collect();
+        MemEaStore { eas, ..Default::default() }
+    }
+}
+
+impl EaStore for MemEaStore {
+    fn read_ea(&self) -> Result<Option<Vec<u8>>> {
+        if self.eas.is_empty() {
+            return Ok(None);
+        }
+        let mut ea_out = crate::ea_parse::EaOut::default();
+        for (name, value) in &self.eas {
+            ea_out.add(name, value);
+        }
+        Ok(Some(ea_out.buffer))
+    }
+
+    fn write_ea(&mut self, buf: &[u8]) -> Result<()> {
+        use crate::ea_parse::{ea_size_after_write, ea_too_large, parse_ea_to_iter, EA_SIZE_MAX};
+
+        let size = ea_size_after_write(self.read_ea()?.as_deref(), buf);
+        if size > EA_SIZE_MAX {
+            return Err(ea_too_large(size));
+        }
+        for ea in parse_ea_to_iter(buf) {
+            let name = ea.name.to_ascii_uppercase();
+            self.eas.retain(|(n, _)| *n != name);
+            if !ea.value.is_empty() {
+                self.eas.push((name, ea.value.to_owned()));
+            }
+        }
+        Ok(())
+    }
+
+    fn reparse_tag(&self) -> Option<u32> {
+        self.reparse_tag
+    }
+
+    fn basic_file_info(&self) -> Option<FILE_BASIC_INFORMATION> {
+        self.basic_file_info
+    }
+
+    fn read_reparse_point(&self) -> Result<Vec<u8>> {
+        Ok(self.reparse_data.clone())
+    }
+
+    fn read_data(&self) -> Result<Vec<u8>> {
+        Ok(self.data.clone())
+    }
 }
 
 impl<'a> Drop for WslFile {
@@ -90,6 +375,31 @@ impl<'a> Drop for WslFile {
     }
 }
 
+pub fn is_dir(basic_file_info: &Option<FILE_BASIC_INFORMATION>) -> bool {
+    basic_file_info.map_or(false, |fbi| fbi.FileAttributes & FILE_ATTRIBUTE_DIRECTORY.0 != 0)
+}
+
+/// directories carry metadata in EA's only, a mode with `S_IFREG` would turn it to a regular file in WSL
+pub fn default_mode(basic_file_info: &Option<FILE_BASIC_INFORMATION>) -> Mode {
+    if is_dir(basic_file_info) {
+        DEFAULT_DIR_MODE
+    } else {
+        DEFAULT_MODE
+    }
+}
+
+/// a wslfs symlink or device may have no `$LXMOD`, its type is of the reparse tag then,
+/// so `chmod 755` does not make it a regular file; a symlink is 0777 as linux shows
+pub fn default_mode_with_tag(basic_file_info: &Option<FILE_BASIC_INFORMATION>, reparse_tag: Option<u32>) -> Mode {
+    use crate::wslfs::WslfsReparseTag;
+
+    match reparse_tag.map(StModeType::from_tag_id) {
+        Some(StModeType::LNK) => DEFAULT_MODE.with_type(StModeType::LNK).with_perms(0o777),
+        Some(StModeType::UNKNOWN) | None => default_mode(basic_file_info),
+        Some(file_type) => DEFAULT_MODE.with_type(file_type),
+    }
+}
+
 pub unsafe fn open_handle(path: &Path, writable: bool) -> Result<WslFile> {
     let mut wsl_file = WslFile::default();
 
@@ -102,7 +412,7 @@ pub unsafe fn open_handle(path: &Path, writable: bool) -> Result<WslFile> {
         null_mut(),
     );
     if nt_status.is_err() {
-        println!("[ERROR] RtlDosPathNameToNtPathName_U_WithStatus: {:#x}", &nt_status.0);
+        eprintln!("[ERROR] RtlDosPathNameToNtPathName_U_WithStatus: {:#x}", &nt_status.0);
         return Err(Error::from_raw_os_error(nt_status.0));
     }
     wsl_file.full_path = full_path;
@@ -115,7 +425,7 @@ pub unsafe fn open_handle(path: &Path, writable: bool) -> Result<WslFile> {
             transmute(&mut file_attribute_tag_info),
             size_of::<FILE_ATTRIBUTE_TAG_INFO>() as u32,
         ) {
-            println!("[ERROR] GetFileInformationByHandleEx {}", &err);
+            eprintln!("[ERROR] GetFileInformationByHandleEx {}", &err);
             return Err(err.into());
         }
         wsl_file.reparse_tag = Some(file_attribute_tag_info.ReparseTag);
@@ -141,6 +451,31 @@ extern "system" {
     ) -> NTSTATUS;
 }
 
+/// retries of `NtOpenFile` when the file is locked by WSL or another process, set by `--open-retries`
+pub static OPEN_RETRIES: AtomicU32 = AtomicU32::new(3);
+/// delay in ms before the first retry, doubled for each retry, set by `--open-retry-delay`
+pub static OPEN_RETRY_DELAY: AtomicU32 = AtomicU32::new(50);
+
+/// `true` if open failed as the file is locked for now, a later open may succeed,
+/// `STATUS_ACCESS_DENIED` is a permission failure, it is neither retried nor skipped
+pub fn is_open_transient_error(nt_status: NTSTATUS) -> bool {
+    nt_status == STATUS_SHARING_VIOLATION
+}
+
+fn retry_open(mut open: impl FnMut() -> NTSTATUS) -> NTSTATUS {
+    let mut delay = OPEN_RETRY_DELAY.load(Ordering::Relaxed) as u64;
+    let mut nt_status = open();
+    for _ in 0..OPEN_RETRIES.load(Ordering::Relaxed) {
+        if !is_open_transient_error(nt_status) {
+            break;
+        }
+        std::thread::sleep(std::time::Duration::from_millis(delay));
+        delay *= 2;
+        nt_status = open();
+    }
+    nt_status
+}
+
 pub unsafe fn open_file_inner(wsl_file: &mut WslFile, writable: bool) -> Result<OpenFileType> {
     let mut isb = IO_STATUS_BLOCK::default();
     let mut oa = OBJECT_ATTRIBUTES::default();
@@ -150,6 +485,8 @@ pub unsafe fn open_file_inner(wsl_file: &mut WslFile, writable: bool) -> Result<
     // donot use OBJ_DONT_REPARSE as it will stop at C:
     oa.Attributes = (OBJ_CASE_INSENSITIVE | OBJ_IGNORE_IMPERSONATED_DEVICEMAP) as u32;
 
+    // without FILE_NO_EA_KNOWLEDGE in open options, EA's flagged FILE_NEED_EA do not fail the open,
+    // without FILE_DIRECTORY_FILE nor FILE_NON_DIRECTORY_FILE, directories and their reparse points are opened as files
     let desire_access = if writable {
          // includes the required FILE_READ_EA and FILE_WRITE_EA access_mask!
         FILE_GENERIC_READ | FILE_GENERIC_WRITE
@@ -162,35 +499,188 @@ pub unsafe fn open_file_inner(wsl_file: &mut WslFile, writable: bool) -> Result<
     } else {
         FILE_SHARE_READ
     };
-    let nt_status = NtOpenFile(
+    let nt_status = retry_open(|| NtOpenFile(
         &mut wsl_file.file_handle,
         desire_access.0,
         &mut oa,
         &mut isb,
         share_access.0,
         FILE_SYNCHRONOUS_IO_NONALERT.0,
-    );
+    ));
     if nt_status.is_err() {
         if nt_status == STATUS_IO_REPARSE_TAG_NOT_HANDLED || nt_status == STATUS_REPARSE_POINT_ENCOUNTERED {
-            let nt_status = NtOpenFile(
+            let nt_status = retry_open(|| NtOpenFile(
                 &mut wsl_file.file_handle,
                 desire_access.0,
                 &mut oa,
                 &mut isb,
                 share_access.0,
                 FILE_SYNCHRONOUS_IO_NONALERT.0 | FILE_OPEN_REPARSE_POINT.0
-            );
+            ));
             if nt_status.is_err() {
-                println!("[ERROR] NtOpenFile: {:#x} , open as REPARSE_POINT", nt_status.0);
+                eprintln!("[ERROR] NtOpenFile: {:#x} , open as REPARSE_POINT", nt_status.0);
                 //println!("{}", error_msg_ntdll(nt_status.0 as u32).unwrap());
-                return Err(Error::from_raw_os_error(nt_status.0));
+                return Err(crate::ntfs_io::check_permission(Error::from_raw_os_error(nt_status.0), false));
             }
             return Ok(OpenFileType::ReparsePoint);
         } else {
-            println!("[ERROR] NtOpenFile: {:#x}", nt_status.0);
+            eprintln!("[ERROR] NtOpenFile: {:#x}", nt_status.0);
             //println!("{}", error_msg_ntdll(nt_status.0 as u32).unwrap());
-            return Err(Error::from_raw_os_error(nt_status.0));
+            return Err(crate::ntfs_io::check_permission(Error::from_raw_os_error(nt_status.0), false));
         }
     }
     return Ok(OpenFileType::Normal);
 }
+
+#[test]
+fn test_restore_file_times_after_chmod() {
+    use crate::lxfs::LxfsParsed;
+
+    let path = std::env::temp_dir().join("wslattr_test_restore_file_times");
+    std::fs::write(&path, b"content").unwrap();
+
+    let mut wsl_file = unsafe { open_handle(&path, false) }.unwrap();
+    let before = wsl_file.basic_file_info.unwrap();
+    wsl_file.reopen_to_write().unwrap();
+
+    std::thread::sleep(std::time::Duration::from_millis(50));
+
+    let ea_parsed = None;
+    let mut lxfs = LxfsParsed::load(&wsl_file, &ea_parsed);
+    lxfs.set_mode(Mode(0o100755));
+    lxfs.save(&mut wsl_file).unwrap();
+    wsl_file.restore_file_times().unwrap();
+
+    let after = query_file_basic_infomation(wsl_file.file_handle).unwrap();
+    drop(wsl_file);
+    let _ = std::fs::remove_file(&path);
+
+    assert_eq!(before.LastWriteTime, after.LastWriteTime);
+    assert_eq!(before.ChangeTime, after.ChangeTime);
+}
+
+#[test]
+fn test_chmod_chown_directory() {
+    use crate::ea_parse::parse_ea;
+    use crate::lxfs::LxfsParsed;
+    use crate::wslfs::WslfsParsed;
+
+    for fs_type in [FsType::Lxfs, FsType::Wslfs] {
+        let path = std::env::temp_dir().join(format!("wslattr_test_directory_{:?}", fs_type));
+        let _ = std::fs::remove_dir(&path);
+        std::fs::create_dir(&path).unwrap();
+
+        let mut wsl_file = unsafe { open_handle(&path, false) }.unwrap();
+        assert!(wsl_file.is_dir());
+        assert!(wsl_file.reparse_tag.is_none());
+        assert_eq!(wsl_file.default_mode(), DEFAULT_DIR_MODE);
+        wsl_file.reopen_to_write().unwrap();
+
+        let ea_parsed = None;
+        let mut lxfs = LxfsParsed::load(&wsl_file, &ea_parsed);
+        let mut wslfs = WslfsParsed::load(&wsl_file, &ea_parsed);
+        let wsl_attrs: &mut dyn WslFileAttributes = match fs_type {
+            FsType::Lxfs => &mut lxfs,
+            FsType::Wslfs => &mut wslfs,
+        };
+        wsl_attrs.set_uid(1000);
+        wsl_attrs.set_gid(1001);
+        wsl_attrs.set_mode(Mode(0o040700));
+        wsl_attrs.save(&mut wsl_file).unwrap();
+
+        let ea_buffer = wsl_file.read_ea().unwrap();
+        drop(wsl_file);
+        let wsl_file = unsafe { open_handle(&path, false) }.unwrap();
+        let ea_parsed = ea_buffer.as_ref().map(|ea_buffer| parse_ea(ea_buffer));
+        let lxfs = LxfsParsed::load(&wsl_file, &ea_parsed);
+        let wslfs = WslfsParsed::load(&wsl_file, &ea_parsed);
+        let wsl_attrs: &dyn WslFileAttributes = match fs_type {
+            FsType::Lxfs => &lxfs,
+            FsType::Wslfs => &wslfs,
+        };
+        let result = (wsl_attrs.get_uid(), wsl_attrs.get_gid(), wsl_attrs.get_mode());
+        drop(wsl_file);
+        let _ = std::fs::remove_dir(&path);
+
+        assert_eq!(result, (Some(1000), Some(1001), Some(Mode(0o040700))), "{:?}", fs_type);
+    }
+}
+
+#[test]
+fn test_fmt_options_label() {
+    assert_eq!(FmtOptions::default().label("$LXUID:"), format!("{:28}", "$LXUID:"));
+    assert_eq!(FmtOptions::COMPACT.label("$LXUID:"), "$LXUID: ");
+    assert_eq!(FmtOptions { width: 4, ..Default::default() }.label("$LXUID:"), "$LXUID: ");
+}
+
+#[test]
+fn test_xattr_namespace() {
+    use crate::ea_parse::parse_ea;
+    use crate::lxfs::LxfsParsed;
+    use crate::wslfs::WslfsParsed;
+
+    assert_eq!(XattrNamespace::of_name("user.foo"), Some(XattrNamespace::User));
+    assert_eq!(XattrNamespace::of_name("security.capability"), Some(XattrNamespace::Security));
+    assert_eq!(XattrNamespace::of_name("user."), None);
+    assert_eq!(XattrNamespace::of_name("USER.foo"), None);
+    assert_eq!(xattr_name("LX.USER.FOO"), "user.foo");
+    assert_eq!(xattr_name("user.Foo"), "user.Foo");
+
+    for fs_type in [FsType::Wslfs, FsType::Lxfs] {
+        let mut ea_store = MemEaStore::default();
+        let mut wslfs = WslfsParsed::default();
+        let mut lxfs = LxfsParsed::default();
+        let wsl_attrs: &mut dyn WslFileAttributes = match fs_type {
+            FsType::Wslfs => &mut wslfs,
+            FsType::Lxfs => &mut lxfs,
+        };
+        wsl_attrs.set_attr(&xattr_name("user.foo"), b"1");
+        wsl_attrs.set_attr(&xattr_name("LX.SECURITY.CAPABILITY"), b"2");
+        // EA names are upper cased by `MemEaStore` as NTFS does
+        wsl_attrs.save(&mut ea_store).unwrap();
+
+        let ea_buffer = ea_store.read_ea().unwrap();
+        let ea_parsed = ea_buffer.as_ref().map(|ea_buffer| parse_ea(ea_buffer));
+        let wslfs = WslfsParsed::load(&ea_store, &ea_parsed);
+        let lxfs = LxfsParsed::load(&ea_store, &ea_parsed);
+        let wsl_attrs: &dyn WslFileAttributes = match fs_type {
+            FsType::Wslfs => &wslfs,
+            FsType::Lxfs => &lxfs,
+        };
+        assert_eq!(wsl_attrs.get_attr("user.foo"), Some(b"1".as_slice()), "{:?}", fs_type);
+        assert_eq!(wsl_attrs.get_attr("security.capability"), Some(b"2".as_slice()), "{:?}", fs_type);
+    }
+}
+
+#[test]
+fn test_glob_match() {
+    assert!(glob_match("user.*", "user.xdg.origin.url"));
+    assert!(glob_match("user.*", "USER.FOO"));
+    assert!(glob_match("LX.*", "LX.USER.FOO"));
+    assert!(!glob_match("LX.*", "$LXUID"));
+    assert!(glob_match("*.origin.*", "user.xdg.origin.url"));
+    assert!(glob_match("user.?", "user.a"));
+    assert!(!glob_match("user.?", "user.ab"));
+    assert!(glob_match("*", ""));
+    assert!(glob_match("a*b*c", "aXbYbZc"));
+    assert!(!glob_match("a*b*c", "aXbYbZ"));
+    assert!(glob_match("user.foo", "USER.FOO"));
+    assert!(!glob_match("user.foo", "user.foobar"));
+
+    let opts = FmtOptions { name_filter: Some("security.*".to_owned()), ..FmtOptions::COMPACT };
+    assert!(opts.shows_name("security.capability"));
+    assert!(!opts.shows_name("user.a"));
+    assert!(FmtOptions::COMPACT.shows_name("user.a"));
+}
+
+#[test]
+fn test_open_access_denied_not_retried() {
+    use windows::Win32::Foundation::STATUS_ACCESS_DENIED;
+
+    assert!(is_open_transient_error(STATUS_SHARING_VIOLATION));
+    assert!(!is_open_transient_error(STATUS_ACCESS_DENIED));
+
+    let mut opens = 0;
+    assert_eq!(retry_open(|| { opens += 1; STATUS_ACCESS_DENIED }), STATUS_ACCESS_DENIED);
+    assert_eq!(opens, 1);
+}
@@ -1,14 +1,16 @@
 use std::borrow::Cow;
 use std::mem::{offset_of, transmute};
 use std::io::Result;
+use std::sync::atomic::Ordering;
 
-use windows::Win32::Foundation::HANDLE;
+use windows::Wdk::Storage::FileSystem::FILE_BASIC_INFORMATION;
 
 use crate::distro::{Distro, FsType};
-use crate::ea_parse::{EaEntry, EaEntryCow, EaEntryRaw};
+use crate::ea_parse::{normalize_ea_name, EaEntry, EaEntryCow, EaEntryRaw, STRICT_EA_NAMES};
 use crate::ntfs_io::{delete_reparse_point, write_reparse_point};
-use crate::posix::{lsperms, StModeType};
-use crate::wsl_file::{open_file_inner, WslFile, WslFileAttributes};
+use crate::posix::{Mode, StModeType};
+use crate::time_utils::LxfsTime;
+use crate::wsl_file::{default_mode_with_tag, open_file_inner, EaStore, FmtOptions, WslFile, WslFileAttributes};
 
 pub const LXUID: &'static str = "$LXUID";
 pub const LXGID: &'static str = "$LXGID";
@@ -25,14 +27,46 @@ pub const LXEA: &'static [u8] = "lxea".as_bytes();
 pub struct WslfsParsed<'a> {
     pub lxuid: Option<Cow<'a, u32>>,
     pub lxgid: Option<Cow<'a, u32>>,
-    pub lxmod: Option<Cow<'a, u32>>,
+    pub lxmod: Option<Cow<'a, Mode>>,
     pub lxdev: Option<Cow<'a, Lxdev>>,
 
     pub lx_dot_ea: Vec<LxDotAttrCow<'a>>,
 
+    /// only WSL reparse tags
     pub reparse_tag: Option<StModeType>,
+    /// reparse tag not from WSL, like dedup or OneDrive, never rewritten
+    pub unknown_reparse_tag: Option<u32>,
 
     pub symlink: Option<String>,
+
+    pub basic_file_info: Option<FILE_BASIC_INFORMATION>,
+
+    /// `$LX*` EA's to delete in save
+    cleared: Vec<WslfsField>,
+}
+
+/// `$LX*` EA's of wslfs
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum WslfsField {
+    /// $LXUID
+    Uid,
+    /// $LXGID
+    Gid,
+    /// $LXMOD
+    Mod,
+    /// $LXDEV
+    Dev,
+}
+
+impl WslfsField {
+    pub fn ea_name(&self) -> &'static str {
+        match self {
+            WslfsField::Uid => LXUID,
+            WslfsField::Gid => LXGID,
+            WslfsField::Mod => LXMOD,
+            WslfsField::Dev => LXDEV,
+        }
+    }
 }
 
 pub struct LxDotAttr<Bytes: AsRef<[u8]>>(EaEntry<Bytes>);
@@ -56,6 +90,17 @@ impl<'a> LxDotAttrCow<'a> {
         self.0.value = Cow::Owned(vec![]);
     }
 
+    /// fix the 'lxea' prefix, `false` if it is not broken
+    pub fn repair_prefix(&mut self) -> bool {
+        let value = match self.check_prefix() {
+            LxeaPrefix::Ok => return false,
+            LxeaPrefix::Double => self.value().to_vec(),
+            LxeaPrefix::Missing => Self::make_value(self.0.value.as_ref()),
+        };
+        self.0.value = Cow::Owned(value);
+        true
+    }
+
     fn make_name(name: &str) -> Vec<u8> {
         let mut name_buf = Vec::with_capacity(LX_DOT.as_bytes().len() + name.as_bytes().len());
         name_buf.append(&mut LX_DOT.as_bytes().to_vec());
@@ -71,7 +116,46 @@ impl<'a> LxDotAttrCow<'a> {
     }
 }
 
+/// names of `LX.LINUX.ATTR.*` EA's, without `LX.` and lowercased as other LX.* names
+pub const LINUX_ATTR_PREFIX: &str = "linux.attr.";
+
+/// `LX.LINUX.ATTR.*` are kept by WSL itself, not xattrs of users like `LX.user.*`,
+/// `set-attr` and `rm-attr` refuse them without `--allow-internal`
+pub fn is_internal_attr_name(name: &str) -> bool {
+    name.to_ascii_lowercase().starts_with(LINUX_ATTR_PREFIX)
+}
+
+/// state of the 'lxea' prefix of a LX.* value
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LxeaPrefix {
+    Ok,
+    /// 'lxea' applied twice, by a tool which prefixed a value read with its prefix
+    Double,
+    /// no 'lxea', WSL cannot read the value
+    Missing,
+}
+
 impl<Bytes: AsRef<[u8]>> LxDotAttr<Bytes> {
+    /// value starts with 'lxea', false for an attribute to remove
+    pub fn is_valid(&self) -> bool {
+        self.0.value.as_ref().starts_with(LXEA)
+    }
+
+    /// an empty value to remove is `Ok`,
+    /// a value which really starts with 'lxea' is reported as `Double` too
+    pub fn check_prefix(&self) -> LxeaPrefix {
+        let v = self.0.value.as_ref();
+        if v.is_empty() {
+            LxeaPrefix::Ok
+        } else if !v.starts_with(LXEA) {
+            LxeaPrefix::Missing
+        } else if v[LXEA.len()..].starts_with(LXEA) {
+            LxeaPrefix::Double
+        } else {
+            LxeaPrefix::Ok
+        }
+    }
+
     pub fn name_ea<'x>(&self) -> &[u8] {
         self.0.name.as_ref()
     }
@@ -87,12 +171,28 @@ impl<Bytes: AsRef<[u8]>> LxDotAttr<Bytes> {
         String::from_utf8(self.name()).unwrap_or(String::from("NAME_ERROR"))
     }
 
+    pub fn is_internal(&self) -> bool {
+        self.name().starts_with(LINUX_ATTR_PREFIX.as_bytes())
+    }
+
     // remove 'lxea'
     pub fn value(&self) -> &[u8] {
         &self.0.value.as_ref()[LXEA.len()..]
     }
 
-    pub fn value_display(&self) -> String {
+    /// the value a conversion keeps, 'lxea' removed if present, a value with a broken prefix is kept as it is,
+    /// `None` for an attribute to remove
+    pub fn value_unprefixed(&self) -> Option<&[u8]> {
+        let v = self.0.value.as_ref();
+        if v.is_empty() {
+            None
+        } else {
+            Some(v.strip_prefix(LXEA).unwrap_or(v))
+        }
+    }
+
+    /// values longer than `max_len` are truncated, 0 means no truncation
+    pub fn value_display(&self, max_len: usize) -> String {
         use std::fmt::Write;
 
         let v = self.0.value.as_ref();
@@ -104,36 +204,62 @@ impl<Bytes: AsRef<[u8]>> LxDotAttr<Bytes> {
             v
         };
 
-        write!(&mut out, "\"").unwrap();
-        crate::escape_utils::escape_bytes_octal(bytes, &mut out, true).unwrap();
-        write!(&mut out, "\"").unwrap();
+        crate::escape_utils::escape_value_display(bytes, max_len, &mut out).unwrap();
 
         out
     }
 }
 
 impl<'a> WslfsParsed<'a> {
-    pub fn load<'b: 'a, 'c>(wsl_file: &'c WslFile, ea_parsed: &'b Option<Vec<EaEntryRaw<'a>>>) -> Self {
+    /// delete the EA in save, as `None` is "unchanged" for other fields, `false` if not set
+    pub fn clear(&mut self, field: WslfsField) -> bool {
+        let was_set = match field {
+            WslfsField::Uid => self.lxuid.take().is_some(),
+            WslfsField::Gid => self.lxgid.take().is_some(),
+            WslfsField::Mod => self.lxmod.take().is_some(),
+            WslfsField::Dev => self.lxdev.take().is_some(),
+        };
+        if was_set && !self.cleared.contains(&field) {
+            self.cleared.push(field);
+        }
+        was_set
+    }
+
+    /// EA names are checked as `--strict` says
+    pub fn load<'b: 'a, 'c>(ea_store: &'c dyn EaStore, ea_parsed: &'b Option<Vec<EaEntryRaw<'a>>>) -> Self {
+        Self::load_with(ea_store, ea_parsed, STRICT_EA_NAMES.load(Ordering::Relaxed))
+    }
+
+    /// EA names with NUL or trailing spaces are ignored if `strict`, or trimmed
+    pub fn load_with<'b: 'a, 'c>(ea_store: &'c dyn EaStore, ea_parsed: &'b Option<Vec<EaEntryRaw<'a>>>, strict: bool) -> Self {
         let mut p = Self::default();
+        p.basic_file_info = ea_store.basic_file_info();
 
-        p.reparse_tag = wsl_file.reparse_tag.map(WslfsReparseTag::from_tag_id);
-        if wsl_file.reparse_tag == Some(IO_REPARSE_TAG_LX_SYMLINK) {
-            p.symlink = read_lx_symlink(wsl_file.file_handle).ok();
+        match ea_store.reparse_tag().map(|t| (t, StModeType::from_tag_id(t))) {
+            Some((t, StModeType::UNKNOWN)) => p.unknown_reparse_tag = Some(t),
+            Some((_, file_type)) => p.reparse_tag = Some(file_type),
+            None => {},
+        }
+        if ea_store.reparse_tag() == Some(IO_REPARSE_TAG_LX_SYMLINK) {
+            p.symlink = read_lx_symlink(ea_store).ok();
         }
 
         p.lx_dot_ea = vec![];
 
         if let Some(ea_parsed) = ea_parsed {
             for ea in ea_parsed {
-                if ea.name == LXUID.as_bytes() {
-                    p.lxuid = Some(Cow::Owned(ea.get_ea::<u32>().to_owned()));
-                } else if ea.name == LXGID.as_bytes() {
-                    p.lxgid = Some(Cow::Owned(ea.get_ea::<u32>().to_owned()));
-                } else if ea.name == LXMOD.as_bytes() {
-                    p.lxmod = Some(Cow::Owned(ea.get_ea::<u32>().to_owned()));
-                } else if ea.name == LXDEV.as_bytes() {
-                    p.lxdev = Some(Cow::Owned(ea.get_ea::<Lxdev>().to_owned()));
-                } else if ea.name.starts_with(LX_DOT.as_bytes()) {
+                let Some(name) = normalize_ea_name(ea.name, strict) else {
+                    continue;
+                };
+                if name == LXUID.as_bytes() {
+                    p.lxuid = Some(Cow::Owned(ea.get_ea::<u32>()));
+                } else if name == LXGID.as_bytes() {
+                    p.lxgid = Some(Cow::Owned(ea.get_ea::<u32>()));
+                } else if name == LXMOD.as_bytes() {
+                    p.lxmod = Some(Cow::Owned(ea.get_ea::<Mode>()));
+                } else if name == LXDEV.as_bytes() {
+                    p.lxdev = Some(Cow::Owned(ea.get_ea::<Lxdev>()));
+                } else if name.starts_with(LX_DOT.as_bytes()) {
                     p.lx_dot_ea.push(LxDotAttr(EaEntryCow {
                         flags: ea.flags,
                         name: ea.name.to_owned().into(),
@@ -161,7 +287,7 @@ impl<'a> WslFileAttributes<'a> for WslfsParsed<'a> {
         !self.lx_dot_ea.is_empty()
     }
 
-    fn fmt(&self, f: &mut dyn std::io::Write, distro: Option<&Distro>) -> std::io::Result<()> {
+    fn fmt(&self, f: &mut dyn std::io::Write, distro: Option<&Distro>, opts: &FmtOptions) -> std::io::Result<()> {
         //Symlink:                   -> target
         //$LXUID:                    Uid: 0 / user1
         //$LXGID:                    Gid: 0
@@ -170,45 +296,71 @@ impl<'a> WslFileAttributes<'a> for WslfsParsed<'a> {
         //Linux extended attributes(LX.*):
         //  user.xdg.origin.url:     http://example.url
 
+        if opts.only_times {
+            // times of wslfs are NTFS times
+            return Ok(());
+        }
+
         match &self.reparse_tag {
             Some(t) => {
-                f.write_fmt(format_args!("{:28}{}\n", "File Type(Reparse Tag):", &t.name().0))?;
+                let name = if opts.color { crate::posix::ansi_colored(t.name().0, *t as u32) } else { t.name().0.to_owned() };
+                f.write_fmt(format_args!("{}{}\n", opts.label("File Type(Reparse Tag):"), name))?;
                 if *t == StModeType::LNK {
-                    f.write_fmt(format_args!("{:28}-> {}\n", "Symlink:", self.symlink.as_ref().map_or("", String::as_str)))?;
+                    f.write_fmt(format_args!("{}-> {}\n", opts.label("Symlink:"), self.symlink.as_ref().map_or("", String::as_str)))?;
                 }
             },
             None => {},
         };
+        if let Some(t) = self.unknown_reparse_tag {
+            f.write_fmt(format_args!("{}{:#010x} (not WSL)\n", opts.label("Reparse Tag:"), t))?;
+        }
 
         if let Some(l) = &self.lxuid {
             let uid: u32 = **l;
             if let Some(user_name) = distro.and_then(|d| d.user_name(uid)) {
-                f.write_fmt(format_args!("{:28}Uid: {} / {}\n", "$LXUID:", uid, user_name))?;
+                f.write_fmt(format_args!("{}Uid: {} / {}\n", opts.label("$LXUID:"), uid, user_name))?;
             } else {
-                f.write_fmt(format_args!("{:28}Uid: {}\n", "$LXUID:", uid))?;
+                f.write_fmt(format_args!("{}Uid: {}\n", opts.label("$LXUID:"), uid))?;
             }
         }
         if let Some(l) = &self.lxgid {
             let gid: u32 = **l;
             if let Some(group_name) = distro.and_then(|d| d.group_name(gid)) {
-                f.write_fmt(format_args!("{:28}Gid: {} / {}\n", "$LXGID:", gid, group_name))?;
+                f.write_fmt(format_args!("{}Gid: {} / {}\n", opts.label("$LXGID:"), gid, group_name))?;
             } else {
-                f.write_fmt(format_args!("{:28}Gid: {}\n", "$LXGID:", gid))?;
+                f.write_fmt(format_args!("{}Gid: {}\n", opts.label("$LXGID:"), gid))?;
             }
         }
         if let Some(l) = &self.lxmod {
             let mode = *l.as_ref();
-            let access = lsperms(mode);
-            f.write_fmt(format_args!("{:28}Mode: {:06o} Access: {}\n", "$LXMOD:", mode, access))?;
+            f.write_fmt(format_args!("{}Mode: {:06o} Access: {}\n", opts.label("$LXMOD:"), mode, opts.access(mode)))?;
         }
         if let Some(l) = &self.lxdev {
-            f.write_fmt(format_args!("{:28}Device type: {}, {}\n", "$LXDEV:", l.major, l.minor))?;
+            f.write_fmt(format_args!("{}Device type: {}, {}\n", opts.label("$LXDEV:"), l.major, l.minor))?;
         }
 
-        if self.lx_dot_ea.len() > 0 {
+        let fmt_lx_dot = |f: &mut dyn std::io::Write, l: &LxDotAttrCow| -> Result<()> {
+            f.write_fmt(format_args!("{}{}\n", opts.label(&format!("  {}", l.name_display())), l.value_display(opts.max_value_len)))?;
+            match l.check_prefix() {
+                LxeaPrefix::Ok => {},
+                LxeaPrefix::Double => f.write_fmt(format_args!("{}[WARNING] double 'lxea' prefix, fix by `repair`\n", opts.label("")))?,
+                LxeaPrefix::Missing => f.write_fmt(format_args!("{}[WARNING] missing 'lxea' prefix, fix by `repair`\n", opts.label("")))?,
+            }
+            Ok(())
+        };
+        let (internal, user): (Vec<&LxDotAttrCow>, Vec<&LxDotAttrCow>) = self.lx_dot_ea.iter()
+            .filter(|l| opts.shows_name(&l.name_display()))
+            .partition(|l| l.is_internal());
+        if user.len() > 0 {
             f.write("Linux extended attributes(LX.*):\n".as_bytes())?;
-            for l in &self.lx_dot_ea {
-                f.write_fmt(format_args!("  {:26}{}\n", l.name_display(), l.value_display()))?;
+            for l in user {
+                fmt_lx_dot(f, l)?;
+            }
+        }
+        if internal.len() > 0 {
+            f.write("WSL internal attributes(LX.LINUX.ATTR.*), not to be edited:\n".as_bytes())?;
+            for l in internal {
+                fmt_lx_dot(f, l)?;
             }
         }
         Ok(())
@@ -222,8 +374,10 @@ impl<'a> WslFileAttributes<'a> for WslfsParsed<'a> {
         self.lxgid.as_ref().map(|l| *l.as_ref())
     }
 
-    fn get_mode(&self) -> Option<u32> {
+    /// without `$LXMOD`, the type of a WSL reparse tag with default permissions, so a chmod does not make it a regular file
+    fn get_mode(&self) -> Option<Mode> {
         self.lxmod.as_ref().map(|l| *l.as_ref())
+            .or_else(|| self.reparse_tag.map(|t| default_mode_with_tag(&self.basic_file_info, Some(t.tag_id()))))
     }
 
     fn get_dev_major(&self) -> Option<u32> {
@@ -242,7 +396,7 @@ impl<'a> WslFileAttributes<'a> for WslfsParsed<'a> {
         self.lxgid = Some(Cow::Owned(gid));
     }
 
-    fn set_mode(&mut self, mode: u32) {
+    fn set_mode(&mut self, mode: Mode) {
         self.lxmod = Some(Cow::Owned(mode));
     }
 
@@ -258,27 +412,69 @@ impl<'a> WslFileAttributes<'a> for WslfsParsed<'a> {
         self.lxdev = Some(lxdev);
     }
 
+    fn get_times(&self) -> Option<(LxfsTime, LxfsTime, LxfsTime)> {
+        None
+    }
+
+    fn set_times(&mut self, _atime: Option<LxfsTime>, _mtime: Option<LxfsTime>, _ctime: Option<LxfsTime>) {
+        // wslfs uses NTFS times only
+    }
+
+    fn list_attrs(&self) -> Vec<(String, &[u8])> {
+        self.lx_dot_ea.iter().filter(|x| x.is_valid()).map(|x| (x.name_display(), x.value())).collect()
+    }
+
+    // NTFS keeps EA names in upper case, names differ in case only are the same EA
+    fn get_attr(&self, name: &str) -> Option<&[u8]> {
+        self.lx_dot_ea.iter().find(|x| x.is_valid() && x.name_display().eq_ignore_ascii_case(name)).map(|x| x.value())
+    }
+
     fn set_attr(&mut self, name: &str, value: &[u8]) {
-        if let Some(x) = self.lx_dot_ea.iter_mut().filter(|x| x.name_display() == name).next() {
+        if let Some(x) = self.lx_dot_ea.iter_mut().filter(|x| x.name_display().eq_ignore_ascii_case(name)).next() {
             x.set_value(value);
         } else {
             self.lx_dot_ea.push(LxDotAttr::new_owned(name, value));
         }
     }
 
-    fn rm_attr(&mut self, name: &str) {
-        if let Some(x) = self.lx_dot_ea.iter_mut().filter(|x| x.name_display() == name).next() {
+    fn rm_attr(&mut self, name: &str) -> bool {
+        if let Some(x) = self.lx_dot_ea.iter_mut().filter(|x| x.name_display().eq_ignore_ascii_case(name)).next() {
+            let found = x.value_unprefixed().is_some();
             x.set_value_to_rm();
+            found
+        } else {
+            false
         }
     }
 
-    fn save(&mut self, wsl_file: &mut WslFile) -> std::io::Result<()> {
+    fn init_default(&mut self) -> bool {
+        // LX.* EA's need no other metadata
+        false
+    }
+
+    /// a value which really starts with 'lxea' looks like a `Double` prefix, so each fix is accepted alone
+    fn repair(&mut self, accept: &mut dyn FnMut(&str) -> bool) -> Vec<String> {
+        self.lx_dot_ea.iter_mut()
+        .filter_map(|l| {
+            let prefix = l.check_prefix();
+            if prefix == LxeaPrefix::Ok {
+                return None;
+            }
+            let fix = format!("{}: {:?} 'lxea' prefix", l.name_display(), prefix);
+            (accept(&fix) && l.repair_prefix()).then_some(fix)
+        })
+        .collect()
+    }
+
+    fn save(&mut self, ea_store: &mut dyn EaStore) -> std::io::Result<()> {
         use crate::ea_parse::{EaOut, get_buffer};
-        use crate::ntfs_io::write_ea;
 
         let mut ea_out = EaOut::default();
 
-        // Some -> None cannot be processed
+        // Some -> None by `clear` only
+        for field in core::mem::take(&mut self.cleared) {
+            ea_out.remove(field.ea_name().as_bytes());
+        }
         if let Some(Cow::Owned(ref x)) = self.lxuid {
             ea_out.add(LXUID.as_bytes(), get_buffer(x));
         }
@@ -292,6 +488,10 @@ impl<'a> WslFileAttributes<'a> for WslfsParsed<'a> {
             ea_out.add(LXDEV.as_bytes(), get_buffer(x));
         }
 
+        if let Some(lxea) = self.lx_dot_ea.iter().find(|lxea| lxea.0.value.len() > u16::MAX as usize) {
+            return Err(crate::ea_parse::ea_too_large(lxea.0.value.len()));
+        }
+
         self.lx_dot_ea = core::mem::take(&mut self.lx_dot_ea).into_iter().filter(|lxea| {
             if let Cow::Owned(_) = lxea.0.value {
                 ea_out.add_entry(&lxea.0);
@@ -301,7 +501,7 @@ impl<'a> WslFileAttributes<'a> for WslfsParsed<'a> {
             }
         }).collect();
 
-        unsafe { write_ea(wsl_file.file_handle, &ea_out.buffer) }
+        ea_store.write_ea(&ea_out.buffer)
     }
 }
 
@@ -361,8 +561,8 @@ struct ReparseDataBufferLxSymlink {
     link: [u8; 1],
 }
 
-fn read_lx_symlink(file_handle: HANDLE) -> Result<String> {
-    let raw_buf = unsafe { crate::ntfs_io::read_reparse_point(file_handle)? };
+fn read_lx_symlink(ea_store: &dyn EaStore) -> Result<String> {
+    let raw_buf = ea_store.read_reparse_point()?;
 
     let data_idx = offset_of!(ReparseDataBufferLxSymlink, lx_symlink_sig);
     let link_idx = offset_of!(ReparseDataBufferLxSymlink, link);
@@ -395,26 +595,60 @@ fn read_lx_symlink(file_handle: HANDLE) -> Result<String> {
 pub unsafe fn delete_wslfs_reparse_point(wsl_file: &mut WslFile) -> Result<()> {
     assert!(wsl_file.writable);
     assert!(wsl_file.reparse_tag.is_some());
+    if StModeType::from_tag_id(wsl_file.reparse_tag.unwrap()) == StModeType::UNKNOWN {
+        return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, format!("cannot delete reparse tag not from WSL: {:#010x}", wsl_file.reparse_tag.unwrap())));
+    }
+    if wsl_file.preview {
+        return Ok(());
+    }
     delete_reparse_point(wsl_file.file_handle, wsl_file.reparse_tag.unwrap())?;
     wsl_file.reparse_tag = None;
+    wsl_file.close();
     open_file_inner(wsl_file, true)?; // open as normal file
     Ok(())
 }
 
-// only for change wslfs file type
+/// make the reparse tag match the file type, regular files and directories have none,
+/// a symlink keeps its reparse data as there is no target to write
+pub unsafe fn retype_wslfs_reparse_point(wsl_file: &mut WslFile, file_type: StModeType) -> Result<()> {
+    match file_type {
+        StModeType::REG | StModeType::DIR => if wsl_file.reparse_tag.is_some() {
+            delete_wslfs_reparse_point(wsl_file)
+        } else {
+            Ok(())
+        },
+        StModeType::LNK => if wsl_file.reparse_tag == Some(IO_REPARSE_TAG_LX_SYMLINK) {
+            Ok(())
+        } else {
+            Err(std::io::Error::new(std::io::ErrorKind::InvalidData, "cannot retype to a symlink without a symlink target"))
+        },
+        _ => set_wslfs_reparse_point(wsl_file, file_type, None),
+    }
+}
+
+// only for change wslfs file type,
+// WSL keeps symlinks and special files as NTFS files, a symlink to a directory like `/lib -> usr/lib` too
 pub unsafe fn set_wslfs_reparse_point(wsl_file: &mut WslFile, tag: StModeType, symlink: Option<&str>) -> Result<()> {
     assert!(wsl_file.writable);
 
+    if wsl_file.is_dir() {
+        return Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, "cannot set a WSL reparse tag on a directory, WSL reads it as a file"));
+    }
+
     let reparse_tag_id = tag.tag_id();
+    if let Some(t) = wsl_file.reparse_tag.filter(|t| StModeType::from_tag_id(*t) == StModeType::UNKNOWN) {
+        return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, format!("cannot rewrite reparse tag not from WSL: {:#010x}", t)));
+    }
+    if wsl_file.preview {
+        return Ok(());
+    }
     if let Some(t) = wsl_file.reparse_tag {
         if t != reparse_tag_id {
             delete_reparse_point(wsl_file.file_handle, t)?;
             wsl_file.reparse_tag = None;
         }
-    } else {
-        // TODO: reopen with reparse data
-        return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, "cannot add reparse point"));
     }
+    // a normal file handle keeps working after the reparse point is added, no need to reopen
 
     let mut buf = match &tag {
         StModeType::LNK => {
@@ -443,3 +677,332 @@ pub unsafe fn set_wslfs_reparse_point(wsl_file: &mut WslFile, tag: StModeType, s
     wsl_file.reparse_tag = Some(reparse_tag_id);
     Ok(())
 }
+
+#[test]
+fn test_chmod_chown_special_files() {
+    use crate::ea_parse::parse_ea;
+    use crate::wsl_file::open_handle;
+
+    for t in [StModeType::FIFO, StModeType::CHR, StModeType::BLK, StModeType::SOCK] {
+        let path = std::env::temp_dir().join(format!("wslattr_test_special_file_{:?}", t));
+        let _ = std::fs::remove_file(&path);
+        std::fs::write(&path, b"").unwrap();
+
+        let mut wsl_file = unsafe { open_handle(&path, true) }.unwrap();
+        unsafe { set_wslfs_reparse_point(&mut wsl_file, t, None) }.unwrap();
+        drop(wsl_file);
+
+        let mut wsl_file = unsafe { open_handle(&path, false) }.unwrap();
+        assert_eq!(wsl_file.reparse_tag, Some(t.tag_id()));
+        wsl_file.reopen_to_write().unwrap();
+
+        let ea_parsed = None;
+        let mut wslfs = WslfsParsed::load(&wsl_file, &ea_parsed);
+        wslfs.set_uid(1000);
+        wslfs.set_gid(1001);
+        wslfs.set_mode(Mode(t as u32 | 0o640));
+        wslfs.set_attr("user.test", b"value");
+        wslfs.save(&mut wsl_file).unwrap();
+        drop(wsl_file);
+
+        let wsl_file = unsafe { open_handle(&path, false) }.unwrap();
+        let ea_buffer = wsl_file.read_ea().unwrap();
+        let ea_parsed = ea_buffer.as_ref().map(|ea_buffer| parse_ea(ea_buffer));
+        let wslfs = WslfsParsed::load(&wsl_file, &ea_parsed);
+        let result = (wslfs.reparse_tag, wslfs.get_uid(), wslfs.get_gid(), wslfs.get_mode(), wslfs.lx_dot_ea.len());
+        drop(wsl_file);
+        let _ = std::fs::remove_file(&path);
+
+        assert_eq!(result, (Some(t), Some(1000), Some(1001), Some(Mode(t as u32 | 0o640)), 1), "{:?}", t);
+    }
+}
+
+#[test]
+fn test_symlink_to_dir() {
+    use crate::wsl_file::open_handle;
+
+    let path = std::env::temp_dir().join("wslattr_test_symlink_to_dir");
+    let _ = std::fs::remove_file(&path);
+    std::fs::write(&path, b"").unwrap();
+    let mut wsl_file = unsafe { open_handle(&path, true) }.unwrap();
+    unsafe { set_wslfs_reparse_point(&mut wsl_file, StModeType::LNK, Some("usr/lib")) }.unwrap();
+    drop(wsl_file);
+
+    let wsl_file = unsafe { open_handle(&path, false) }.unwrap();
+    let ea_parsed = None;
+    let wslfs = WslfsParsed::load(&wsl_file, &ea_parsed);
+    let result = (wslfs.reparse_tag, wslfs.symlink.clone());
+    drop(wsl_file);
+    let _ = std::fs::remove_file(&path);
+    assert_eq!(result, (Some(StModeType::LNK), Some("usr/lib".to_owned())));
+
+    let dir = std::env::temp_dir().join("wslattr_test_symlink_dir_refused");
+    let _ = std::fs::remove_dir(&dir);
+    std::fs::create_dir(&dir).unwrap();
+    let mut wsl_file = unsafe { open_handle(&dir, true) }.unwrap();
+    let result = unsafe { set_wslfs_reparse_point(&mut wsl_file, StModeType::LNK, Some("usr/lib")) };
+    drop(wsl_file);
+    let _ = std::fs::remove_dir(&dir);
+    assert!(result.is_err());
+}
+
+#[test]
+fn test_save_load_round_trip() {
+    use crate::ea_parse::parse_ea;
+    use crate::wsl_file::MemEaStore;
+
+    let mut ea_store = MemEaStore::default();
+    let ea_parsed = None;
+    let mut wslfs = WslfsParsed::load(&ea_store, &ea_parsed);
+    wslfs.set_uid(1000);
+    wslfs.set_mode(Mode(0o100600));
+    wslfs.set_attr("user.a", b"1");
+    wslfs.set_attr("user.b", b"2");
+    wslfs.save(&mut ea_store).unwrap();
+
+    let ea_buffer = ea_store.read_ea().unwrap();
+    let ea_parsed = ea_buffer.as_ref().map(|ea_buffer| parse_ea(ea_buffer));
+    let mut wslfs = WslfsParsed::load(&ea_store, &ea_parsed);
+    assert_eq!(wslfs.get_uid(), Some(1000));
+    assert_eq!(wslfs.get_gid(), None);
+    assert_eq!(wslfs.get_mode(), Some(Mode(0o100600)));
+    assert_eq!(wslfs.lx_dot_ea.iter().map(|x| x.name_display()).collect::<Vec<_>>(), ["user.a", "user.b"]);
+
+    wslfs.rm_attr("user.a");
+    wslfs.save(&mut ea_store).unwrap();
+    assert_eq!(wslfs.lx_dot_ea.len(), 1);
+    assert_eq!(ea_store.eas.len(), 3);
+}
+
+#[test]
+fn test_load_unknown_reparse_tag() {
+    use crate::wsl_file::MemEaStore;
+
+    // IO_REPARSE_TAG_CLOUD_6 from OneDrive
+    let ea_store = MemEaStore { reparse_tag: Some(0x9000601a), ..Default::default() };
+    let ea_parsed = None;
+    let wslfs = WslfsParsed::load(&ea_store, &ea_parsed);
+    assert_eq!(wslfs.reparse_tag, None);
+    assert_eq!(wslfs.unknown_reparse_tag, Some(0x9000601a));
+    assert!(!wslfs.maybe());
+
+    let mut out = vec![];
+    wslfs.fmt(&mut out, None, &FmtOptions::default()).unwrap();
+    assert_eq!(String::from_utf8(out).unwrap(), format!("{:28}0x9000601a (not WSL)\n", "Reparse Tag:"));
+
+    let ea_store = MemEaStore { reparse_tag: Some(IO_REPARSE_TAG_LX_FIFO), ..Default::default() };
+    let wslfs = WslfsParsed::load(&ea_store, &ea_parsed);
+    assert_eq!(wslfs.reparse_tag, Some(StModeType::FIFO));
+    assert_eq!(wslfs.unknown_reparse_tag, None);
+}
+
+#[test]
+fn test_get_attr() {
+    let mut wslfs = WslfsParsed::default();
+    assert_eq!(wslfs.get_attr("user.test"), None);
+    wslfs.set_attr("user.test", b"value");
+    assert_eq!(wslfs.get_attr("user.test"), Some(b"value".as_slice()));
+    wslfs.rm_attr("user.test");
+    assert_eq!(wslfs.get_attr("user.test"), None);
+}
+
+#[test]
+fn test_lxea_prefix() {
+    let single = LxDotAttr::new_owned("user.test", b"value");
+    assert_eq!(single.check_prefix(), LxeaPrefix::Ok);
+
+    let mut double = LxDotAttr::new_owned("user.test", b"lxeavalue");
+    assert_eq!(double.check_prefix(), LxeaPrefix::Double);
+    assert!(double.repair_prefix());
+    assert_eq!(double.check_prefix(), LxeaPrefix::Ok);
+    assert_eq!(double.value(), b"value");
+
+    let mut missing = LxDotAttr::new_owned("user.test", b"");
+    missing.0.value = Cow::Borrowed(b"value");
+    assert_eq!(missing.check_prefix(), LxeaPrefix::Missing);
+    assert!(missing.repair_prefix());
+    assert_eq!(missing.value(), b"value");
+    assert!(!missing.repair_prefix());
+
+    let mut removed = LxDotAttr::new_owned("user.test", b"");
+    removed.set_value_to_rm();
+    assert_eq!(removed.check_prefix(), LxeaPrefix::Ok);
+
+    let mut wslfs = WslfsParsed::default();
+    wslfs.lx_dot_ea.push(single);
+    wslfs.lx_dot_ea.push(LxDotAttr::new_owned("user.double", b"lxeavalue"));
+    // declined, a value which really starts with 'lxea' is kept
+    let mut asked = vec![];
+    assert!(wslfs.repair(&mut |fix| { asked.push(fix.to_owned()); false }).is_empty());
+    assert_eq!(asked, ["user.double: Double 'lxea' prefix"]);
+    assert_eq!(wslfs.get_attr("user.double"), Some(b"lxeavalue".as_slice()));
+    assert_eq!(wslfs.repair(&mut |_| true), vec!["user.double: Double 'lxea' prefix".to_owned()]);
+    assert_eq!(wslfs.get_attr("user.double"), Some(b"value".as_slice()));
+    assert!(wslfs.repair(&mut |_| true).is_empty());
+}
+
+#[test]
+fn test_internal_attr() {
+    assert!(is_internal_attr_name("linux.attr.x"));
+    assert!(is_internal_attr_name("LINUX.ATTR.x"));
+    assert!(!is_internal_attr_name("user.linux.attr.x"));
+
+    let mut wslfs = WslfsParsed::default();
+    wslfs.set_attr("user.test", b"value");
+    wslfs.lx_dot_ea.push(LxDotAttr::new_owned("linux.attr.x", b"internal"));
+    assert!(!wslfs.lx_dot_ea[0].is_internal());
+    assert!(wslfs.lx_dot_ea[1].is_internal());
+
+    let mut out = vec![];
+    wslfs.fmt(&mut out, None, &FmtOptions::default()).unwrap();
+    let out = String::from_utf8(out).unwrap();
+    let user = out.find("Linux extended attributes(LX.*):\n").unwrap();
+    let internal = out.find("WSL internal attributes(LX.LINUX.ATTR.*), not to be edited:\n").unwrap();
+    assert!(user < out.find("user.test").unwrap() && out.find("user.test").unwrap() < internal);
+    assert!(internal < out.find("linux.attr.x").unwrap());
+}
+
+#[test]
+fn test_repair_lxea_prefix_saved() {
+    use crate::ea_parse::{parse_ea, EaOut};
+    use crate::wsl_file::MemEaStore;
+
+    let mut ea_out = EaOut::default();
+    ea_out.add(b"LX.user.missing", b"value1");
+    ea_out.add(b"LX.user.double", b"lxealxeavalue2");
+    ea_out.add(b"LX.user.ok", b"lxeavalue3");
+    let mut ea_store = MemEaStore::from_ea_buffer(&ea_out.buffer);
+
+    let ea_buffer = ea_store.read_ea().unwrap();
+    let ea_parsed = ea_buffer.as_ref().map(|ea_buffer| parse_ea(ea_buffer));
+    let mut wslfs = WslfsParsed::load(&ea_store, &ea_parsed);
+    assert_eq!(wslfs.repair(&mut |_| true), vec![
+        "user.missing: Missing 'lxea' prefix".to_owned(),
+        "user.double: Double 'lxea' prefix".to_owned(),
+    ]);
+    wslfs.save(&mut ea_store).unwrap();
+
+    let ea_buffer = ea_store.read_ea().unwrap();
+    let ea_parsed = ea_buffer.as_ref().map(|ea_buffer| parse_ea(ea_buffer));
+    let mut wslfs = WslfsParsed::load(&ea_store, &ea_parsed);
+    assert_eq!(wslfs.get_attr("user.missing"), Some(b"value1".as_slice()));
+    assert_eq!(wslfs.get_attr("user.double"), Some(b"value2".as_slice()));
+    assert_eq!(wslfs.get_attr("user.ok"), Some(b"value3".as_slice()));
+    assert!(wslfs.repair(&mut |_| true).is_empty());
+}
+
+#[test]
+fn test_load_ea_name_with_nul() {
+    use crate::ea_parse::{parse_ea, EaOut};
+    use crate::wsl_file::MemEaStore;
+
+    let mut ea_out = EaOut::default();
+    ea_out.add(b"$LXUID\0", &1000u32.to_le_bytes());
+    ea_out.add(b"$LXGID ", &1000u32.to_le_bytes());
+    let ea_parsed = Some(parse_ea(&ea_out.buffer));
+    let ea_store = MemEaStore::default();
+
+    let wslfs = WslfsParsed::load_with(&ea_store, &ea_parsed, false);
+    assert_eq!(wslfs.get_uid(), Some(1000));
+    assert_eq!(wslfs.get_gid(), Some(1000));
+
+    let wslfs = WslfsParsed::load_with(&ea_store, &ea_parsed, true);
+    assert_eq!(wslfs.get_uid(), None);
+    assert_eq!(wslfs.get_gid(), None);
+    assert!(!wslfs.maybe());
+}
+
+#[test]
+fn test_clear_field() {
+    use crate::ea_parse::parse_ea;
+    use crate::wsl_file::MemEaStore;
+
+    let mut ea_store = MemEaStore::default();
+    let mut wslfs = WslfsParsed::default();
+    wslfs.set_uid(1000);
+    wslfs.set_gid(1000);
+    wslfs.set_mode(Mode(0o100644));
+    wslfs.set_dev_major(1);
+    wslfs.set_dev_minor(3);
+    wslfs.save(&mut ea_store).unwrap();
+    assert_eq!(ea_store.eas.len(), 4);
+
+    let ea_buffer = ea_store.read_ea().unwrap();
+    let ea_parsed = ea_buffer.as_ref().map(|ea_buffer| parse_ea(ea_buffer));
+    let mut wslfs = WslfsParsed::load(&ea_store, &ea_parsed);
+    assert!(wslfs.clear(WslfsField::Dev));
+    assert!(!wslfs.clear(WslfsField::Dev));
+    wslfs.save(&mut ea_store).unwrap();
+    let names: Vec<&[u8]> = ea_store.eas.iter().map(|(name, _)| name.as_slice()).collect();
+    assert_eq!(names, [LXUID.as_bytes(), LXGID.as_bytes(), LXMOD.as_bytes()]);
+
+    let ea_buffer = ea_store.read_ea().unwrap();
+    let ea_parsed = ea_buffer.as_ref().map(|ea_buffer| parse_ea(ea_buffer));
+    let wslfs = WslfsParsed::load(&ea_store, &ea_parsed);
+    assert_eq!(wslfs.get_dev_major(), None);
+    assert_eq!(wslfs.get_uid(), Some(1000));
+    assert_eq!(wslfs.get_mode(), Some(Mode(0o100644)));
+}
+
+#[test]
+fn test_empty_attr_value() {
+    use crate::ea_parse::parse_ea;
+    use crate::wsl_file::MemEaStore;
+
+    let mut ea_store = MemEaStore::default();
+    let mut wslfs = WslfsParsed::default();
+    wslfs.set_attr("user.empty", b"");
+    wslfs.set_attr("user.removed", b"value");
+    wslfs.save(&mut ea_store).unwrap();
+    // empty value is stored as the 'lxea' prefix only
+    assert!(ea_store.eas.iter().any(|(name, value)| name == b"LX.USER.EMPTY" && value == LXEA));
+
+    let ea_buffer = ea_store.read_ea().unwrap();
+    let ea_parsed = ea_buffer.as_ref().map(|ea_buffer| parse_ea(ea_buffer));
+    let mut wslfs = WslfsParsed::load(&ea_store, &ea_parsed);
+    assert_eq!(wslfs.get_attr("user.empty"), Some(b"".as_slice()));
+    wslfs.rm_attr("user.removed");
+    wslfs.save(&mut ea_store).unwrap();
+    assert_eq!(wslfs.get_attr("user.removed"), None);
+    assert_eq!(ea_store.eas.len(), 1);
+}
+
+#[test]
+fn test_mode_from_reparse_tag() {
+    use crate::wsl_file::MemEaStore;
+
+    let ea_parsed = None;
+    let ea_store = MemEaStore { reparse_tag: Some(IO_REPARSE_TAG_LX_CHR), ..Default::default() };
+    let mut wslfs = WslfsParsed::load(&ea_store, &ea_parsed);
+    assert!(wslfs.lxmod.is_none());
+    assert_eq!(wslfs.get_mode(), Some(Mode(0o020644)));
+    assert_eq!(wslfs.get_mode().unwrap().file_type(), StModeType::CHR);
+    wslfs.set_mode(Mode(0o020600));
+    assert_eq!(wslfs.get_mode(), Some(Mode(0o020600)));
+
+    let ea_store = MemEaStore { reparse_tag: Some(IO_REPARSE_TAG_LX_FIFO), ..Default::default() };
+    assert_eq!(WslfsParsed::load(&ea_store, &ea_parsed).get_mode().map(|m| m.file_type()), Some(StModeType::FIFO));
+
+    // no WSL type to keep
+    let ea_store = MemEaStore { reparse_tag: Some(0x9000601a), ..Default::default() };
+    assert_eq!(WslfsParsed::load(&ea_store, &ea_parsed).get_mode(), None);
+}
+
+#[test]
+fn test_rm_attr_broken_prefix() {
+    use crate::ea_parse::{parse_ea, EaOut};
+    use crate::wsl_file::MemEaStore;
+
+    let mut ea_out = EaOut::default();
+    ea_out.add(b"LX.user.missing", b"value1");
+    let mut ea_store = MemEaStore::from_ea_buffer(&ea_out.buffer);
+
+    let ea_buffer = ea_store.read_ea().unwrap();
+    let ea_parsed = ea_buffer.as_ref().map(|ea_buffer| parse_ea(ea_buffer));
+    let mut wslfs = WslfsParsed::load(&ea_store, &ea_parsed);
+    assert_eq!(wslfs.get_attr("user.missing"), None);
+    assert!(wslfs.rm_attr("user.missing"));
+    assert!(!wslfs.rm_attr("user.none"));
+    wslfs.save(&mut ea_store).unwrap();
+    assert!(ea_store.eas.is_empty());
+}